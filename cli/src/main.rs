@@ -1,13 +1,15 @@
 use std::fs;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::{Parser, Subcommand};
 use dotenv::dotenv;
 use log::{debug, info};
 use walletka_core::{
     bdk::bitcoin::Network,
     builder::WalletkaBuilder,
+    enums::{FeePolicy, MessageSignatureFormat},
     io::database::DatabaseStore,
+    types::{BatchRecipient, TransactionFilter},
     utils::{generate_mnemonic, load_mnemonic, save_mnemonic},
 };
 
@@ -28,10 +30,158 @@ enum Commands {
         light: Option<bool>,
     },
     Address,
+    /// BIP21 URI payable onchain, optionally by Cashu or RGB
+    ReceiveUri {
+        amount_sat: Option<u64>,
+        #[arg(long)]
+        label: Option<String>,
+        #[arg(long)]
+        message: Option<String>,
+        /// Accept Cashu token sent to nostr public key
+        #[arg(long)]
+        cashu: bool,
+        #[arg(long)]
+        rgb_invoice: Option<String>,
+    },
+    BitcoinSweepLegacy,
+    BitcoinSend {
+        address: String,
+        amount_sat: u64,
+        /// Fee rate in sat/vB
+        #[arg(long)]
+        fee_rate: Option<f32>,
+        /// Confirmation target in blocks used for fee estimation
+        #[arg(long)]
+        target_blocks: Option<u32>,
+        /// Only show fee, size and inputs of the transaction
+        #[arg(long)]
+        dry_run: bool,
+        /// Spend only given utxos (txid:vout)
+        #[arg(long)]
+        utxo: Vec<String>,
+    },
+    BitcoinSendBatch {
+        /// Recipients as address:amount_sat
+        recipients: Vec<String>,
+        /// Send all remaining funds to this address
+        #[arg(long)]
+        send_max_to: Option<String>,
+        /// Fee rate in sat/vB
+        #[arg(long)]
+        fee_rate: Option<f32>,
+    },
+    BitcoinSweepWif {
+        wif: String,
+        /// Destination address, this wallet by default
+        #[arg(long)]
+        destination: Option<String>,
+        /// Fee rate in sat/vB
+        #[arg(long)]
+        fee_rate: Option<f32>,
+    },
+    /// Pay bitcoin address or URI, RGB invoice or nostr npub by the best layer
+    Pay {
+        destination: String,
+        /// Amount in sats or RGB asset units, when destination doesn't contain it
+        amount: Option<u64>,
+        /// Fee rate in sat/vB
+        #[arg(long)]
+        fee_rate: Option<f32>,
+    },
+    /// Pay bitcoin URI, with payjoin when it contains pj endpoint
+    PayjoinSend {
+        uri: String,
+        /// Fee rate in sat/vB
+        #[arg(long)]
+        fee_rate: Option<f32>,
+    },
+    /// Receive payjoin on local HTTP endpoint
+    PayjoinReceive {
+        amount_sat: Option<u64>,
+        #[arg(long, default_value = "127.0.0.1:3010")]
+        listen: String,
+    },
+    MessageSign {
+        address: String,
+        message: String,
+        /// Use BIP137 signature instead of BIP322 simple
+        #[arg(long)]
+        bip137: bool,
+    },
+    MessageVerify {
+        address: String,
+        message: String,
+        signature: String,
+    },
+    SilentPaymentAddress,
+    SilentPaymentScan {
+        from_height: u32,
+        to_height: Option<u32>,
+    },
+    SilentPaymentSweep {
+        /// Fee rate in sat/vB
+        #[arg(long)]
+        fee_rate: Option<f32>,
+    },
+    BitcoinDrain {
+        address: String,
+        /// Fee rate in sat/vB
+        #[arg(long)]
+        fee_rate: Option<f32>,
+    },
+    BitcoinFreezeUtxo {
+        outpoint: String,
+        frozen: Option<bool>,
+    },
+    BitcoinLabelUtxo {
+        outpoint: String,
+        label: Option<String>,
+    },
+    PsbtCreate {
+        address: String,
+        amount_sat: u64,
+        /// Fee rate in sat/vB
+        #[arg(long)]
+        fee_rate: Option<f32>,
+        /// Spend only given utxos (txid:vout)
+        #[arg(long)]
+        utxo: Vec<String>,
+    },
+    PsbtSign {
+        psbt: String,
+    },
+    PsbtCombine {
+        psbts: Vec<String>,
+    },
+    PsbtFinalize {
+        psbt: String,
+    },
+    PsbtBroadcast {
+        psbt: String,
+    },
+    BitcoinBumpFee {
+        txid: String,
+        /// New fee rate in sat/vB
+        fee_rate: f32,
+    },
+    BitcoinCpfp {
+        txid: String,
+        /// Fee rate of parent and child in sat/vB
+        fee_rate: f32,
+    },
     Assets,
     Balance {
         currency_symbol: Option<String>,
     },
+    History {
+        /// Show only transactions of currency
+        #[arg(long)]
+        currency_symbol: Option<String>,
+        #[arg(long, default_value_t = 0)]
+        offset: u32,
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+    },
     Contacts,
     ImportContacts {
         npub: String,
@@ -47,6 +197,26 @@ enum Commands {
         keyset_id: String,
         amount_sat: u64,
     },
+    /// Reclaim sent token the recipient didn't redeem
+    CashuReclaim {
+        /// Hash of the token
+        token_id: String,
+    },
+    /// Pay Lightning invoice by ecash of the keyset
+    CashuMelt {
+        keyset_id: String,
+        invoice: String,
+    },
+    /// Request Lightning invoice minting ecash once paid
+    CashuMintRequest {
+        mint_url: String,
+        amount_sat: u64,
+    },
+    /// Check mint quote and mint ecash if its invoice is paid
+    CashuMintQuote {
+        quote_id: String,
+    },
+    CashuMintQuotes,
     RgbCreateUtxos,
     RgbCreateAssetNia {
         ticker: String,
@@ -97,7 +267,7 @@ async fn main() -> Result<()> {
         data_path,
         vec![nostr_relay_url],
         Some("130.61.74.161:50001".to_string()),
-        Some("esplora.tchaicash.space:443".to_string()),
+        Some("https://esplora.tchaicash.space:443".to_string()),
         Some("rpc://rgb.tchaicash.space:443".to_string()), // Todo: Some("rgb.tchaicash.space:443".to_string()),
     );
 
@@ -109,12 +279,208 @@ async fn main() -> Result<()> {
         Commands::Info => todo!(),
         Commands::Sync { light } => {
             debug!("Syncing Walletka");
-            walletka.sync(light.unwrap_or(false)).await?;
+            let mut events = walletka.subscribe();
+            let report = walletka.sync(light.unwrap_or(false)).await?;
+            for layer in report.layers {
+                info!(
+                    "{:?}: {:?} in {} ms, {} changes",
+                    layer.layer, layer.status, layer.duration_ms, layer.changes
+                );
+            }
+            while let Ok(event) = events.try_recv() {
+                info!("Event: {:?}", event);
+            }
         }
         Commands::Address => {
             let address = walletka.get_bitcoin_address()?;
             dbg!(address);
         }
+        Commands::ReceiveUri {
+            amount_sat,
+            label,
+            message,
+            cashu,
+            rgb_invoice,
+        } => {
+            let uri = walletka.create_bip21_uri(amount_sat, label, message, cashu, rgb_invoice)?;
+            info!("Payment URI: {}", uri);
+        }
+        Commands::BitcoinSweepLegacy => match walletka.sweep_legacy_bitcoin_wallet()? {
+            Some(txid) => info!("Legacy wallet swept: {}", txid),
+            None => info!("Nothing to sweep"),
+        },
+        Commands::BitcoinSend {
+            address,
+            amount_sat,
+            fee_rate,
+            target_blocks,
+            dry_run,
+            utxo,
+        } => {
+            let fee_policy = match (fee_rate, target_blocks) {
+                (Some(sat_per_vb), _) => Some(FeePolicy::FeeRate { sat_per_vb }),
+                (None, Some(blocks)) => Some(FeePolicy::ConfirmationTarget { blocks }),
+                (None, None) => None,
+            };
+
+            if dry_run {
+                let preview =
+                    walletka.preview_bitcoin_payment(address, amount_sat, fee_policy, utxo)?;
+                dbg!(preview);
+            } else {
+                let txid = walletka.pay_to_address(address, amount_sat, fee_policy, utxo)?;
+                info!("Transaction sent: {}", txid);
+            }
+        }
+        Commands::BitcoinSendBatch {
+            recipients,
+            send_max_to,
+            fee_rate,
+        } => {
+            let recipients = recipients
+                .iter()
+                .map(|r| match r.split_once(':') {
+                    Some((address, amount_sat)) => Ok(BatchRecipient {
+                        address: address.to_string(),
+                        amount_sat: amount_sat.parse()?,
+                    }),
+                    None => bail!("Invalid recipient {}, use address:amount_sat", r),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let fee_policy = fee_rate.map(|sat_per_vb| FeePolicy::FeeRate { sat_per_vb });
+
+            let result = walletka
+                .pay_batch(recipients, send_max_to, fee_policy)
+                .await?;
+            info!("Transaction sent: {}", result.txid);
+            dbg!(result);
+        }
+        Commands::BitcoinSweepWif {
+            wif,
+            destination,
+            fee_rate,
+        } => {
+            let fee_policy = fee_rate.map(|sat_per_vb| FeePolicy::FeeRate { sat_per_vb });
+
+            let txids = walletka.sweep_wif(wif, destination, fee_policy)?;
+            info!("Private key swept: {:?}", txids);
+        }
+        Commands::Pay {
+            destination,
+            amount,
+            fee_rate,
+        } => {
+            let fee_policy = fee_rate.map(|sat_per_vb| FeePolicy::FeeRate { sat_per_vb });
+
+            let parsed = walletka.parse_destination(destination.clone())?;
+            debug!("Paying {:?}", parsed);
+
+            let receipt = walletka.pay(destination, amount, fee_policy).await?;
+            info!("Paid: {:?}", receipt);
+        }
+        Commands::PayjoinSend { uri, fee_rate } => {
+            let fee_policy = fee_rate.map(|sat_per_vb| FeePolicy::FeeRate { sat_per_vb });
+
+            let txid = walletka.pay_payjoin_uri(uri, fee_policy)?;
+            info!("Transaction sent: {}", txid);
+        }
+        Commands::PayjoinReceive { amount_sat, listen } => {
+            let uri = walletka.get_payjoin_uri(format!("http://{}/payjoin", listen), amount_sat)?;
+            info!("Payjoin URI: {}", uri);
+
+            walletka.serve_payjoin(listen)?;
+            info!("Payjoin proposal sent");
+        }
+        Commands::MessageSign {
+            address,
+            message,
+            bip137,
+        } => {
+            let format = match bip137 {
+                true => MessageSignatureFormat::Bip137,
+                false => MessageSignatureFormat::Bip322Simple,
+            };
+
+            let signature = walletka.sign_message(address, message, format)?;
+            info!("Signature: {}", signature);
+        }
+        Commands::MessageVerify {
+            address,
+            message,
+            signature,
+        } => {
+            let valid = walletka.verify_message(address, message, signature)?;
+            info!("Signature valid: {}", valid);
+        }
+        Commands::SilentPaymentAddress => {
+            let address = walletka.get_silent_payment_address()?;
+            info!("Silent payment address: {}", address);
+        }
+        Commands::SilentPaymentScan {
+            from_height,
+            to_height,
+        } => {
+            let outpoints = walletka
+                .scan_silent_payments(from_height, to_height)
+                .await?;
+            info!("Silent payments found: {:#?}", outpoints);
+        }
+        Commands::SilentPaymentSweep { fee_rate } => {
+            let fee_policy = fee_rate.map(|sat_per_vb| FeePolicy::FeeRate { sat_per_vb });
+
+            match walletka.sweep_silent_payments(fee_policy).await? {
+                Some(txid) => info!("Transaction sent: {}", txid),
+                None => info!("No silent payment to sweep"),
+            }
+        }
+        Commands::BitcoinDrain { address, fee_rate } => {
+            let fee_policy = fee_rate.map(|sat_per_vb| FeePolicy::FeeRate { sat_per_vb });
+
+            let txid = walletka.drain_to(address, fee_policy)?;
+            info!("Transaction sent: {}", txid);
+        }
+        Commands::BitcoinFreezeUtxo { outpoint, frozen } => {
+            walletka
+                .set_utxo_frozen(outpoint, frozen.unwrap_or(true))
+                .await?;
+        }
+        Commands::BitcoinLabelUtxo { outpoint, label } => {
+            walletka.set_utxo_label(outpoint, label).await?;
+        }
+        Commands::PsbtCreate {
+            address,
+            amount_sat,
+            fee_rate,
+            utxo,
+        } => {
+            let fee_policy = fee_rate.map(|sat_per_vb| FeePolicy::FeeRate { sat_per_vb });
+            let psbt = walletka.create_psbt(address, amount_sat, fee_policy, utxo)?;
+            println!("{}", psbt);
+        }
+        Commands::PsbtSign { psbt } => {
+            let psbt = walletka.sign_psbt_base64(psbt)?;
+            println!("{}", psbt);
+        }
+        Commands::PsbtCombine { psbts } => {
+            let psbt = walletka.combine_psbts(psbts)?;
+            println!("{}", psbt);
+        }
+        Commands::PsbtFinalize { psbt } => {
+            let tx_hex = walletka.finalize_psbt(psbt)?;
+            println!("{}", tx_hex);
+        }
+        Commands::PsbtBroadcast { psbt } => {
+            let txid = walletka.broadcast_psbt(psbt)?;
+            info!("Transaction sent: {}", txid);
+        }
+        Commands::BitcoinBumpFee { txid, fee_rate } => {
+            let txid = walletka.bump_fee(txid, fee_rate)?;
+            info!("Replacement transaction sent: {}", txid);
+        }
+        Commands::BitcoinCpfp { txid, fee_rate } => {
+            let txid = walletka.cpfp(txid, fee_rate)?;
+            info!("Child transaction sent: {}", txid);
+        }
         Commands::Assets => {
             let assets = walletka.get_assets().await?;
             dbg!(assets);
@@ -123,6 +489,19 @@ async fn main() -> Result<()> {
             let balance = walletka.get_balance(currency_symbol).await?;
             info!("Balance: {:#? }", balance);
         }
+        Commands::History {
+            currency_symbol,
+            offset,
+            limit,
+        } => {
+            let filter = TransactionFilter {
+                currency_symbol,
+                ..Default::default()
+            };
+
+            let transactions = walletka.get_transactions(filter, offset, limit).await?;
+            info!("Transactions: {:#?}", transactions);
+        }
         Commands::Contacts => {
             // let contacts = walletka.get_all_contacts().await?;
             // dbg!(contacts);
@@ -156,6 +535,29 @@ async fn main() -> Result<()> {
                 .await?;
             dbg!(token);
         }
+        Commands::CashuReclaim { token_id } => {
+            let item = walletka.reclaim_cashu_token(token_id).await?;
+            dbg!(item);
+        }
+        Commands::CashuMelt { keyset_id, invoice } => {
+            let receipt = walletka.melt_cashu(invoice, keyset_id).await?;
+            dbg!(receipt);
+        }
+        Commands::CashuMintRequest {
+            mint_url,
+            amount_sat,
+        } => {
+            let quote = walletka.request_cashu_mint(mint_url, amount_sat).await?;
+            dbg!(quote);
+        }
+        Commands::CashuMintQuote { quote_id } => {
+            let quote = walletka.check_cashu_mint_quote(quote_id).await?;
+            dbg!(quote);
+        }
+        Commands::CashuMintQuotes => {
+            let quotes = walletka.get_cashu_mint_quotes().await?;
+            dbg!(quotes);
+        }
         Commands::RgbCreateUtxos => {
             walletka.create_rgb_utxos()?;
             info!("Utxos created");
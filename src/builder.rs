@@ -1,14 +1,20 @@
-use anyhow::Result;
-use bdk::bitcoin::Network;
-use bdk::blockchain::{AnyBlockchain, ElectrumBlockchain};
-use bdk::electrum_client::Client;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use bdk::bitcoin::{Network, OutPoint};
 use bdk::keys::bip39::Mnemonic;
-use log::debug;
+use itertools::Itertools;
+use log::{debug, warn};
 
-use crate::io::clients::NostrClient;
+use crate::enums::WatchOnlySource;
+use crate::errors::WalletkaError;
+use crate::io::clients::{BlockchainClient, ChainBackend, NostrClient};
+use crate::io::repositories::bitcoin_repository::BitcoinRepository;
 use crate::io::repositories::cashu_repository::CashuRepository;
+use crate::io::repositories::transaction_respository::TransactionRepository;
+use crate::types::BitcoinAccount;
 use crate::wallets::bitcoin::BitcoinWallet;
-use crate::wallets::cashu::CashuWallet;
+use crate::wallets::cashu::{reclaim_duration, CashuWallet};
 use crate::wallets::rgb::RgbWallet;
 use crate::{
     io::{
@@ -30,6 +36,10 @@ pub struct WalletkaBuilder {
     pub electrum_url: Option<String>,
     pub esplora_url: Option<String>,
     pub rgb_transport_url: Option<String>,
+    pub bitcoin_accounts: Vec<BitcoinAccount>,
+    pub chain_backends: Vec<ChainBackend>,
+    pub watch_only: Option<WatchOnlySource>,
+    pub cashu_reclaim_after_secs: Option<u64>,
 }
 
 // Todo Needed?
@@ -46,6 +56,10 @@ impl Default for WalletkaBuilder {
             electrum_url: None,
             esplora_url: None,
             rgb_transport_url: None,
+            bitcoin_accounts: vec![BitcoinAccount::default()],
+            chain_backends: vec![],
+            watch_only: None,
+            cashu_reclaim_after_secs: None,
         }
     }
 }
@@ -79,6 +93,10 @@ impl WalletkaBuilder {
             electrum_url,
             esplora_url,
             rgb_transport_url,
+            bitcoin_accounts: vec![BitcoinAccount::default()],
+            chain_backends: vec![],
+            watch_only: None,
+            cashu_reclaim_after_secs: None,
         }
     }
 
@@ -124,67 +142,178 @@ impl WalletkaBuilder {
         self.electrum_url = electrum_url;
     }
 
-    pub async fn build(&self) -> Result<Walletka> {
-        let database = get_database(self.database_store.clone(), Some(self.network.to_string()))
-            .await
-            .unwrap();
+    pub fn set_esplora_url(&mut self, esplora_url: Option<String>) {
+        self.esplora_url = esplora_url;
+    }
+
+    /// Set chain backends tried in order before electrum and esplora urls
+    pub fn set_chain_backends(&mut self, chain_backends: Vec<ChainBackend>) {
+        self.chain_backends = chain_backends;
+    }
+
+    pub fn add_chain_backend(&mut self, chain_backend: ChainBackend) {
+        self.chain_backends.push(chain_backend);
+    }
+
+    /// Get all configured chain backends in failover order
+    pub fn get_chain_backends(&self) -> Vec<ChainBackend> {
+        let mut chain_backends = self.chain_backends.clone();
+
+        if let Some(url) = self.electrum_url.clone() {
+            chain_backends.push(ChainBackend::Electrum { url });
+        }
+        if let Some(url) = self.esplora_url.clone() {
+            chain_backends.push(ChainBackend::Esplora { url });
+        }
+
+        chain_backends.into_iter().unique().collect()
+    }
+
+    /// Set bitcoin accounts held by the wallet. First account is active by default
+    pub fn set_bitcoin_accounts(&mut self, accounts: Vec<BitcoinAccount>) {
+        self.bitcoin_accounts = accounts;
+    }
+
+    pub fn add_bitcoin_account(&mut self, account: BitcoinAccount) {
+        self.bitcoin_accounts.push(account);
+    }
+
+    /// Build watch-only Walletka from public keys, mnemonic is ignored
+    pub fn set_watch_only(&mut self, source: Option<WatchOnlySource>) {
+        self.watch_only = source;
+    }
+
+    /// Reclaim sent Cashu tokens not redeemed within given time during sync.
+    /// Tokens are never reclaimed automatically by default
+    pub fn set_cashu_reclaim_after(&mut self, secs: Option<u64>) -> Result<(), WalletkaError> {
+        if let Some(secs) = secs {
+            reclaim_duration(secs)?;
+        }
+        self.cashu_reclaim_after_secs = secs;
+
+        Ok(())
+    }
+
+    pub async fn build(&self) -> Result<Walletka, WalletkaError> {
+        let database =
+            get_database(self.database_store.clone(), Some(self.network.to_string())).await?;
         debug!("Database created");
 
-        let nostr_client = NostrClient::new(
-            self.nostr_relay_urls.clone(),
-            self.mnemonic_words.clone().unwrap(),
-            self.passphrase.clone(),
-        )
-        .await?;
-        debug!("Nostr client created");
+        let chain_backends = self.get_chain_backends();
+
+        let blockchain = match chain_backends.is_empty() {
+            true => None,
+            false => Some(Arc::new(BlockchainClient::new(chain_backends.clone())?)),
+        };
+        debug!("Blockchain created");
+
+        let (mut bitcoin_wallet, rgb_wallet, nostr_client) = match self.watch_only.clone() {
+            Some(source) => {
+                let bitcoin_wallet = BitcoinWallet::new_watch_only(
+                    self.network,
+                    source,
+                    blockchain,
+                    self.data_path.clone(),
+                )?;
+                debug!("Watch-only bitcoin wallet created");
+
+                (bitcoin_wallet, None, None)
+            }
+            None => {
+                let mnemonic_words = match self.mnemonic_words.clone() {
+                    Some(words) => words,
+                    None => {
+                        return Err(WalletkaError::InvalidMnemonic {
+                            details: "No mnemonic provided".to_string(),
+                        })
+                    }
+                };
+
+                let nostr_client = NostrClient::new(
+                    self.nostr_relay_urls.clone(),
+                    mnemonic_words.clone(),
+                    self.passphrase.clone(),
+                )
+                .await
+                .map_err(|err| WalletkaError::Nostr {
+                    details: err.to_string(),
+                })?;
+                debug!("Nostr client created");
+
+                let contacts_repository = ContactsRepository::new(database.clone());
+                debug!("Contacts repository created");
 
-        let contacts_repository = ContactsRepository::new(database.clone());
-        debug!("Contacts repository created");
+                let _contacts_service =
+                    ContactsManager::new(contacts_repository, nostr_client.clone());
+                debug!("Contacts service created");
 
-        let _contacts_service = ContactsManager::new(contacts_repository, nostr_client);
-        debug!("Contacts service created");
+                let mnemonic = Mnemonic::parse(mnemonic_words.clone())?;
 
-        let mnemonic = Mnemonic::parse(self.mnemonic_words.clone().unwrap())?;
+                let bitcoin_wallet = BitcoinWallet::new(
+                    self.network,
+                    mnemonic,
+                    self.passphrase.clone(),
+                    blockchain,
+                    self.data_path.clone(),
+                    self.bitcoin_accounts.clone(),
+                )?;
+                debug!("Bitcoin wallet created");
 
+                // rgb-lib takes only the mnemonic and derives keys without the passphrase,
+                // RGB layer is left out rather than holding assets under other keys.
+                // Hosts see it missing in `Walletka::get_layers`
+                let rgb_wallet = match self.passphrase.as_ref().is_some_and(|p| !p.is_empty()) {
+                    true => {
+                        warn!("RGB layer is not available with BIP39 passphrase");
+                        None
+                    }
+                    false => {
+                        let rgb_wallet = RgbWallet::new(
+                            mnemonic_words,
+                            None,
+                            self.data_path.clone(),
+                            self.network.into(),
+                            chain_backends.iter().map(|b| b.url()).collect(),
+                            self.rgb_transport_url.clone(),
+                        )
+                        .await?;
+                        debug!("RGB wallet created");
 
-        let blockchain = match self.electrum_url.clone() {
-            Some(url) => {
-                debug!("Creating Electrum blockchain");
-                let electrum_client = Client::new(&url)?;
-                Some(AnyBlockchain::from(ElectrumBlockchain::from(electrum_client)))
+                        Some(rgb_wallet)
+                    }
+                };
+
+                (bitcoin_wallet, rgb_wallet, Some(nostr_client))
             }
-            None => None,
-            
         };
-        debug!("Blockchain created");
 
-        let bitcoin_wallet = BitcoinWallet::new(
-            self.network,
-            mnemonic,
-            self.passphrase.clone(),
-            blockchain,
-            self.data_path.clone(),
-        )
-        .unwrap();
-        debug!("Bitcoin wallet created");
+        let bitcoin_repository = BitcoinRepository::new(database.clone());
+        for txo in bitcoin_repository.get_frozen_txos().await? {
+            bitcoin_wallet.set_utxo_frozen(OutPoint::from_str(&txo.outpoint())?, true);
+        }
 
         let cashu_repository = CashuRepository::new(database.clone());
-        let cashu_wallet = CashuWallet::new(cashu_repository).await?;
+        let cashu_wallet =
+            CashuWallet::new(cashu_repository, self.cashu_reclaim_after_secs).await?;
         debug!("Cashu wallet created");
 
-        let rgb_wallet = RgbWallet::new(
-            self.mnemonic_words.clone().unwrap(),
-            self.data_path.clone(),
-            self.network.into(),
-            self.electrum_url.clone(),
-            self.rgb_transport_url.clone(),
-        )
-        .await?;
-        debug!("RGB wallet created");
-
-        let walletka = Walletka::new(bitcoin_wallet, cashu_wallet, rgb_wallet);
+        let transaction_repository = TransactionRepository::new(database.clone());
+
+        let mut walletka = Walletka::new(
+            bitcoin_wallet,
+            cashu_wallet,
+            rgb_wallet,
+            bitcoin_repository,
+            transaction_repository,
+            nostr_client,
+        );
         debug!("Walletka created");
 
+        // Mint may be unreachable, unfinished operations are retried during sync
+        if let Err(err) = walletka.reconcile_cashu_operations().await {
+            warn!("Can't reconcile Cashu operations: {}", err);
+        }
+
         Ok(walletka)
     }
 }
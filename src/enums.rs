@@ -1,11 +1,35 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+use crate::types::Bip21Uri;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum TransactionDirection {
     Received,
     Sent,
 }
 
+/// State of transaction in history
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum TransactionStatus {
+    /// Unconfirmed onchain transaction, unclaimed token or unfinished transfer
+    Pending,
+    Confirmed,
+    Failed,
+}
+
+/// State of Cashu mint quote
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum CashuQuoteState {
+    /// Invoice is not paid yet or proofs are not minted yet
+    Unpaid,
+    /// Proofs were minted into the wallet
+    Issued,
+    /// Invoice wasn't paid in time, the quote is polled less often
+    Expired,
+    /// Interrupted mint couldn't be retried, proofs issued by the mint may be lost
+    Failed,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum WalletkaAssetLocation {
     Utxo { loc: String },
@@ -14,7 +38,7 @@ pub enum WalletkaAssetLocation {
     Fedimint { loc: String },
 }
 
-#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum WalletkaLayer {
     Blockchain,
     Lightning,
@@ -23,6 +47,50 @@ pub enum WalletkaLayer {
     Rgb,
 }
 
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum LayerSyncStatus {
+    Syncing,
+    Synced,
+    Failed { error: String },
+}
+
+/// Change of wallet state emitted to subscribers
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum WalletkaEvent {
+    NewUtxo {
+        outpoint: String,
+        amount_sat: u64,
+    },
+    TransactionConfirmed {
+        txid: String,
+    },
+    CashuTokenClaimed {
+        amount_sat: u64,
+        memo: Option<String>,
+    },
+    CashuMinted {
+        quote_id: String,
+        amount_sat: u64,
+    },
+    CashuTokenRedeemed {
+        tx_id: String,
+        amount_sat: u64,
+    },
+    CashuTokenReclaimed {
+        tx_id: String,
+        amount_sat: u64,
+    },
+    RgbTransferStatusChanged {
+        transfer_id: String,
+        currency_symbol: String,
+        status: TransactionStatus,
+    },
+    NostrDirectMessage {
+        sender: String,
+        message: String,
+    },
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum WalletkaAssetState {
     Unknown,
@@ -31,3 +99,93 @@ pub enum WalletkaAssetState {
     Spent,
     Unspendable,
 }
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum BitcoinScriptType {
+    /// Legacy P2PKH
+    Bip44,
+    /// Nested segwit P2SH-P2WPKH
+    Bip49,
+    /// Native segwit P2WPKH
+    Bip84,
+    /// Taproot P2TR
+    Bip86,
+}
+
+impl BitcoinScriptType {
+    pub fn purpose(&self) -> u32 {
+        match self {
+            BitcoinScriptType::Bip44 => 44,
+            BitcoinScriptType::Bip49 => 49,
+            BitcoinScriptType::Bip84 => 84,
+            BitcoinScriptType::Bip86 => 86,
+        }
+    }
+}
+
+/// Fee policy of onchain transaction
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FeePolicy {
+    /// Explicit fee rate in sat/vB
+    FeeRate { sat_per_vb: f32 },
+    /// Fee rate estimated by blockchain backend to confirm within target blocks
+    ConfirmationTarget { blocks: u32 },
+}
+
+/// Destination of payment recognized in user input
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum PaymentDestination {
+    BitcoinAddress {
+        address: String,
+    },
+    /// BIP352 static address
+    SilentPaymentAddress {
+        address: String,
+    },
+    /// BIP21 `bitcoin:` URI, possibly payable from more layers
+    BitcoinUri {
+        uri: Bip21Uri,
+    },
+    /// Token to be claimed, it can't be paid
+    CashuToken {
+        token: String,
+        mint_url: String,
+        amount_sat: u64,
+        memo: Option<String>,
+    },
+    RgbInvoice {
+        invoice: String,
+        asset_id: Option<String>,
+        /// Amount in asset units
+        amount: Option<u64>,
+    },
+    /// Nostr user paid by Cashu token in direct message
+    NostrPubkey {
+        npub: String,
+    },
+}
+
+/// Format of bitcoin message signature
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum MessageSignatureFormat {
+    /// BIP322 simple signature for P2WPKH and P2TR addresses
+    Bip322Simple,
+    /// BIP137 signature for P2PKH, P2SH-P2WPKH and P2WPKH addresses
+    Bip137,
+}
+
+/// Public key material of watch-only bitcoin wallet
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum WatchOnlySource {
+    /// Account level extended public key, optionally with key origin
+    /// of the signing device, e.g. `[d34db33f/84'/0'/0']xpub...`
+    Xpub {
+        xpub: String,
+        script_type: BitcoinScriptType,
+    },
+    /// Output descriptors with public keys only
+    Descriptors {
+        descriptor: String,
+        change_descriptor: Option<String>,
+    },
+}
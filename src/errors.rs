@@ -30,6 +30,13 @@ pub enum WalletkaError {
         details: String,
     },
 
+    /// The provided payment URI is malformed or requires unsupported features
+    #[error("Invalid URI: {details}")]
+    InvalidUri {
+        /// Error details
+        details: String,
+    },
+
     /// The provided script is invalid
     #[error("Invalid script: {details}")]
     InvalidScript {
@@ -50,6 +57,274 @@ pub enum WalletkaError {
         /// Error details
         details: String,
     },
+
+    /// The provided output descriptor is invalid
+    #[error("Invalid descriptor: {details}")]
+    InvalidDescriptor {
+        /// Error details
+        details: String,
+    },
+
+    /// An invalid argument has been provided, e.g. amount or outpoint
+    #[error("Invalid input: {details}")]
+    InvalidInput {
+        /// Error details
+        details: String,
+    },
+
+    /// The wallet doesn't hold enough funds for the operation
+    #[error("Insufficient funds: {details}")]
+    InsufficientFunds {
+        /// Error details
+        details: String,
+    },
+
+    /// Requested transaction, asset or other item doesn't exist
+    #[error("Not found: {details}")]
+    NotFound {
+        /// Error details
+        details: String,
+    },
+
+    /// No blockchain backend or RGB indexer is reachable
+    #[error("Offline mode")]
+    Offline,
+
+    /// An error was received from the Esplora server or other HTTP service
+    #[error("HTTP error: {details}")]
+    Http {
+        /// Error details
+        details: String,
+    },
+
+    /// Payjoin receiver or sender didn't follow BIP78
+    #[error("Payjoin error: {details}")]
+    Payjoin {
+        /// Error details
+        details: String,
+    },
+
+    /// An error in cashu-sdk or a response of Cashu mint
+    #[error("Cashu error: {details}")]
+    Cashu {
+        /// Error details
+        details: String,
+    },
+
+    /// An error in rgb-lib
+    #[error("RGB error: {details}")]
+    Rgb {
+        /// Error details
+        details: String,
+    },
+
+    /// An error in nostr client or invalid nostr data
+    #[error("Nostr error: {details}")]
+    Nostr {
+        /// Error details
+        details: String,
+    },
+
+    /// An error in wallet database
+    #[error("Database error: {details}")]
+    Database {
+        /// Error details
+        details: String,
+    },
+
+    /// An error reading or writing wallet files
+    #[error("IO error: {details}")]
+    Io {
+        /// Error details
+        details: String,
+    },
+
+    /// The wallet holds no private keys and can't sign
+    #[error("Watch-only wallet can't sign")]
+    WatchOnly,
+
+    /// The wallet was built without the layer, see `Walletka::get_layers`
+    #[error("Layer unavailable: {details}")]
+    LayerUnavailable {
+        /// Error details
+        details: String,
+    },
+
+    /// An error of no specific kind
+    #[error("{details}")]
+    Generic {
+        /// Error details
+        details: String,
+    },
+}
+
+/// Errors of io layer are passed in anyhow, known sources keep their kind
+impl From<anyhow::Error> for WalletkaError {
+    fn from(e: anyhow::Error) -> Self {
+        macro_rules! downcast {
+            ($error:ident, $($source:ty),*) => {
+                $(
+                    let $error = match $error.downcast::<$source>() {
+                        Ok(source) => return source.into(),
+                        Err($error) => $error,
+                    };
+                )*
+            };
+        }
+
+        downcast!(
+            e,
+            WalletkaError,
+            bdk::Error,
+            electrum_client::Error,
+            bdk::bitcoin::address::Error,
+            bdk::bitcoin::psbt::PsbtParseError,
+            rgb_lib::Error,
+            surrealdb::Error,
+            minreq::Error,
+            std::io::Error
+        );
+
+        WalletkaError::Generic {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::keys::KeyError> for WalletkaError {
+    fn from(e: bdk::keys::KeyError) -> Self {
+        WalletkaError::Bdk {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::miniscript::Error> for WalletkaError {
+    fn from(e: bdk::miniscript::Error) -> Self {
+        WalletkaError::InvalidDescriptor {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::sled::Error> for WalletkaError {
+    fn from(e: bdk::sled::Error) -> Self {
+        WalletkaError::Database {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::miniscript::psbt::Error> for WalletkaError {
+    fn from(e: bdk::miniscript::psbt::Error) -> Self {
+        WalletkaError::InvalidPsbt {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::bitcoin::psbt::Error> for WalletkaError {
+    fn from(e: bdk::bitcoin::psbt::Error) -> Self {
+        WalletkaError::InvalidPsbt {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::bitcoin::consensus::encode::Error> for WalletkaError {
+    fn from(e: bdk::bitcoin::consensus::encode::Error) -> Self {
+        WalletkaError::InvalidInput {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::bitcoin::hashes::hex::Error> for WalletkaError {
+    fn from(e: bdk::bitcoin::hashes::hex::Error) -> Self {
+        WalletkaError::InvalidInput {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::bitcoin::blockdata::transaction::ParseOutPointError> for WalletkaError {
+    fn from(e: bdk::bitcoin::blockdata::transaction::ParseOutPointError) -> Self {
+        WalletkaError::InvalidInput {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::bitcoin::key::Error> for WalletkaError {
+    fn from(e: bdk::bitcoin::key::Error) -> Self {
+        WalletkaError::InvalidInput {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::bitcoin::secp256k1::Error> for WalletkaError {
+    fn from(e: bdk::bitcoin::secp256k1::Error) -> Self {
+        WalletkaError::InvalidInput {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<bdk::bitcoin::sighash::Error> for WalletkaError {
+    fn from(e: bdk::bitcoin::sighash::Error) -> Self {
+        WalletkaError::Bdk {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<nostr_sdk::nips::nip19::Error> for WalletkaError {
+    fn from(e: nostr_sdk::nips::nip19::Error) -> Self {
+        WalletkaError::Nostr {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<rgb_lib::Error> for WalletkaError {
+    fn from(e: rgb_lib::Error) -> Self {
+        WalletkaError::Rgb {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<surrealdb::Error> for WalletkaError {
+    fn from(e: surrealdb::Error) -> Self {
+        WalletkaError::Database {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<minreq::Error> for WalletkaError {
+    fn from(e: minreq::Error) -> Self {
+        WalletkaError::Http {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<std::io::Error> for WalletkaError {
+    fn from(e: std::io::Error) -> Self {
+        WalletkaError::Io {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<std::num::ParseIntError> for WalletkaError {
+    fn from(e: std::num::ParseIntError) -> Self {
+        WalletkaError::InvalidInput {
+            details: e.to_string(),
+        }
+    }
 }
 
 impl From<bdk::keys::bip39::Error> for WalletkaError {
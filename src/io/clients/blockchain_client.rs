@@ -0,0 +1,278 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    OnceLock,
+};
+
+use anyhow::{bail, Result};
+use bdk::bitcoin::{consensus::deserialize, Block, Transaction, Txid};
+use bdk::blockchain::{
+    electrum::ElectrumBlockchainConfig,
+    esplora::{EsploraBlockchain, EsploraBlockchainConfig},
+    AnyBlockchain, Blockchain, ConfigurableBlockchain, ElectrumBlockchain, GetHeight, GetTx,
+};
+use bdk::database::BatchDatabase;
+use bdk::{FeeRate, SyncOptions, Wallet as BdkWallet};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+const STOP_GAP: usize = 20;
+const ESPLORA_TIMEOUT_SECS: u64 = 30;
+/// Hung backend fails after the timeout, so the next one can be tried
+const ELECTRUM_TIMEOUT_SECS: u8 = 30;
+const ELECTRUM_RETRY: u8 = 1;
+
+/// Blockchain data source. Clients of all backends are blocking, bdk is built either
+/// with blocking or async clients. Async code has to call them from blocking threads
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum ChainBackend {
+    Electrum { url: String },
+    Esplora { url: String },
+}
+
+impl ChainBackend {
+    pub fn url(&self) -> String {
+        match self {
+            ChainBackend::Electrum { url } => url.clone(),
+            ChainBackend::Esplora { url } => url.clone(),
+        }
+    }
+
+    fn connect(&self) -> Result<AnyBlockchain> {
+        let blockchain = match self {
+            ChainBackend::Electrum { url } => {
+                debug!("Creating Electrum blockchain {}", url);
+                AnyBlockchain::from(ElectrumBlockchain::from_config(
+                    &ElectrumBlockchainConfig {
+                        url: url.clone(),
+                        socks5: None,
+                        retry: ELECTRUM_RETRY,
+                        timeout: Some(ELECTRUM_TIMEOUT_SECS),
+                        stop_gap: STOP_GAP,
+                        validate_domain: true,
+                    },
+                )?)
+            }
+            ChainBackend::Esplora { url } => {
+                debug!("Creating Esplora blockchain {}", url);
+                AnyBlockchain::from(EsploraBlockchain::from_config(&EsploraBlockchainConfig {
+                    base_url: url.clone(),
+                    proxy: None,
+                    concurrency: None,
+                    stop_gap: STOP_GAP,
+                    timeout: Some(ESPLORA_TIMEOUT_SECS),
+                })?)
+            }
+        };
+
+        Ok(blockchain)
+    }
+}
+
+/// Blockchain client failing over between configured backends.
+/// Backends are connected lazily and the last working one is tried first
+pub struct BlockchainClient {
+    backends: Vec<ChainBackend>,
+    connections: Vec<OnceLock<AnyBlockchain>>,
+    active: AtomicUsize,
+}
+
+impl BlockchainClient {
+    pub fn new(backends: Vec<ChainBackend>) -> Result<Self> {
+        if backends.is_empty() {
+            bail!("No chain backend provided");
+        }
+
+        Ok(Self {
+            connections: backends.iter().map(|_| OnceLock::new()).collect(),
+            backends,
+            active: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn get_backends(&self) -> Vec<ChainBackend> {
+        self.backends.clone()
+    }
+
+    pub fn get_active_backend(&self) -> ChainBackend {
+        self.backends[self.active.load(Ordering::Relaxed)].clone()
+    }
+
+    fn get_connection(&self, index: usize) -> Result<&AnyBlockchain> {
+        if let Some(blockchain) = self.connections[index].get() {
+            return Ok(blockchain);
+        }
+
+        let blockchain = self.backends[index].connect()?;
+        Ok(self.connections[index].get_or_init(|| blockchain))
+    }
+
+    fn with_failover<T>(&self, f: impl Fn(&AnyBlockchain) -> Result<T>) -> Result<T> {
+        let active = self.active.load(Ordering::Relaxed);
+        let mut last_error = None;
+
+        for i in 0..self.backends.len() {
+            let index = (active + i) % self.backends.len();
+
+            match self.get_connection(index).and_then(&f) {
+                Ok(result) => {
+                    if index != active {
+                        info!("Switched chain backend to {}", self.backends[index].url());
+                        self.active.store(index, Ordering::Relaxed);
+                    }
+                    return Ok(result);
+                }
+                Err(err) => {
+                    warn!(
+                        "Chain backend {} failed: {}",
+                        self.backends[index].url(),
+                        err
+                    );
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        match last_error {
+            Some(err) => Err(err),
+            None => bail!("No chain backend available"),
+        }
+    }
+
+    pub fn sync_wallet<D: BatchDatabase>(&self, wallet: &BdkWallet<D>) -> Result<()> {
+        self.with_failover(|b| Ok(wallet.sync(b, SyncOptions::default())?))
+    }
+
+    pub fn broadcast(&self, transaction: &Transaction) -> Result<()> {
+        self.with_failover(|b| Ok(b.broadcast(transaction)?))
+    }
+
+    pub fn estimate_fee(&self, target_blocks: usize) -> Result<FeeRate> {
+        self.with_failover(|b| Ok(b.estimate_fee(target_blocks)?))
+    }
+
+    pub fn get_height(&self) -> Result<u32> {
+        self.with_failover(|b| Ok(b.get_height()?))
+    }
+
+    pub fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>> {
+        self.with_failover(|b| Ok(b.get_tx(txid)?))
+    }
+
+    /// Get block by height, only Esplora backends serve full blocks
+    pub fn get_block(&self, height: u32) -> Result<Block> {
+        let mut last_error = None;
+
+        for backend in self.backends.iter() {
+            let url = match backend {
+                ChainBackend::Esplora { url } => url.trim_end_matches('/'),
+                ChainBackend::Electrum { .. } => continue,
+            };
+
+            match get_esplora_block(url, height) {
+                Ok(block) => return Ok(block),
+                Err(err) => {
+                    warn!("Chain backend {} failed: {}", url, err);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        match last_error {
+            Some(err) => Err(err),
+            None => bail!("Blocks can be fetched from Esplora backend only"),
+        }
+    }
+}
+
+fn get_esplora_block(url: &str, height: u32) -> Result<Block> {
+    let response = minreq::get(format!("{url}/block-height/{height}"))
+        .with_timeout(ESPLORA_TIMEOUT_SECS)
+        .send()?;
+    if response.status_code != 200 {
+        bail!("Block {} not found", height);
+    }
+    let hash = response.as_str()?.trim().to_string();
+
+    let response = minreq::get(format!("{url}/block/{hash}/raw"))
+        .with_timeout(ESPLORA_TIMEOUT_SECS)
+        .send()?;
+    if response.status_code != 200 {
+        bail!("Block {} not found", hash);
+    }
+
+    Ok(deserialize(response.as_bytes())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    /// Local Esplora endpoint answering every request with tip height
+    fn serve_tip_height(height: u32) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+
+                let body = height.to_string();
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        Ok(url)
+    }
+
+    #[test]
+    fn failover_from_dead_backend() -> Result<()> {
+        let url = serve_tip_height(123)?;
+        let client = BlockchainClient::new(vec![
+            ChainBackend::Electrum {
+                url: "127.0.0.1:1".to_string(),
+            },
+            ChainBackend::Esplora { url: url.clone() },
+        ])?;
+
+        assert_eq!(123, client.get_height()?);
+        assert_eq!(ChainBackend::Esplora { url }, client.get_active_backend());
+        // Working backend is tried first from now on
+        assert_eq!(123, client.get_height()?);
+
+        Ok(())
+    }
+
+    /// Run against local regtest backends, e.g.
+    /// `ELECTRUM_URL=127.0.0.1:50001 ESPLORA_URL=http://127.0.0.1:3002 cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn failover_to_working_backend() -> Result<()> {
+        let mut backends = vec![ChainBackend::Electrum {
+            url: "127.0.0.1:1".to_string(),
+        }];
+
+        if let Ok(url) = std::env::var("ELECTRUM_URL") {
+            backends.push(ChainBackend::Electrum { url });
+        }
+        if let Ok(url) = std::env::var("ESPLORA_URL") {
+            backends.push(ChainBackend::Esplora { url });
+        }
+
+        let client = BlockchainClient::new(backends)?;
+
+        client.get_height()?;
+        assert_ne!(0, client.active.load(Ordering::Relaxed));
+
+        Ok(())
+    }
+}
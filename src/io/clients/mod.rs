@@ -1,2 +1,6 @@
+mod blockchain_client;
 mod nostr_client;
+mod payjoin_client;
+pub use blockchain_client::{BlockchainClient, ChainBackend};
 pub use nostr_client::NostrClient;
+pub use payjoin_client::{PayjoinClient, PayjoinParams};
@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use anyhow::Result;
-use log::{debug, info};
+use log::{debug, info, warn};
 use nostr_sdk::{
     nips::{nip04, nip06::FromMnemonic},
     Client, Contact, Event, Filter, Keys, Kind, Metadata, PublicKey, Tag, Timestamp,
@@ -10,6 +10,7 @@ use nostr_sdk::{
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
 
+#[derive(Clone)]
 pub struct NostrClient {
     keys: Keys,
     client: Client,
@@ -94,7 +95,9 @@ impl NostrClient {
         Ok(contact_list)
     }
 
-    pub async fn get_nip04_messages(&self, since: Timestamp) -> Result<()> {
+    /// Get received nip04 messages with their decrypted content,
+    /// messages which can't be decrypted are left out
+    pub async fn get_nip04_messages(&self, since: Timestamp) -> Result<Vec<(Event, String)>> {
         debug!("Getting nip04 private messages");
 
         let filters: Vec<Filter> = vec![Filter::new()
@@ -109,11 +112,29 @@ impl NostrClient {
 
         debug!("Nip04 messages sucessfully retrieved");
 
+        // Malformed or foreign message is skipped, content may hold Cashu token and isn't logged
+        let mut messages = vec![];
         for event in events.into_iter() {
-            let encrypted_msg = self.decrypt_nip04(event.content.clone())?;
-            debug!("Received message: {}", encrypted_msg);
+            match nip04::decrypt(self.keys.secret_key()?, &event.pubkey, &event.content) {
+                Ok(decrypted_msg) => {
+                    debug!("Received message in event:{}", event.id);
+
+                    messages.push((event, decrypted_msg));
+                }
+                Err(err) => warn!("Can't decrypt message in event:{}: {}", event.id, err),
+            }
         }
 
+        Ok(messages)
+    }
+
+    pub async fn send_nip04_message(&self, receiver: PublicKey, message: String) -> Result<()> {
+        debug!("Sending nip04 message to {}", receiver.to_bech32()?);
+
+        let event_id = self.client.send_direct_msg(receiver, message, None).await?;
+
+        info!("Nip04 message sent in event:{}", event_id);
+
         Ok(())
     }
 
@@ -0,0 +1,158 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+
+use anyhow::{bail, Result};
+use log::{debug, info, warn};
+
+use crate::utils::query_pairs;
+
+const PAYJOIN_VERSION: u8 = 1;
+const REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// Optional parameters of BIP78 payjoin request
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PayjoinParams {
+    pub additional_fee_output_index: Option<usize>,
+    pub max_additional_fee_contribution: Option<u64>,
+    pub min_fee_rate: Option<f32>,
+    pub disable_output_substitution: bool,
+}
+
+impl PayjoinParams {
+    pub fn to_query(&self) -> String {
+        let mut query = vec![format!("v={PAYJOIN_VERSION}")];
+
+        if let Some(index) = self.additional_fee_output_index {
+            query.push(format!("additionalfeeoutputindex={index}"));
+        }
+        if let Some(fee) = self.max_additional_fee_contribution {
+            query.push(format!("maxadditionalfeecontribution={fee}"));
+        }
+        if let Some(fee_rate) = self.min_fee_rate {
+            query.push(format!("minfeerate={fee_rate}"));
+        }
+        if self.disable_output_substitution {
+            query.push("disableoutputsubstitution=true".to_string());
+        }
+
+        query.join("&")
+    }
+
+    pub fn from_query(query: &str) -> Result<Self> {
+        let mut params = PayjoinParams::default();
+
+        for (key, value) in query_pairs(query)? {
+            match key.as_str() {
+                "v" if value != PAYJOIN_VERSION.to_string() => {
+                    bail!("Unsupported payjoin version {}", value)
+                }
+                "additionalfeeoutputindex" => {
+                    params.additional_fee_output_index = Some(value.parse()?)
+                }
+                "maxadditionalfeecontribution" => {
+                    params.max_additional_fee_contribution = Some(value.parse()?)
+                }
+                "minfeerate" => params.min_fee_rate = Some(value.parse()?),
+                "disableoutputsubstitution" => params.disable_output_substitution = value == "true",
+                _ => {}
+            }
+        }
+
+        Ok(params)
+    }
+}
+
+/// Sender side of BIP78 payjoin
+pub struct PayjoinClient;
+
+impl PayjoinClient {
+    /// Send signed original PSBT to receiver, returns proposal PSBT in base64
+    pub fn send_original(endpoint: &str, psbt: &str, params: &PayjoinParams) -> Result<String> {
+        let url = match endpoint.contains('?') {
+            true => format!("{}&{}", endpoint, params.to_query()),
+            false => format!("{}?{}", endpoint, params.to_query()),
+        };
+        debug!("Sending original PSBT to {}", url);
+
+        let response = minreq::post(url)
+            .with_header("Content-Type", "text/plain")
+            .with_body(psbt)
+            .with_timeout(REQUEST_TIMEOUT_SECS)
+            .send()?;
+
+        match response.status_code {
+            200 => Ok(response.as_str()?.trim().to_string()),
+            code => bail!(
+                "Payjoin receiver responded {}: {}",
+                code,
+                response.as_str().unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Minimal HTTP receiver endpoint handling a single payjoin request.
+    /// Handler gets original PSBT and request parameters and returns proposal PSBT
+    pub fn serve_once(
+        listen_address: &str,
+        handler: impl FnOnce(&str, PayjoinParams) -> Result<String>,
+    ) -> Result<()> {
+        let listener = TcpListener::bind(listen_address)?;
+        info!("Waiting for payjoin request on {}", listen_address);
+
+        let (mut stream, peer) = listener.accept()?;
+        debug!("Payjoin request from {}", peer);
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let target = request_line.split_whitespace().nth(1).unwrap_or_default();
+        let query = target.split_once('?').map(|(_, q)| q).unwrap_or_default();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header)?;
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse()?;
+                }
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+
+        let result = PayjoinParams::from_query(query)
+            .and_then(|params| handler(String::from_utf8(body)?.trim(), params));
+
+        let (status, body) = match &result {
+            Ok(proposal) => ("200 OK", proposal.clone()),
+            Err(err) => {
+                warn!("Payjoin request rejected: {}", err);
+                (
+                    "400 Bad Request",
+                    format!(
+                        "{{\"errorCode\": \"unavailable\", \"message\": \"{}\"}}",
+                        err.to_string().replace('"', "'")
+                    ),
+                )
+            }
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        stream.flush()?;
+
+        result.map(|_| ())
+    }
+}
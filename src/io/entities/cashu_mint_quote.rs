@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Datetime, Thing};
+
+use crate::enums::CashuQuoteState;
+
+/// Lightning invoice requested from Cashu mint, proofs are minted once it's paid
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CashuMintQuote {
+    pub id: Option<Thing>,
+    /// Quote id, payment hash of the invoice for mints without quote ids
+    pub quote_id: String,
+    pub mint_url: String,
+    pub amount_sat: u64,
+    /// Bolt11 invoice to pay
+    pub request: String,
+    pub state: CashuQuoteState,
+    pub datetime: Datetime,
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Datetime, Thing};
+
+use super::CashuProof;
+
+/// Journal entry of operation in flight at Cashu mint, removed once the operation finishes
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CashuOperation {
+    pub id: Option<Thing>,
+    pub kind: CashuOperationKind,
+    pub mint_url: String,
+    /// Own proofs spent by the operation
+    pub proofs: Vec<Thing>,
+    pub amount_sat: u64,
+    /// Sent or claimed token, or id of mint quote
+    #[serde(default)]
+    pub reference: Option<String>,
+    /// Proofs issued by the mint, recorded as soon as the mint returns them
+    /// so interrupted operation can be finished with them
+    #[serde(default)]
+    pub new_proofs: Option<Vec<CashuProof>>,
+    /// Operation which can't be finished nor retried, kept for manual recovery
+    #[serde(default)]
+    pub failed: bool,
+    pub datetime: Datetime,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+pub enum CashuOperationKind {
+    Send,
+    Melt,
+    Claim,
+    /// Claim of own sent token
+    Reclaim,
+    Mint,
+}
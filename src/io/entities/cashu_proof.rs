@@ -6,6 +6,8 @@ use cashu_sdk::{
 use serde::{Deserialize, Serialize};
 use surrealdb::sql::{Datetime, Thing};
 
+use crate::errors::WalletkaError;
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct CashuProof {
     pub id: Option<Thing>,
@@ -13,6 +15,8 @@ pub struct CashuProof {
     pub amount_sat: u64,
     pub secret: Secret,
     pub c: PublicKey,
+    #[serde(default)]
+    pub state: CashuProofState,
 }
 
 impl CashuProof {
@@ -23,8 +27,14 @@ impl CashuProof {
             amount_sat,
             secret,
             c,
+            state: CashuProofState::Unspent,
         }
     }
+
+    /// Id of stored proof
+    pub fn record_id(&self) -> Result<String, WalletkaError> {
+        record_id(&self.id)
+    }
 }
 
 impl From<&Proof> for CashuProof {
@@ -35,6 +45,7 @@ impl From<&Proof> for CashuProof {
             amount_sat: value.amount.to_sat(),
             secret: value.secret.clone(),
             c: value.c.clone(),
+            state: CashuProofState::Unspent,
         }
     }
 }
@@ -60,8 +71,35 @@ pub struct PendingCashuToken {
     pub amount_sat: u64,
 }
 
+impl PendingCashuToken {
+    /// Id of stored token
+    pub fn record_id(&self) -> Result<String, WalletkaError> {
+        record_id(&self.id)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum PendingCashuTokenSource {
     Received,
     Sent,
 }
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Debug)]
+pub enum CashuProofState {
+    #[default]
+    Unspent,
+    /// Used by melt in flight
+    Reserved,
+    /// Used by swap in flight, the mint may have spent it already
+    PendingSwap,
+    Spent,
+}
+
+fn record_id(id: &Option<Thing>) -> Result<String, WalletkaError> {
+    match id {
+        Some(id) => Ok(id.id.to_string()),
+        None => Err(WalletkaError::Database {
+            details: "Record is not stored".to_string(),
+        }),
+    }
+}
@@ -8,6 +8,8 @@ pub enum ContactAddressType {
     Npub,
     Nip05,
     LightningNodePubkey,
+    /// BIP352 static onchain address
+    SilentPayment,
     Other(String),
 }
 
@@ -1,10 +1,16 @@
 mod cashu_mint;
+mod cashu_mint_quote;
+mod cashu_operation;
 mod cashu_proof;
 mod contact;
+mod silent_payment_output;
 mod transaction;
 mod txo;
 pub use cashu_mint::CashuMint;
-pub use cashu_proof::{CashuProof, PendingCashuToken, PendingCashuTokenSource};
+pub use cashu_mint_quote::CashuMintQuote;
+pub use cashu_operation::{CashuOperation, CashuOperationKind};
+pub use cashu_proof::{CashuProof, CashuProofState, PendingCashuToken, PendingCashuTokenSource};
 pub use contact::{ContactAddress, ContactAddressType, WalletkaContact};
-pub use transaction::WalletkaTransaction;
+pub use silent_payment_output::SilentPaymentOutput;
+pub use transaction::{WalletkaTransaction, WalletkaTransactionEntry};
 pub use txo::Txo;
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+/// Output received to silent payment address
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SilentPaymentOutput {
+    pub id: Option<Thing>,
+    pub tx_id: String,
+    pub vout: u32,
+    pub amount_sats: u64,
+    pub script_pubkey: String,
+    /// Hex encoded tweak of the spend key
+    pub tweak: String,
+    pub block_height: u32,
+    pub spent: bool,
+}
+
+impl SilentPaymentOutput {
+    pub fn outpoint(&self) -> String {
+        format!("{}:{}", self.tx_id, self.vout)
+    }
+}
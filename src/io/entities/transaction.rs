@@ -1,10 +1,113 @@
+use bdk::TransactionDetails;
+use chrono::{DateTime, Utc};
+use rgb_lib::wallet::{AssetNIA, Transfer, TransferKind};
+use rgb_lib::TransferStatus;
 use serde::{Deserialize, Serialize};
-use surrealdb::sql::Thing;
+use surrealdb::sql::{Datetime, Thing};
 
-use crate::enums::TransactionDirection;
+use crate::enums::{TransactionDirection, TransactionStatus, WalletkaLayer};
+use crate::types::{Amount, Currency};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// History entry of any layer
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WalletkaTransaction {
     pub id: Option<Thing>,
+    pub layer: WalletkaLayer,
     pub direction: TransactionDirection,
+    pub status: TransactionStatus,
+    /// Onchain txid, RGB transfer index, hash of Cashu token or Cashu quote id
+    pub tx_id: Option<String>,
+    /// Amount sent or received without the fee
+    pub amount: Amount,
+    pub fee_sat: Option<u64>,
+    pub datetime: Datetime,
+    pub contact_id: Option<String>,
+    pub memo: Option<String>,
+    pub entries: Vec<WalletkaTransactionEntry>,
+}
+
+/// Single output of transaction
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct WalletkaTransactionEntry {
+    pub direction: TransactionDirection,
+    pub address: String,
+    pub amount_sat: u64,
+    pub vout: Option<u32>,
+}
+
+impl From<TransactionDetails> for WalletkaTransaction {
+    fn from(value: TransactionDetails) -> Self {
+        let fee_sat = value.fee.unwrap_or_default();
+        let (direction, amount_sat) = match value.sent > value.received {
+            true => (
+                TransactionDirection::Sent,
+                (value.sent - value.received).saturating_sub(fee_sat),
+            ),
+            false => (TransactionDirection::Received, value.received - value.sent),
+        };
+
+        let (status, datetime) = match value.confirmation_time {
+            Some(block_time) => (
+                TransactionStatus::Confirmed,
+                timestamp_datetime(block_time.timestamp as i64),
+            ),
+            None => (TransactionStatus::Pending, Datetime::default()),
+        };
+
+        Self {
+            id: None,
+            layer: WalletkaLayer::Blockchain,
+            direction,
+            status,
+            tx_id: Some(value.txid.to_string()),
+            amount: Amount::new(amount_sat, Currency::bitcoin()),
+            fee_sat: value
+                .fee
+                .filter(|_| direction == TransactionDirection::Sent),
+            datetime,
+            contact_id: None,
+            memo: None,
+            entries: vec![],
+        }
+    }
+}
+
+impl From<(AssetNIA, Transfer)> for WalletkaTransaction {
+    fn from((asset, transfer): (AssetNIA, Transfer)) -> Self {
+        Self {
+            id: None,
+            layer: WalletkaLayer::Rgb,
+            direction: match transfer.kind {
+                TransferKind::Send => TransactionDirection::Sent,
+                _ => TransactionDirection::Received,
+            },
+            status: match transfer.status {
+                TransferStatus::Settled => TransactionStatus::Confirmed,
+                TransferStatus::Failed => TransactionStatus::Failed,
+                _ => TransactionStatus::Pending,
+            },
+            tx_id: Some(transfer.idx.to_string()),
+            amount: Amount::new(
+                transfer.amount,
+                Currency::new(
+                    asset.ticker.clone(),
+                    asset.name,
+                    asset.ticker,
+                    asset.precision as u64,
+                ),
+            ),
+            fee_sat: None,
+            datetime: timestamp_datetime(transfer.created_at),
+            contact_id: None,
+            memo: None,
+            entries: vec![],
+        }
+    }
+}
+
+fn timestamp_datetime(timestamp: i64) -> Datetime {
+    match DateTime::<Utc>::from_timestamp(timestamp, 0) {
+        Some(datetime) => Datetime::from(datetime),
+        None => Datetime::default(),
+    }
 }
@@ -9,6 +9,15 @@ pub struct Txo {
     pub vout: u32,
     pub amount_sats: u64,
     pub spent: bool,
+    /// Frozen txo is never picked by automatic coin selection
+    pub frozen: bool,
+    pub label: Option<String>,
+}
+
+impl Txo {
+    pub fn outpoint(&self) -> String {
+        format!("{}:{}", self.tx_id, self.vout)
+    }
 }
 
 impl From<LocalUtxo> for Txo {
@@ -18,7 +27,9 @@ impl From<LocalUtxo> for Txo {
             tx_id: value.outpoint.txid.to_string(),
             vout: value.outpoint.vout,
             amount_sats: value.txout.value,
-            spent: false,
+            spent: value.is_spent,
+            frozen: false,
+            label: None,
         }
     }
 }
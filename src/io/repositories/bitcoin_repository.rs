@@ -1,5 +1,12 @@
+use log::debug;
 use surrealdb::{Connection, Surreal};
 
+use crate::errors::WalletkaError;
+use crate::io::entities::{SilentPaymentOutput, Txo};
+
+const TXOS_TABLE: &str = "txos";
+const SILENT_PAYMENT_OUTPUTS_TABLE: &str = "silent_payment_outputs";
+
 pub struct BitcoinRepository<C>
 where
     C: Connection,
@@ -14,4 +21,134 @@ where
     pub fn new(database: Surreal<C>) -> Self {
         Self { database }
     }
+
+    /// Create or update txo
+    pub async fn save_txo(&self, txo: Txo) -> Result<Txo, WalletkaError> {
+        debug!("Saving txo {}", txo.outpoint());
+
+        let id = format!("{}_{}", txo.tx_id, txo.vout);
+        let saved: Option<Txo> = self.database.update((TXOS_TABLE, id)).content(txo).await?;
+
+        match saved {
+            Some(txo) => Ok(txo),
+            None => Err(WalletkaError::Database {
+                details: "Can't save txo!".to_string(),
+            }),
+        }
+    }
+
+    pub async fn get_txo(&self, tx_id: &str, vout: u32) -> Result<Option<Txo>, WalletkaError> {
+        let txo: Option<Txo> = self
+            .database
+            .select((TXOS_TABLE, format!("{}_{}", tx_id, vout)))
+            .await?;
+
+        Ok(txo)
+    }
+
+    pub async fn get_txos(&self) -> Result<Vec<Txo>, WalletkaError> {
+        let txos: Vec<Txo> = self.database.select(TXOS_TABLE).await?;
+
+        Ok(txos)
+    }
+
+    pub async fn get_frozen_txos(&self) -> Result<Vec<Txo>, WalletkaError> {
+        let txos = self.get_txos().await?;
+
+        Ok(txos.into_iter().filter(|t| t.frozen).collect())
+    }
+
+    /// Create or update output received to silent payment address
+    pub async fn save_silent_payment_output(
+        &self,
+        output: SilentPaymentOutput,
+    ) -> Result<SilentPaymentOutput, WalletkaError> {
+        debug!("Saving silent payment output {}", output.outpoint());
+
+        let id = format!("{}_{}", output.tx_id, output.vout);
+        let saved: Option<SilentPaymentOutput> = self
+            .database
+            .update((SILENT_PAYMENT_OUTPUTS_TABLE, id))
+            .content(output)
+            .await?;
+
+        match saved {
+            Some(output) => Ok(output),
+            None => Err(WalletkaError::Database {
+                details: "Can't save silent payment output!".to_string(),
+            }),
+        }
+    }
+
+    pub async fn get_silent_payment_output(
+        &self,
+        tx_id: &str,
+        vout: u32,
+    ) -> Result<Option<SilentPaymentOutput>, WalletkaError> {
+        let output: Option<SilentPaymentOutput> = self
+            .database
+            .select((SILENT_PAYMENT_OUTPUTS_TABLE, format!("{}_{}", tx_id, vout)))
+            .await?;
+
+        Ok(output)
+    }
+
+    pub async fn get_silent_payment_outputs(
+        &self,
+    ) -> Result<Vec<SilentPaymentOutput>, WalletkaError> {
+        let outputs: Vec<SilentPaymentOutput> =
+            self.database.select(SILENT_PAYMENT_OUTPUTS_TABLE).await?;
+
+        Ok(outputs)
+    }
+
+    pub async fn get_unspent_silent_payment_outputs(
+        &self,
+    ) -> Result<Vec<SilentPaymentOutput>, WalletkaError> {
+        let outputs = self.get_silent_payment_outputs().await?;
+
+        Ok(outputs.into_iter().filter(|o| !o.spent).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use surrealdb::engine::local::Mem;
+
+    #[tokio::test]
+    async fn freeze_txo() -> Result<()> {
+        let db = Surreal::new::<Mem>(()).await?;
+        db.use_ns("test").use_db("test").await?;
+
+        let repo = BitcoinRepository::new(db);
+
+        let txo = Txo {
+            id: None,
+            tx_id: "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16".to_string(),
+            vout: 1,
+            amount_sats: 10_000,
+            spent: false,
+            frozen: false,
+            label: None,
+        };
+
+        let mut saved = repo.save_txo(txo).await?;
+        assert_ne!(None, saved.id);
+        assert!(repo.get_frozen_txos().await?.is_empty());
+
+        saved.frozen = true;
+        saved.label = Some("Cold storage".to_string());
+        repo.save_txo(saved.clone()).await?;
+
+        let frozen = repo.get_frozen_txos().await?;
+        assert_eq!(1, frozen.len());
+        assert_eq!(Some("Cold storage".to_string()), frozen[0].label);
+
+        let loaded = repo.get_txo(&saved.tx_id, saved.vout).await?;
+        assert!(loaded.is_some_and(|t| t.frozen));
+
+        Ok(())
+    }
 }
@@ -1,14 +1,19 @@
 use log::debug;
 use sha256::digest;
 
-use anyhow::{bail, Result};
-use surrealdb::{engine::local::Db, Surreal};
+use surrealdb::{engine::local::Db, sql::Thing, Surreal};
 
-use crate::io::entities::{CashuMint, CashuProof, PendingCashuToken};
+use crate::enums::CashuQuoteState;
+use crate::errors::WalletkaError;
+use crate::io::entities::{
+    CashuMint, CashuMintQuote, CashuOperation, CashuProof, CashuProofState, PendingCashuToken,
+};
 
 const CASHU_PROOFS_TABLE: &str = "cashu_proofs";
 const PENDING_CASHU_TOKENS_TABLE: &str = "cashu_pending_tokens";
 const CASHU_MINTS_TABLE: &str = "cashu_mints";
+const CASHU_MINT_QUOTES_TABLE: &str = "cashu_mint_quotes";
+const CASHU_OPERATIONS_TABLE: &str = "cashu_operations";
 
 pub struct CashuRepository
 {
@@ -21,7 +26,7 @@ impl CashuRepository
         Self { db }
     }
 
-    pub async fn store_proof(&self, proof: CashuProof) -> Result<CashuProof> {
+    pub async fn store_proof(&self, proof: CashuProof) -> Result<CashuProof, WalletkaError> {
         debug!("Storing proof to db...");
         let id = digest(proof.secret.to_string());
         let proof: Option<CashuProof> = self
@@ -32,29 +37,49 @@ impl CashuRepository
 
         match proof {
             Some(proof) => Ok(proof.to_owned()),
-            None => bail!("Can't store proof!"),
+            None => Err(WalletkaError::Database {
+                details: "Can't store proof!".to_string(),
+            }),
         }
     }
 
-    pub async fn get_proofs(&self) -> Result<Vec<CashuProof>> {
+    pub async fn get_proofs(&self) -> Result<Vec<CashuProof>, WalletkaError> {
         let proofs: Vec<CashuProof> = self.db.select(CASHU_PROOFS_TABLE).await?;
 
         Ok(proofs)
     }
 
-    pub async fn get_proof_by_id(&self, id: String) -> Result<Option<CashuProof>> {
+    pub async fn get_proof_by_id(&self, id: String) -> Result<Option<CashuProof>, WalletkaError> {
         let proof: Option<CashuProof> = self.db.select((CASHU_PROOFS_TABLE, &id)).await?;
 
         Ok(proof)
     }
 
-    pub async fn delete_proof(&self, id: String) -> Result<bool> {
+    pub async fn get_proofs_by_ids(
+        &self,
+        ids: Vec<Thing>,
+    ) -> Result<Vec<CashuProof>, WalletkaError> {
+        let mut response = self
+            .db
+            .query("SELECT * FROM $ids")
+            .bind(("ids", ids))
+            .await?;
+
+        let proofs: Vec<CashuProof> = response.take(0)?;
+
+        Ok(proofs)
+    }
+
+    pub async fn delete_proof(&self, id: String) -> Result<bool, WalletkaError> {
         let proof: Option<CashuProof> = self.db.delete((CASHU_PROOFS_TABLE, &id)).await?;
 
         Ok(proof.is_some())
     }
 
-    pub async fn add_pending_token(&self, token: PendingCashuToken) -> Result<PendingCashuToken> {
+    pub async fn add_pending_token(
+        &self,
+        token: PendingCashuToken,
+    ) -> Result<PendingCashuToken, WalletkaError> {
         debug!("Adding pending token");
 
         let id = digest(token.token.clone());
@@ -67,17 +92,29 @@ impl CashuRepository
 
         match proof {
             Some(proof) => Ok(proof.to_owned()),
-            None => bail!("Can't store proof!"),
+            None => Err(WalletkaError::Database {
+                details: "Can't store proof!".to_string(),
+            }),
         }
     }
 
-    pub async fn get_pending_tokens(&self) -> Result<Vec<PendingCashuToken>> {
+    pub async fn get_pending_tokens(&self) -> Result<Vec<PendingCashuToken>, WalletkaError> {
         let tokens: Vec<PendingCashuToken> = self.db.select(PENDING_CASHU_TOKENS_TABLE).await?;
 
         Ok(tokens)
     }
 
-    pub async fn set_pending_token_claimed(&self, id: String) -> Result<()> {
+    pub async fn get_pending_token(
+        &self,
+        id: &str,
+    ) -> Result<Option<PendingCashuToken>, WalletkaError> {
+        let token: Option<PendingCashuToken> =
+            self.db.select((PENDING_CASHU_TOKENS_TABLE, id)).await?;
+
+        Ok(token)
+    }
+
+    pub async fn set_pending_token_claimed(&self, id: String) -> Result<(), WalletkaError> {
         debug!("Setting pending token claimed: {}", id);
         let mut updated = self
             .db
@@ -91,11 +128,13 @@ impl CashuRepository
 
         match token {
             Some(_) => Ok(()),
-            None => bail!("Can't set token claimed!"),
+            None => Err(WalletkaError::Database {
+                details: "Can't set token claimed!".to_string(),
+            }),
         }
     }
 
-    pub async fn add_mint(&self, cashu_mint: CashuMint) -> Result<CashuMint> {
+    pub async fn add_mint(&self, cashu_mint: CashuMint) -> Result<CashuMint, WalletkaError> {
         debug!("Adding cashu mint {}", cashu_mint.keyset_id);
 
         let created: Option<CashuMint> = self
@@ -106,23 +145,25 @@ impl CashuRepository
 
         match created {
             Some(mint) => Ok(mint.to_owned()),
-            None => bail!("Can't create cashu mint!"),
+            None => Err(WalletkaError::Database {
+                details: "Can't create cashu mint!".to_string(),
+            }),
         }
     }
 
-    pub async fn get_mints(&self) -> Result<Vec<CashuMint>> {
+    pub async fn get_mints(&self) -> Result<Vec<CashuMint>, WalletkaError> {
         let mints: Vec<CashuMint> = self.db.select(CASHU_MINTS_TABLE).await?;
 
         Ok(mints)
     }
 
-    pub async fn get_mint_by_id(&self, id: String) -> Result<Option<CashuMint>> {
+    pub async fn get_mint_by_id(&self, id: String) -> Result<Option<CashuMint>, WalletkaError> {
         let mint: Option<CashuMint> = self.db.select((CASHU_MINTS_TABLE, id)).await?;
 
         Ok(mint)
     }
 
-    pub async fn update_mint(&self, cashu_mint: CashuMint) -> Result<CashuMint> {
+    pub async fn update_mint(&self, cashu_mint: CashuMint) -> Result<CashuMint, WalletkaError> {
         let updated: Option<CashuMint> = self
             .db
             .update((CASHU_MINTS_TABLE, &cashu_mint.keyset_id))
@@ -131,17 +172,426 @@ impl CashuRepository
 
         match updated {
             Some(mint) => Ok(mint),
-            None => bail!("Can't update cashu mint!"),
+            None => Err(WalletkaError::Database {
+                details: "Can't update cashu mint!".to_string(),
+            }),
         }
     }
 
-    pub async fn delete_mint(&self, id: String) -> Result<()> {
+    pub async fn delete_mint(&self, id: String) -> Result<(), WalletkaError> {
         debug!("Deleting cashu mint {}", id);
         let deleted: Option<CashuMint> = self.db.delete((CASHU_MINTS_TABLE, id)).await?;
 
         match deleted {
             Some(_) => Ok(()),
-            None => bail!("Can't delete cashu mint!"),
+            None => Err(WalletkaError::Database {
+                details: "Can't delete cashu mint!".to_string(),
+            }),
+        }
+    }
+
+    /// Journal operation and set state of its proofs in one transaction
+    pub async fn begin_operation(
+        &self,
+        mut operation: CashuOperation,
+        state: CashuProofState,
+    ) -> Result<CashuOperation, WalletkaError> {
+        let id = digest(
+            operation
+                .proofs
+                .iter()
+                .map(|p| p.to_string())
+                .chain(operation.reference.clone())
+                .collect::<Vec<String>>()
+                .join(","),
+        );
+        debug!("Beginning cashu operation {}", id);
+
+        self.db
+            .query("BEGIN TRANSACTION")
+            .query("UPDATE $proofs SET state = $state")
+            .query("CREATE type::thing($table, $id) CONTENT $operation")
+            .query("COMMIT TRANSACTION")
+            .bind(("proofs", operation.proofs.clone()))
+            .bind(("state", state))
+            .bind(("table", CASHU_OPERATIONS_TABLE))
+            .bind(("id", id.clone()))
+            .bind(("operation", operation.clone()))
+            .await?
+            .check()?;
+
+        operation.id = Some(Thing::from((CASHU_OPERATIONS_TABLE, id.as_str())));
+
+        Ok(operation)
+    }
+
+    /// Record proofs returned by the mint and reference of the operation
+    pub async fn record_operation_result(
+        &self,
+        operation: &CashuOperation,
+    ) -> Result<(), WalletkaError> {
+        debug!("Recording result of cashu operation {:?}", operation.id);
+
+        self.db
+            .query("UPDATE $operation SET new_proofs = $new_proofs, reference = $reference")
+            .bind(("operation", operation.id.clone()))
+            .bind(("new_proofs", operation.new_proofs.clone()))
+            .bind(("reference", operation.reference.clone()))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Mark proofs spent or unspent again, store new proofs and sent token and remove
+    /// the operation from journal in one transaction
+    pub async fn finish_operation(
+        &self,
+        operation_id: Option<Thing>,
+        spent: Vec<Thing>,
+        released: Vec<Thing>,
+        new_proofs: Vec<CashuProof>,
+        token: Option<PendingCashuToken>,
+    ) -> Result<(), WalletkaError> {
+        debug!("Finishing cashu operation {:?}", operation_id);
+
+        let mut query = self
+            .db
+            .query("BEGIN TRANSACTION")
+            .query("UPDATE $spent SET state = $spent_state")
+            .query("UPDATE $released SET state = $unspent_state")
+            .bind(("spent", spent))
+            .bind(("spent_state", CashuProofState::Spent))
+            .bind(("released", released))
+            .bind(("unspent_state", CashuProofState::Unspent))
+            .bind(("proofs_table", CASHU_PROOFS_TABLE));
+
+        for (i, proof) in new_proofs.into_iter().enumerate() {
+            query = query
+                .query(format!(
+                    "CREATE type::thing($proofs_table, $proof_id_{i}) CONTENT $proof_{i}"
+                ))
+                .bind((format!("proof_id_{i}"), digest(proof.secret.to_string())))
+                .bind((format!("proof_{i}"), proof));
+        }
+
+        if let Some(token) = token {
+            query = query
+                .query("CREATE type::thing($tokens_table, $token_id) CONTENT $token")
+                .bind(("tokens_table", PENDING_CASHU_TOKENS_TABLE))
+                .bind(("token_id", digest(token.token.clone())))
+                .bind(("token", token));
+        }
+
+        if let Some(operation_id) = operation_id {
+            query = query
+                .query("DELETE $operation")
+                .bind(("operation", operation_id));
+        }
+
+        query.query("COMMIT TRANSACTION").await?.check()?;
+
+        Ok(())
+    }
+
+    /// Keep operation in journal as failed, it isn't reconciled anymore
+    pub async fn fail_operation(&self, operation_id: Option<Thing>) -> Result<(), WalletkaError> {
+        debug!("Failing cashu operation {:?}", operation_id);
+
+        self.db
+            .query("UPDATE $operation SET failed = true")
+            .bind(("operation", operation_id))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    pub async fn get_operations(&self) -> Result<Vec<CashuOperation>, WalletkaError> {
+        let operations: Vec<CashuOperation> = self.db.select(CASHU_OPERATIONS_TABLE).await?;
+
+        Ok(operations)
+    }
+
+    /// Create or update mint quote, quotes are stored by quote id
+    pub async fn save_mint_quote(
+        &self,
+        quote: CashuMintQuote,
+    ) -> Result<CashuMintQuote, WalletkaError> {
+        debug!("Saving cashu mint quote {}", quote.quote_id);
+
+        let saved: Option<CashuMintQuote> = self
+            .db
+            .update((CASHU_MINT_QUOTES_TABLE, &quote.quote_id))
+            .content(quote)
+            .await?;
+
+        match saved {
+            Some(quote) => Ok(quote),
+            None => Err(WalletkaError::Database {
+                details: "Can't save cashu mint quote!".to_string(),
+            }),
         }
     }
+
+    pub async fn get_mint_quote(
+        &self,
+        quote_id: &str,
+    ) -> Result<Option<CashuMintQuote>, WalletkaError> {
+        let quote: Option<CashuMintQuote> =
+            self.db.select((CASHU_MINT_QUOTES_TABLE, quote_id)).await?;
+
+        Ok(quote)
+    }
+
+    pub async fn get_mint_quotes(&self) -> Result<Vec<CashuMintQuote>, WalletkaError> {
+        let mut response = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} ORDER BY datetime DESC",
+                CASHU_MINT_QUOTES_TABLE
+            ))
+            .await?;
+
+        let quotes: Vec<CashuMintQuote> = response.take(0)?;
+
+        Ok(quotes)
+    }
+
+    pub async fn get_mint_quotes_by_state(
+        &self,
+        state: CashuQuoteState,
+    ) -> Result<Vec<CashuMintQuote>, WalletkaError> {
+        let mut response = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} WHERE state = $state",
+                CASHU_MINT_QUOTES_TABLE
+            ))
+            .bind(("state", state))
+            .await?;
+
+        let quotes: Vec<CashuMintQuote> = response.take(0)?;
+
+        Ok(quotes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use cashu_sdk::nuts::Token;
+    use std::str::FromStr;
+    use surrealdb::engine::local::Mem;
+    use surrealdb::sql::Datetime;
+
+    use crate::io::entities::{CashuOperationKind, PendingCashuTokenSource};
+
+    /// Proofs of 1, 2, 4 and 8 sats
+    const PROOFS_TOKEN: &str = "cashuAeyJ0b2tlbiI6W3sibWludCI6Imh0dHBzOi8vbWludC5leGFtcGxlLmNvbSIsInByb29mcyI6W3siaWQiOiJEU0FsOW52dnlmdmEiLCJhbW91bnQiOjEsInNlY3JldCI6InNlY3JldDEiLCJDIjoiMDJjMDIwMDY3ZGI3MjdkNTg2YmMzMTgzYWVjZjk3ZmNiODAwYzNmNGNjNDc1OWY2OWM2MjZjOWRiNWQ4ZjViNWQ0In0seyJpZCI6IkRTQWw5bnZ2eWZ2YSIsImFtb3VudCI6Miwic2VjcmV0Ijoic2VjcmV0MiIsIkMiOiIwMmMwMjAwNjdkYjcyN2Q1ODZiYzMxODNhZWNmOTdmY2I4MDBjM2Y0Y2M0NzU5ZjY5YzYyNmM5ZGI1ZDhmNWI1ZDQifSx7ImlkIjoiRFNBbDludnZ5ZnZhIiwiYW1vdW50Ijo0LCJzZWNyZXQiOiJzZWNyZXQ0IiwiQyI6IjAyYzAyMDA2N2RiNzI3ZDU4NmJjMzE4M2FlY2Y5N2ZjYjgwMGMzZjRjYzQ3NTlmNjljNjI2YzlkYjVkOGY1YjVkNCJ9LHsiaWQiOiJEU0FsOW52dnlmdmEiLCJhbW91bnQiOjgsInNlY3JldCI6InNlY3JldDgiLCJDIjoiMDJjMDIwMDY3ZGI3MjdkNTg2YmMzMTgzYWVjZjk3ZmNiODAwYzNmNGNjNDc1OWY2OWM2MjZjOWRiNWQ4ZjViNWQ0In1dfV19";
+
+    fn operation(
+        kind: CashuOperationKind,
+        proofs: &[CashuProof],
+        amount_sat: u64,
+    ) -> CashuOperation {
+        CashuOperation {
+            id: None,
+            kind,
+            mint_url: "https://mint.example.com".to_string(),
+            proofs: proofs.iter().filter_map(|p| p.id.clone()).collect(),
+            amount_sat,
+            reference: None,
+            new_proofs: None,
+            failed: false,
+            datetime: Datetime::default(),
+        }
+    }
+
+    async fn proof_states(repo: &CashuRepository) -> Result<Vec<(u64, CashuProofState)>> {
+        let mut states: Vec<(u64, CashuProofState)> = repo
+            .get_proofs()
+            .await?
+            .into_iter()
+            .map(|p| (p.amount_sat, p.state))
+            .collect();
+        states.sort_by_key(|(amount_sat, _)| *amount_sat);
+
+        Ok(states)
+    }
+
+    fn mint_quote(quote_id: &str, amount_sat: u64) -> CashuMintQuote {
+        CashuMintQuote {
+            id: None,
+            quote_id: quote_id.to_string(),
+            mint_url: "https://mint.example.com".to_string(),
+            amount_sat,
+            request: format!("lnbc{}", quote_id),
+            state: CashuQuoteState::Unpaid,
+            datetime: Datetime::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn journal_operation() -> Result<()> {
+        let db = Surreal::new::<Mem>(()).await?;
+        db.use_ns("test").use_db("test").await?;
+
+        let repo = CashuRepository::new(db);
+
+        let operation = repo
+            .begin_operation(
+                operation(CashuOperationKind::Send, &[], 1_000),
+                CashuProofState::PendingSwap,
+            )
+            .await?;
+        assert_eq!(1, repo.get_operations().await?.len());
+
+        let token = PendingCashuToken {
+            id: None,
+            claimed: false,
+            datetime: Datetime::default(),
+            token: "cashuAtoken".to_string(),
+            source: PendingCashuTokenSource::Sent,
+            amount_sat: 1_000,
+        };
+        repo.finish_operation(operation.id, vec![], vec![], vec![], Some(token))
+            .await?;
+
+        assert!(repo.get_operations().await?.is_empty());
+        assert_eq!(1, repo.get_pending_tokens().await?.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn operation_proof_states() -> Result<()> {
+        let db = Surreal::new::<Mem>(()).await?;
+        db.use_ns("test").use_db("test").await?;
+
+        let repo = CashuRepository::new(db);
+
+        let mut proofs: Vec<CashuProof> = Token::from_str(PROOFS_TOKEN)
+            .unwrap()
+            .token
+            .iter()
+            .flat_map(|m| m.proofs.iter())
+            .map(CashuProof::from)
+            .collect();
+        let change = proofs.pop().unwrap();
+        let mut stored = vec![];
+        for proof in proofs {
+            stored.push(repo.store_proof(proof).await?);
+        }
+
+        let mut send = repo
+            .begin_operation(
+                operation(CashuOperationKind::Send, &stored[..2], 2),
+                CashuProofState::PendingSwap,
+            )
+            .await?;
+        let melt = repo
+            .begin_operation(
+                operation(CashuOperationKind::Melt, &stored[2..], 3),
+                CashuProofState::Reserved,
+            )
+            .await?;
+        assert_eq!(
+            vec![
+                (1, CashuProofState::PendingSwap),
+                (2, CashuProofState::PendingSwap),
+                (4, CashuProofState::Reserved),
+            ],
+            proof_states(&repo).await?
+        );
+
+        send.new_proofs = Some(vec![change.clone()]);
+        send.reference = Some("cashuAsent".to_string());
+        repo.record_operation_result(&send).await?;
+        let recorded = repo
+            .get_operations()
+            .await?
+            .into_iter()
+            .find(|o| o.id == send.id)
+            .unwrap();
+        assert_eq!(Some("cashuAsent".to_string()), recorded.reference);
+        assert_eq!(8, recorded.new_proofs.unwrap()[0].amount_sat);
+
+        let token = PendingCashuToken {
+            id: None,
+            claimed: false,
+            datetime: Datetime::default(),
+            token: "cashuAsent".to_string(),
+            source: PendingCashuTokenSource::Sent,
+            amount_sat: 2,
+        };
+        repo.finish_operation(send.id, send.proofs, vec![], vec![change], Some(token))
+            .await?;
+        repo.finish_operation(melt.id, vec![], melt.proofs, vec![], None)
+            .await?;
+
+        assert_eq!(
+            vec![
+                (1, CashuProofState::Spent),
+                (2, CashuProofState::Spent),
+                (4, CashuProofState::Unspent),
+                (8, CashuProofState::Unspent),
+            ],
+            proof_states(&repo).await?
+        );
+        assert!(repo.get_operations().await?.is_empty());
+
+        let tokens = repo.get_pending_tokens().await?;
+        assert_eq!(1, tokens.len());
+        assert_eq!("cashuAsent", tokens[0].token);
+        assert!(!tokens[0].claimed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mint_quotes_by_state() -> Result<()> {
+        let db = Surreal::new::<Mem>(()).await?;
+        db.use_ns("test").use_db("test").await?;
+
+        let repo = CashuRepository::new(db);
+
+        repo.save_mint_quote(mint_quote("aa", 1_000)).await?;
+        repo.save_mint_quote(mint_quote("bb", 2_000)).await?;
+        assert_eq!(
+            2,
+            repo.get_mint_quotes_by_state(CashuQuoteState::Unpaid)
+                .await?
+                .len()
+        );
+
+        let mut issued = mint_quote("aa", 1_000);
+        issued.state = CashuQuoteState::Issued;
+        repo.save_mint_quote(issued).await?;
+
+        let mut expired = mint_quote("bb", 2_000);
+        expired.state = CashuQuoteState::Expired;
+        repo.save_mint_quote(expired).await?;
+        repo.save_mint_quote(mint_quote("cc", 3_000)).await?;
+
+        let unpaid = repo
+            .get_mint_quotes_by_state(CashuQuoteState::Unpaid)
+            .await?;
+        assert_eq!(1, unpaid.len());
+        assert_eq!("cc", unpaid[0].quote_id);
+
+        let expired = repo
+            .get_mint_quotes_by_state(CashuQuoteState::Expired)
+            .await?;
+        assert_eq!(1, expired.len());
+        assert_eq!("bb", expired[0].quote_id);
+
+        let quote = repo.get_mint_quote("aa").await?.unwrap();
+        assert_eq!(CashuQuoteState::Issued, quote.state);
+        assert_eq!(3, repo.get_mint_quotes().await?.len());
+        assert!(repo.get_mint_quote("dd").await?.is_none());
+
+        Ok(())
+    }
 }
@@ -1,6 +1,6 @@
-use anyhow::{bail, Result};
 use surrealdb::{Connection, Surreal};
 
+use crate::errors::WalletkaError;
 use crate::io::entities::WalletkaContact;
 
 const TABLE_NAME: &str = "contacts";
@@ -20,37 +20,54 @@ where
         Self { db: database }
     }
 
-    pub async fn add(&self, contact: WalletkaContact) -> Result<WalletkaContact> {
+    pub async fn add(&self, contact: WalletkaContact) -> Result<WalletkaContact, WalletkaError> {
         let created: Vec<WalletkaContact> = self.db.create(TABLE_NAME).content(contact).await?;
 
-        Ok(created.into_iter().last().unwrap())
+        match created.into_iter().last() {
+            Some(contact) => Ok(contact),
+            None => Err(WalletkaError::Database {
+                details: "Can't create contact!".to_string(),
+            }),
+        }
     }
 
-    pub async fn get_all(&self) -> Result<Vec<WalletkaContact>> {
+    pub async fn get_all(&self) -> Result<Vec<WalletkaContact>, WalletkaError> {
         let contacts: Vec<WalletkaContact> = self.db.select(TABLE_NAME).await?;
 
         Ok(contacts)
     }
 
-    pub async fn delete(&self, contact: WalletkaContact) -> Result<()> {
-        let _: Option<WalletkaContact> =
-            self.db.delete((TABLE_NAME, contact.id.unwrap().id)).await?;
+    pub async fn delete(&self, contact: WalletkaContact) -> Result<(), WalletkaError> {
+        let id = match contact.id {
+            Some(thing) => thing.id,
+            None => {
+                return Err(WalletkaError::InvalidInput {
+                    details: "No id provided".to_string(),
+                })
+            }
+        };
+
+        let _: Option<WalletkaContact> = self.db.delete((TABLE_NAME, id)).await?;
         Ok(())
     }
 
-    pub async fn get_by_id(&self, id: &str) -> Result<WalletkaContact> {
+    pub async fn get_by_id(&self, id: &str) -> Result<WalletkaContact, WalletkaError> {
         let contact: Option<WalletkaContact> = self.db.select((TABLE_NAME, id)).await?;
 
         match contact {
             Some(contact) => Ok(contact),
-            None => bail!("COntact with id {} not found", id),
+            None => Err(WalletkaError::NotFound {
+                details: format!("Contact with id {} not found", id),
+            }),
         }
     }
 
-    pub async fn update(&self, contact: WalletkaContact) -> Result<WalletkaContact> {
+    pub async fn update(&self, contact: WalletkaContact) -> Result<WalletkaContact, WalletkaError> {
         let id = match contact.id.clone() {
             Some(thing) => thing.id,
-            None => bail!("No id provided"),
+            None => Err(WalletkaError::Database {
+                details: "No id provided".to_string(),
+            }),
         };
 
         let contact: Option<WalletkaContact> =
@@ -58,7 +75,9 @@ where
 
         match contact {
             Some(contact) => Ok(contact),
-            None => bail!("Contact not found!"),
+            None => Err(WalletkaError::NotFound {
+                details: "Contact not found!".to_string(),
+            }),
         }
     }
 }
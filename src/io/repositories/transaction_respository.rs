@@ -1,5 +1,13 @@
+use log::debug;
 use surrealdb::{Connection, Surreal};
 
+use crate::enums::WalletkaLayer;
+use crate::errors::WalletkaError;
+use crate::io::entities::WalletkaTransaction;
+use crate::types::TransactionFilter;
+
+const TRANSACTIONS_TABLE: &str = "transactions";
+
 pub struct TransactionRepository<C>
 where
     C: Connection,
@@ -7,6 +15,17 @@ where
     database: Surreal<C>,
 }
 
+impl<C> Clone for TransactionRepository<C>
+where
+    C: Connection,
+{
+    fn clone(&self) -> Self {
+        Self {
+            database: self.database.clone(),
+        }
+    }
+}
+
 impl<C> TransactionRepository<C>
 where
     C: Connection,
@@ -14,4 +33,178 @@ where
     pub fn new(database: Surreal<C>) -> Self {
         Self { database }
     }
+
+    /// Create or update transaction, transactions with `tx_id` are stored once per layer
+    pub async fn save_transaction(
+        &self,
+        transaction: WalletkaTransaction,
+    ) -> Result<WalletkaTransaction, WalletkaError> {
+        debug!("Saving transaction {:?}", transaction.tx_id);
+
+        let saved: Option<WalletkaTransaction> = match &transaction.tx_id {
+            Some(tx_id) => {
+                self.database
+                    .update((
+                        TRANSACTIONS_TABLE,
+                        transaction_key(transaction.layer, tx_id),
+                    ))
+                    .content(transaction)
+                    .await?
+            }
+            None => {
+                let created: Vec<WalletkaTransaction> = self
+                    .database
+                    .create(TRANSACTIONS_TABLE)
+                    .content(transaction)
+                    .await?;
+                created.into_iter().next()
+            }
+        };
+
+        match saved {
+            Some(transaction) => Ok(transaction),
+            None => Err(WalletkaError::Database {
+                details: "Can't save transaction!".to_string(),
+            }),
+        }
+    }
+
+    pub async fn get_transaction(
+        &self,
+        id: &str,
+    ) -> Result<Option<WalletkaTransaction>, WalletkaError> {
+        let transaction: Option<WalletkaTransaction> =
+            self.database.select((TRANSACTIONS_TABLE, id)).await?;
+
+        Ok(transaction)
+    }
+
+    pub async fn get_layer_transaction(
+        &self,
+        layer: WalletkaLayer,
+        tx_id: &str,
+    ) -> Result<Option<WalletkaTransaction>, WalletkaError> {
+        self.get_transaction(&transaction_key(layer, tx_id)).await
+    }
+
+    /// Get page of transactions matching filter, newest first
+    pub async fn get_transactions(
+        &self,
+        filter: TransactionFilter,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<WalletkaTransaction>, WalletkaError> {
+        let mut conditions = vec![];
+        if filter.layer.is_some() {
+            conditions.push("layer = $layer");
+        }
+        if filter.direction.is_some() {
+            conditions.push("direction = $direction");
+        }
+        if filter.status.is_some() {
+            conditions.push("status = $status");
+        }
+        if filter.currency_symbol.is_some() {
+            conditions.push("amount.currency.symbol = $currency_symbol");
+        }
+        if filter.contact_id.is_some() {
+            conditions.push("contact_id = $contact_id");
+        }
+
+        let condition = match conditions.is_empty() {
+            true => String::new(),
+            false => format!("WHERE {}", conditions.join(" AND ")),
+        };
+
+        let mut response = self
+            .database
+            .query(format!(
+                "SELECT * FROM {} {} ORDER BY datetime DESC LIMIT $limit START $offset",
+                TRANSACTIONS_TABLE, condition
+            ))
+            .bind(("layer", filter.layer))
+            .bind(("direction", filter.direction))
+            .bind(("status", filter.status))
+            .bind(("currency_symbol", filter.currency_symbol))
+            .bind(("contact_id", filter.contact_id))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+
+        let transactions: Vec<WalletkaTransaction> = response.take(0)?;
+
+        Ok(transactions)
+    }
+}
+
+fn transaction_key(layer: WalletkaLayer, tx_id: &str) -> String {
+    format!("{:?}_{}", layer, tx_id).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use surrealdb::engine::local::Mem;
+    use surrealdb::sql::Datetime;
+
+    use crate::enums::{TransactionDirection, TransactionStatus};
+    use crate::types::{Amount, Currency};
+
+    fn transaction(layer: WalletkaLayer, tx_id: &str, amount_sat: u64) -> WalletkaTransaction {
+        WalletkaTransaction {
+            id: None,
+            layer,
+            direction: TransactionDirection::Received,
+            status: TransactionStatus::Pending,
+            tx_id: Some(tx_id.to_string()),
+            amount: Amount::new(amount_sat, Currency::bitcoin()),
+            fee_sat: None,
+            datetime: Datetime::default(),
+            contact_id: None,
+            memo: None,
+            entries: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn filter_transactions() -> Result<()> {
+        let db = Surreal::new::<Mem>(()).await?;
+        db.use_ns("test").use_db("test").await?;
+
+        let repo = TransactionRepository::new(db);
+
+        repo.save_transaction(transaction(WalletkaLayer::Blockchain, "aa", 1_000))
+            .await?;
+        repo.save_transaction(transaction(WalletkaLayer::Cashu, "bb", 2_000))
+            .await?;
+        repo.save_transaction(transaction(WalletkaLayer::Cashu, "cc", 3_000))
+            .await?;
+
+        let mut confirmed = transaction(WalletkaLayer::Blockchain, "aa", 1_000);
+        confirmed.status = TransactionStatus::Confirmed;
+        repo.save_transaction(confirmed).await?;
+
+        let all = repo
+            .get_transactions(TransactionFilter::default(), 0, 10)
+            .await?;
+        assert_eq!(3, all.len());
+
+        let cashu = TransactionFilter {
+            layer: Some(WalletkaLayer::Cashu),
+            ..Default::default()
+        };
+        assert_eq!(2, repo.get_transactions(cashu.clone(), 0, 10).await?.len());
+        assert_eq!(1, repo.get_transactions(cashu, 1, 10).await?.len());
+
+        let confirmed = TransactionFilter {
+            status: Some(TransactionStatus::Confirmed),
+            ..Default::default()
+        };
+        let confirmed = repo.get_transactions(confirmed, 0, 10).await?;
+        assert_eq!(1, confirmed.len());
+        assert_eq!(Some("aa".to_string()), confirmed[0].tx_id);
+
+        Ok(())
+    }
 }
@@ -44,11 +44,17 @@ mod tests {
             None,
             None,
             data_path.to_string(),
+            vec![
+                types::BitcoinAccount::new(enums::BitcoinScriptType::Bip84, 0),
+                types::BitcoinAccount::new(enums::BitcoinScriptType::Bip86, 1),
+            ],
         )
         .expect("Can't create bitcoin wallet");
 
         bitcoin_wallet
             .get_unused_address()
             .expect("Can't get address");
+
+        assert_eq!(2, bitcoin_wallet.get_accounts().len());
     }
 }
@@ -0,0 +1,259 @@
+use log::debug;
+use surrealdb::Connection;
+use tokio::sync::broadcast;
+
+use crate::{
+    enums::{TransactionStatus, WalletkaEvent, WalletkaLayer},
+    errors::WalletkaError,
+    io::{
+        entities::WalletkaTransaction, repositories::transaction_respository::TransactionRepository,
+    },
+    types::{TransactionFilter, WalletkaHistoryItem},
+};
+
+const EVENTS_CAPACITY: usize = 100;
+
+/// Transaction history of all layers and events emitted when it changes
+pub struct HistoryManager<C>
+where
+    C: Connection,
+{
+    repository: TransactionRepository<C>,
+    events: broadcast::Sender<WalletkaEvent>,
+}
+
+/// Clones share the repository and event subscribers
+impl<C> Clone for HistoryManager<C>
+where
+    C: Connection,
+{
+    fn clone(&self) -> Self {
+        Self {
+            repository: self.repository.clone(),
+            events: self.events.clone(),
+        }
+    }
+}
+
+impl<C> HistoryManager<C>
+where
+    C: Connection,
+{
+    pub fn new(repository: TransactionRepository<C>) -> Self {
+        let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+
+        Self { repository, events }
+    }
+
+    /// Subscribe to wallet events, slow subscribers lose the oldest events
+    pub fn subscribe(&self) -> broadcast::Receiver<WalletkaEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn emit(&self, event: WalletkaEvent) {
+        debug!("Emitting event {:?}", event);
+
+        // Sending fails only when nobody is subscribed
+        let _ = self.events.send(event);
+    }
+
+    /// Save transaction to history, keeps details already known about the transaction
+    pub async fn record(
+        &self,
+        mut transaction: WalletkaTransaction,
+    ) -> Result<WalletkaTransaction, WalletkaError> {
+        if let Some(tx_id) = &transaction.tx_id {
+            let existing = self
+                .repository
+                .get_layer_transaction(transaction.layer, tx_id)
+                .await?;
+
+            match (transaction.layer, existing.as_ref().map(|e| e.status)) {
+                (WalletkaLayer::Blockchain, Some(TransactionStatus::Pending))
+                    if transaction.status == TransactionStatus::Confirmed =>
+                {
+                    self.emit(WalletkaEvent::TransactionConfirmed {
+                        txid: tx_id.clone(),
+                    })
+                }
+                (WalletkaLayer::Rgb, status) if status != Some(transaction.status) => {
+                    self.emit(WalletkaEvent::RgbTransferStatusChanged {
+                        transfer_id: tx_id.clone(),
+                        currency_symbol: transaction.amount.currency.symbol.clone(),
+                        status: transaction.status,
+                    })
+                }
+                _ => {}
+            }
+
+            if let Some(existing) = existing {
+                transaction.datetime = existing.datetime;
+                transaction.contact_id = transaction.contact_id.or(existing.contact_id);
+                transaction.memo = transaction.memo.or(existing.memo);
+                transaction.fee_sat = transaction.fee_sat.or(existing.fee_sat);
+                if transaction.entries.is_empty() {
+                    transaction.entries = existing.entries;
+                }
+            }
+        }
+
+        self.repository.save_transaction(transaction).await
+    }
+
+    /// Get page of transactions, newest first
+    pub async fn get_transactions(
+        &self,
+        filter: TransactionFilter,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<WalletkaHistoryItem>, WalletkaError> {
+        let transactions = self
+            .repository
+            .get_transactions(filter, offset, limit)
+            .await?;
+
+        Ok(transactions
+            .into_iter()
+            .map(WalletkaHistoryItem::from)
+            .collect())
+    }
+
+    /// Set counterparty contact and memo of transaction
+    pub async fn set_details(
+        &self,
+        id: String,
+        contact_id: Option<String>,
+        memo: Option<String>,
+    ) -> Result<WalletkaHistoryItem, WalletkaError> {
+        let mut transaction = match self.repository.get_transaction(&id).await? {
+            Some(transaction) => transaction,
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Transaction {} not found", id),
+                })
+            }
+        };
+
+        transaction.contact_id = contact_id;
+        transaction.memo = memo;
+
+        let transaction = self.repository.save_transaction(transaction).await?;
+
+        Ok(WalletkaHistoryItem::from(transaction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use surrealdb::engine::local::Mem;
+    use surrealdb::sql::Datetime;
+    use surrealdb::Surreal;
+
+    use crate::{
+        enums::TransactionDirection,
+        types::{Amount, Currency},
+    };
+
+    fn transaction(
+        layer: WalletkaLayer,
+        tx_id: &str,
+        status: TransactionStatus,
+        currency: Currency,
+    ) -> WalletkaTransaction {
+        WalletkaTransaction {
+            id: None,
+            layer,
+            direction: TransactionDirection::Received,
+            status,
+            tx_id: Some(tx_id.to_string()),
+            amount: Amount::new(1_000, currency),
+            fee_sat: None,
+            datetime: Datetime::default(),
+            contact_id: None,
+            memo: None,
+            entries: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn record_emits_events() -> Result<()> {
+        let db = Surreal::new::<Mem>(()).await?;
+        db.use_ns("test").use_db("test").await?;
+
+        let history = HistoryManager::new(TransactionRepository::new(db));
+        let mut events = history.subscribe();
+
+        let bitcoin = Currency::bitcoin();
+        let rgb = Currency::new(
+            "TST".to_string(),
+            "Test asset".to_string(),
+            "TST".to_string(),
+            0,
+        );
+
+        // New onchain transaction is not a confirmation
+        for status in [TransactionStatus::Pending, TransactionStatus::Confirmed] {
+            history
+                .record(transaction(
+                    WalletkaLayer::Blockchain,
+                    "aa",
+                    status,
+                    bitcoin.clone(),
+                ))
+                .await?;
+        }
+        history
+            .record(transaction(
+                WalletkaLayer::Blockchain,
+                "bb",
+                TransactionStatus::Confirmed,
+                bitcoin,
+            ))
+            .await?;
+
+        // RGB transfer emits every status change only
+        for status in [
+            TransactionStatus::Pending,
+            TransactionStatus::Pending,
+            TransactionStatus::Confirmed,
+        ] {
+            history
+                .record(transaction(WalletkaLayer::Rgb, "1", status, rgb.clone()))
+                .await?;
+        }
+
+        assert_eq!(
+            WalletkaEvent::TransactionConfirmed {
+                txid: "aa".to_string()
+            },
+            events.try_recv()?
+        );
+        for status in [TransactionStatus::Pending, TransactionStatus::Confirmed] {
+            assert_eq!(
+                WalletkaEvent::RgbTransferStatusChanged {
+                    transfer_id: "1".to_string(),
+                    currency_symbol: "TST".to_string(),
+                    status,
+                },
+                events.try_recv()?
+            );
+        }
+        assert!(events.try_recv().is_err());
+
+        history.emit(WalletkaEvent::NewUtxo {
+            outpoint: "aa:0".to_string(),
+            amount_sat: 1_000,
+        });
+        assert_eq!(
+            WalletkaEvent::NewUtxo {
+                outpoint: "aa:0".to_string(),
+                amount_sat: 1_000,
+            },
+            events.try_recv()?
+        );
+
+        Ok(())
+    }
+}
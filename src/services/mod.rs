@@ -1,3 +1,7 @@
 mod contacts_manager;
+mod history_manager;
+mod sync_scheduler;
 
 pub use contacts_manager::ContactsManager;
+pub use history_manager::HistoryManager;
+pub(crate) use sync_scheduler::SyncScheduler;
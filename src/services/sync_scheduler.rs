@@ -0,0 +1,220 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use tokio::runtime::Handle;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+
+use crate::{
+    enums::{LayerSyncStatus, WalletkaLayer},
+    errors::WalletkaError,
+    types::SyncSchedulerConfig,
+    walletka::SyncTarget,
+};
+
+/// Syncs blockchain and RGB layers in background on configured intervals until stopped
+/// or dropped. Cashu and nostr checks are left to `Walletka::sync`
+pub struct SyncScheduler {
+    stop: watch::Sender<bool>,
+    online: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl SyncScheduler {
+    /// Start scheduler on current tokio runtime, first sync runs immediately
+    pub(crate) fn start(
+        target: Arc<SyncTarget>,
+        config: SyncSchedulerConfig,
+    ) -> Result<Self, WalletkaError> {
+        let handle = Handle::try_current().map_err(|err| WalletkaError::Generic {
+            details: err.to_string(),
+        })?;
+        let (stop, stop_rx) = watch::channel(false);
+        let (online, online_rx) = watch::channel(true);
+
+        let task = handle.spawn(run(target, config, stop_rx, online_rx));
+
+        Ok(Self { stop, online, task })
+    }
+
+    /// Pause syncing while offline, all layers are synced when back online
+    pub fn set_online(&self, online: bool) {
+        self.online.send_replace(online);
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Stop scheduler, waits for sync in progress to finish
+    pub async fn stop(mut self) {
+        self.stop.send_replace(true);
+
+        if let Err(err) = (&mut self.task).await {
+            warn!("Sync scheduler failed: {}", err);
+        }
+    }
+}
+
+/// Dropped scheduler stops after sync in progress
+impl Drop for SyncScheduler {
+    fn drop(&mut self) {
+        self.stop.send_replace(true);
+    }
+}
+
+struct LayerSchedule {
+    layer: WalletkaLayer,
+    interval: Duration,
+    failures: u32,
+    next_sync: Instant,
+}
+
+impl LayerSchedule {
+    fn new(layer: WalletkaLayer, interval_secs: Option<u64>) -> Option<Self> {
+        interval_secs.map(|interval_secs| Self {
+            layer,
+            interval: Duration::from_secs(interval_secs),
+            failures: 0,
+            next_sync: Instant::now(),
+        })
+    }
+
+    /// Interval doubles with every failure in a row up to `max_backoff`
+    fn schedule_next(&mut self, success: bool, max_backoff: Duration) {
+        self.failures = match success {
+            true => 0,
+            false => self.failures.saturating_add(1),
+        };
+
+        self.next_sync = Instant::now() + self.delay(max_backoff);
+    }
+
+    fn delay(&self, max_backoff: Duration) -> Duration {
+        self.interval
+            .saturating_mul(2u32.saturating_pow(self.failures))
+            .min(max_backoff.max(self.interval))
+    }
+}
+
+async fn run(
+    target: Arc<SyncTarget>,
+    config: SyncSchedulerConfig,
+    mut stop: watch::Receiver<bool>,
+    mut online: watch::Receiver<bool>,
+) {
+    let max_backoff = Duration::from_secs(config.max_backoff_secs);
+    let mut schedules: Vec<LayerSchedule> = [
+        (WalletkaLayer::Blockchain, config.bitcoin_interval_secs),
+        (WalletkaLayer::Rgb, config.rgb_interval_secs),
+    ]
+    .into_iter()
+    .filter_map(|(layer, interval_secs)| LayerSchedule::new(layer, interval_secs))
+    .collect();
+
+    let mut next_sync = match schedules.iter().map(|s| s.next_sync).min() {
+        Some(next_sync) => next_sync,
+        None => {
+            info!("No layer scheduled for sync");
+            return;
+        }
+    };
+
+    loop {
+        if !*online.borrow_and_update() {
+            debug!("Sync paused while offline");
+
+            tokio::select! {
+                biased;
+                _ = stop.changed() => return,
+                _ = online.changed() => {}
+            }
+
+            for schedule in schedules.iter_mut() {
+                schedule.next_sync = Instant::now();
+            }
+            next_sync = Instant::now();
+            continue;
+        }
+
+        tokio::select! {
+            biased;
+            _ = stop.changed() => return,
+            _ = online.changed() => continue,
+            _ = time::sleep_until(next_sync) => {}
+        }
+
+        let now = Instant::now();
+        let due: Vec<WalletkaLayer> = schedules
+            .iter()
+            .filter(|s| s.next_sync <= now)
+            .map(|s| s.layer)
+            .collect();
+
+        debug!("Scheduled sync of {:?}", due);
+        let report = target.clone().sync_layers(due.clone(), true).await;
+
+        for schedule in schedules.iter_mut().filter(|s| due.contains(&s.layer)) {
+            let success = match &report {
+                Ok(report) => !report.layers.iter().any(|l| {
+                    l.layer == schedule.layer && matches!(l.status, LayerSyncStatus::Failed { .. })
+                }),
+                Err(err) => {
+                    warn!("Scheduled sync of {:?} failed: {}", schedule.layer, err);
+                    false
+                }
+            };
+
+            schedule.schedule_next(success, max_backoff);
+        }
+
+        next_sync = schedules.iter().map(|s| s.next_sync).min().unwrap_or(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let max_backoff = Duration::from_secs(100);
+        let mut schedule = LayerSchedule::new(WalletkaLayer::Blockchain, Some(10)).unwrap();
+        assert_eq!(Duration::from_secs(10), schedule.delay(max_backoff));
+
+        for delay_secs in [20, 40, 80, 100, 100] {
+            let now = Instant::now();
+            schedule.schedule_next(false, max_backoff);
+
+            assert_eq!(Duration::from_secs(delay_secs), schedule.delay(max_backoff));
+            assert!(schedule.next_sync >= now + Duration::from_secs(delay_secs));
+        }
+        assert_eq!(5, schedule.failures);
+
+        schedule.schedule_next(true, max_backoff);
+        assert_eq!(0, schedule.failures);
+        assert_eq!(Duration::from_secs(10), schedule.delay(max_backoff));
+    }
+
+    #[test]
+    fn backoff_never_below_interval() {
+        let mut schedule = LayerSchedule::new(WalletkaLayer::Rgb, Some(60)).unwrap();
+
+        schedule.schedule_next(false, Duration::from_secs(30));
+        assert_eq!(
+            Duration::from_secs(60),
+            schedule.delay(Duration::from_secs(30))
+        );
+
+        // Long outage doesn't overflow
+        schedule.failures = u32::MAX;
+        schedule.schedule_next(false, Duration::from_secs(3600));
+        assert_eq!(u32::MAX, schedule.failures);
+        assert_eq!(
+            Duration::from_secs(3600),
+            schedule.delay(Duration::from_secs(3600))
+        );
+    }
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct BatchRecipient {
+    pub address: String,
+    pub amount_sat: u64,
+}
+
+/// Output of batch payment transaction
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct BatchPaymentOutput {
+    pub address: String,
+    pub amount_sat: u64,
+    pub vout: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct BatchPaymentResult {
+    pub txid: String,
+    pub fee_sat: u64,
+    pub outputs: Vec<BatchPaymentOutput>,
+}
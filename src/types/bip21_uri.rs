@@ -0,0 +1,209 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use bdk::bitcoin::{address::NetworkUnchecked, Address, Amount, Denomination};
+use nostr_sdk::{FromBech32, PublicKey};
+use rgb_lib::wallet::Invoice;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::WalletkaError,
+    utils::{percent_encode, query_pairs},
+};
+
+/// BIP21 `bitcoin:` URI. Cashu and RGB parameters let the same URI be paid from more layers
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Bip21Uri {
+    pub address: String,
+    pub amount_sat: Option<u64>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    /// BIP78 payjoin endpoint
+    pub payjoin_endpoint: Option<String>,
+    pub payjoin_output_substitution: bool,
+    /// Nostr public key receiving Cashu tokens in direct messages, `cashu` parameter
+    pub cashu_npub: Option<String>,
+    /// RGB invoice, `rgb` parameter
+    pub rgb_invoice: Option<String>,
+}
+
+impl Bip21Uri {
+    pub fn new(address: String) -> Self {
+        Self {
+            address,
+            amount_sat: None,
+            label: None,
+            message: None,
+            payjoin_endpoint: None,
+            payjoin_output_substitution: true,
+            cashu_npub: None,
+            rgb_invoice: None,
+        }
+    }
+}
+
+impl FromStr for Bip21Uri {
+    type Err = WalletkaError;
+
+    fn from_str(uri: &str) -> Result<Self, Self::Err> {
+        let uri = uri.trim();
+        let uri = match uri.get(..8) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("bitcoin:") => &uri[8..],
+            _ => return Err(invalid_uri("Not a bitcoin URI")),
+        };
+        let (address, query) = uri.split_once('?').unwrap_or((uri, ""));
+
+        Address::<NetworkUnchecked>::from_str(address)?;
+        let mut bip21_uri = Bip21Uri::new(address.to_string());
+
+        let pairs = query_pairs(query).map_err(|err| invalid_uri(&err.to_string()))?;
+        let mut keys = vec![];
+
+        for (key, value) in pairs {
+            if keys.contains(&key) {
+                return Err(invalid_uri(&format!("Duplicate parameter {}", key)));
+            }
+            keys.push(key.clone());
+
+            match key.as_str() {
+                "amount" => bip21_uri.amount_sat = Some(parse_amount(&value)?),
+                "label" => bip21_uri.label = Some(value),
+                "message" => bip21_uri.message = Some(value),
+                "pj" => {
+                    if !value.starts_with("https://") && !value.starts_with("http://") {
+                        return Err(invalid_uri("Payjoin endpoint must be HTTP URL"));
+                    }
+                    bip21_uri.payjoin_endpoint = Some(value)
+                }
+                "pjos" => {
+                    bip21_uri.payjoin_output_substitution = match value.as_str() {
+                        "0" => false,
+                        "1" => true,
+                        _ => return Err(invalid_uri("Invalid pjos value")),
+                    }
+                }
+                "cashu" => {
+                    PublicKey::from_bech32(&value)
+                        .map_err(|err| invalid_uri(&format!("Invalid cashu npub: {}", err)))?;
+                    bip21_uri.cashu_npub = Some(value)
+                }
+                "rgb" => {
+                    Invoice::new(value.clone())
+                        .map_err(|err| invalid_uri(&format!("Invalid RGB invoice: {}", err)))?;
+                    bip21_uri.rgb_invoice = Some(value)
+                }
+                key if key.starts_with("req-") => {
+                    return Err(invalid_uri(&format!(
+                        "Unsupported required parameter {}",
+                        key
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        if bip21_uri.payjoin_endpoint.is_none() && keys.iter().any(|k| k == "pjos") {
+            return Err(invalid_uri("pjos without payjoin endpoint"));
+        }
+
+        Ok(bip21_uri)
+    }
+}
+
+impl Display for Bip21Uri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut query = vec![];
+
+        if let Some(amount_sat) = self.amount_sat {
+            query.push(format!(
+                "amount={}",
+                Amount::from_sat(amount_sat).to_string_in(Denomination::Bitcoin)
+            ));
+        }
+        if let Some(label) = &self.label {
+            query.push(format!("label={}", percent_encode(label)));
+        }
+        if let Some(message) = &self.message {
+            query.push(format!("message={}", percent_encode(message)));
+        }
+        if let Some(endpoint) = &self.payjoin_endpoint {
+            query.push(format!("pj={}", percent_encode(endpoint)));
+            if !self.payjoin_output_substitution {
+                query.push("pjos=0".to_string());
+            }
+        }
+        if let Some(npub) = &self.cashu_npub {
+            query.push(format!("cashu={}", npub));
+        }
+        if let Some(invoice) = &self.rgb_invoice {
+            query.push(format!("rgb={}", percent_encode(invoice)));
+        }
+
+        match query.is_empty() {
+            true => write!(f, "bitcoin:{}", self.address),
+            false => write!(f, "bitcoin:{}?{}", self.address, query.join("&")),
+        }
+    }
+}
+
+fn parse_amount(value: &str) -> Result<u64, WalletkaError> {
+    let amount = Amount::from_str_in(value, Denomination::Bitcoin)
+        .map_err(|err| invalid_uri(&format!("Invalid amount {}: {}", value, err)))?;
+
+    if amount == Amount::ZERO || amount > Amount::MAX_MONEY {
+        return Err(invalid_uri(&format!("Amount {} out of range", value)));
+    }
+
+    Ok(amount.to_sat())
+}
+
+fn invalid_uri(details: &str) -> WalletkaError {
+    WalletkaError::InvalidUri {
+        details: details.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+
+    #[test]
+    fn parse_payjoin_uri() -> Result<(), WalletkaError> {
+        let uri = Bip21Uri::from_str(&format!(
+            "bitcoin:{}?amount=0.0001&label=Coffee%20shop&pj=https%3A%2F%2Fexample.com%2Fpj&pjos=0",
+            ADDRESS
+        ))?;
+
+        assert_eq!(ADDRESS, uri.address);
+        assert_eq!(Some(10_000), uri.amount_sat);
+        assert_eq!(Some("Coffee shop".to_string()), uri.label);
+        assert_eq!(
+            Some("https://example.com/pj".to_string()),
+            uri.payjoin_endpoint
+        );
+        assert!(!uri.payjoin_output_substitution);
+        assert_eq!(uri, Bip21Uri::from_str(&uri.to_string())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_invalid_uri() {
+        for uri in [
+            format!("lightning:{}", ADDRESS),
+            "bitcoin:notanaddress".to_string(),
+            format!("bitcoin:{}?amount=abc", ADDRESS),
+            format!("bitcoin:{}?amount=0", ADDRESS),
+            format!("bitcoin:{}?amount=21000001", ADDRESS),
+            format!("bitcoin:{}?amount=1&amount=2", ADDRESS),
+            format!("bitcoin:{}?label=%zz", ADDRESS),
+            format!("bitcoin:{}?req-unknown=1", ADDRESS),
+            format!("bitcoin:{}?cashu=npub1invalid", ADDRESS),
+            format!("bitcoin:{}?pjos=0", ADDRESS),
+        ] {
+            assert!(Bip21Uri::from_str(&uri).is_err(), "{} accepted", uri);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::enums::BitcoinScriptType;
+
+/// Bitcoin account derived from the wallet seed (m/purpose'/coin_type'/index')
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct BitcoinAccount {
+    pub script_type: BitcoinScriptType,
+    pub index: u32,
+}
+
+impl BitcoinAccount {
+    pub fn new(script_type: BitcoinScriptType, index: u32) -> Self {
+        Self { script_type, index }
+    }
+
+    /// Name of the sled tree holding the account data
+    pub fn tree_name(&self) -> String {
+        format!("BIP{}_{}", self.script_type.purpose(), self.index)
+    }
+}
+
+impl Default for BitcoinAccount {
+    fn default() -> Self {
+        Self {
+            script_type: BitcoinScriptType::Bip84,
+            index: 0,
+        }
+    }
+}
+
+impl Display for BitcoinAccount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bip{}/{}", self.script_type.purpose(), self.index)
+    }
+}
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// Onchain transaction built but neither signed nor broadcasted
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BitcoinTxPreview {
+    pub fee_sat: u64,
+    pub vsize: u64,
+    pub fee_rate: f32,
+    /// Selected inputs as txid:vout
+    pub inputs: Vec<String>,
+}
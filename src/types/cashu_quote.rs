@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{enums::CashuQuoteState, io::entities::CashuMintQuote};
+
+/// Lightning invoice which mints Cashu ecash into the wallet once paid
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct CashuQuote {
+    pub quote_id: String,
+    pub mint_url: String,
+    pub amount_sat: u64,
+    /// Bolt11 invoice to pay
+    pub request: String,
+    pub state: CashuQuoteState,
+}
+
+impl From<CashuMintQuote> for CashuQuote {
+    fn from(value: CashuMintQuote) -> Self {
+        Self {
+            quote_id: value.quote_id,
+            mint_url: value.mint_url,
+            amount_sat: value.amount_sat,
+            request: value.request,
+            state: value.state,
+        }
+    }
+}
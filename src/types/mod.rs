@@ -1,6 +1,24 @@
 mod amount;
+mod batch_payment;
+mod bip21_uri;
+mod bitcoin_account;
+mod bitcoin_tx_preview;
+mod cashu_quote;
+mod payment_receipt;
+mod sync_report;
+mod sync_scheduler_config;
+mod transaction_history;
 mod walletka_asset;
 mod walletka_balance;
 pub use amount::{Amount, Currency};
+pub use batch_payment::{BatchPaymentOutput, BatchPaymentResult, BatchRecipient};
+pub use bip21_uri::Bip21Uri;
+pub use bitcoin_account::BitcoinAccount;
+pub use bitcoin_tx_preview::BitcoinTxPreview;
+pub use cashu_quote::CashuQuote;
+pub use payment_receipt::PaymentReceipt;
+pub use sync_report::{LayerSyncResult, SyncReport};
+pub use sync_scheduler_config::SyncSchedulerConfig;
+pub use transaction_history::{TransactionFilter, WalletkaHistoryItem};
 pub use walletka_asset::WalletkaAsset;
 pub use walletka_balance::WalletkaBalance;
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{enums::WalletkaLayer, io::entities::WalletkaTransaction};
+
+use super::Amount;
+
+/// Result of payment made on any layer
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct PaymentReceipt {
+    pub layer: WalletkaLayer,
+    /// Onchain txid, RGB transfer index or hash of Cashu token
+    pub tx_id: Option<String>,
+    pub amount: Amount,
+    /// Onchain fee is known after next sync
+    pub fee_sat: Option<u64>,
+    /// Id of transaction history entry
+    pub history_id: String,
+    /// Cashu token, receiver can't claim it until it is delivered
+    pub token: Option<String>,
+    /// Token was sent but couldn't be delivered to receiver,
+    /// it stays pending and can be passed to receiver other way
+    pub delivery_error: Option<String>,
+}
+
+impl From<WalletkaTransaction> for PaymentReceipt {
+    fn from(value: WalletkaTransaction) -> Self {
+        Self {
+            layer: value.layer,
+            tx_id: value.tx_id,
+            amount: value.amount,
+            fee_sat: value.fee_sat,
+            history_id: value.id.map(|id| id.id.to_string()).unwrap_or_default(),
+            token: None,
+            delivery_error: None,
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::enums::{LayerSyncStatus, WalletkaLayer};
+
+/// Progress or result of syncing single layer
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct LayerSyncResult {
+    pub layer: WalletkaLayer,
+    pub status: LayerSyncStatus,
+    pub duration_ms: u64,
+    /// Number of new or updated transactions and transfers
+    pub changes: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub layers: Vec<LayerSyncResult>,
+}
+
+impl SyncReport {
+    pub fn is_success(&self) -> bool {
+        self.layers
+            .iter()
+            .all(|l| l.status == LayerSyncStatus::Synced)
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// Intervals of background sync, layer is not synced when its interval is not set
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SyncSchedulerConfig {
+    pub bitcoin_interval_secs: Option<u64>,
+    /// Light refresh of RGB transfers waiting for counterparty
+    pub rgb_interval_secs: Option<u64>,
+    /// Upper limit of interval extended after failed syncs
+    pub max_backoff_secs: u64,
+}
+
+impl Default for SyncSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            bitcoin_interval_secs: Some(600),
+            rgb_interval_secs: Some(60),
+            max_backoff_secs: 3600,
+        }
+    }
+}
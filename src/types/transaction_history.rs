@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enums::{TransactionDirection, TransactionStatus, WalletkaLayer},
+    io::entities::{WalletkaTransaction, WalletkaTransactionEntry},
+};
+
+use super::Amount;
+
+/// Transaction history entry as returned to wallet users
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct WalletkaHistoryItem {
+    pub id: String,
+    pub layer: WalletkaLayer,
+    pub direction: TransactionDirection,
+    pub status: TransactionStatus,
+    pub tx_id: Option<String>,
+    pub amount: Amount,
+    pub fee_sat: Option<u64>,
+    /// Unix timestamp in seconds
+    pub timestamp: u64,
+    pub contact_id: Option<String>,
+    pub memo: Option<String>,
+    pub entries: Vec<WalletkaTransactionEntry>,
+}
+
+impl From<WalletkaTransaction> for WalletkaHistoryItem {
+    fn from(value: WalletkaTransaction) -> Self {
+        Self {
+            id: value.id.map(|id| id.id.to_string()).unwrap_or_default(),
+            layer: value.layer,
+            direction: value.direction,
+            status: value.status,
+            tx_id: value.tx_id,
+            amount: value.amount,
+            fee_sat: value.fee_sat,
+            timestamp: value.datetime.0.timestamp().max(0) as u64,
+            contact_id: value.contact_id,
+            memo: value.memo,
+            entries: value.entries,
+        }
+    }
+}
+
+/// Filter of transaction history, unset fields match any transaction
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct TransactionFilter {
+    pub layer: Option<WalletkaLayer>,
+    pub direction: Option<TransactionDirection>,
+    pub status: Option<TransactionStatus>,
+    pub currency_symbol: Option<String>,
+    pub contact_id: Option<String>,
+}
@@ -4,10 +4,11 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     enums::{WalletkaAssetLocation, WalletkaAssetState, WalletkaLayer},
-    io::entities::{CashuProof, PendingCashuToken},
+    errors::WalletkaError,
+    io::entities::{CashuProof, CashuProofState, PendingCashuToken},
 };
 
-use super::{Amount, Currency};
+use super::{Amount, BitcoinAccount, Currency};
 
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
 pub struct WalletkaAsset {
@@ -15,10 +16,12 @@ pub struct WalletkaAsset {
     pub asset_location: WalletkaAssetLocation,
     pub asset_state: WalletkaAssetState,
     pub amount: Amount,
+    pub bitcoin_account: Option<BitcoinAccount>,
+    pub label: Option<String>,
 }
 
-impl From<LocalUtxo> for WalletkaAsset {
-    fn from(value: LocalUtxo) -> Self {
+impl From<(BitcoinAccount, LocalUtxo)> for WalletkaAsset {
+    fn from((account, value): (BitcoinAccount, LocalUtxo)) -> Self {
         Self {
             layer: WalletkaLayer::Blockchain,
             asset_location: WalletkaAssetLocation::Utxo {
@@ -30,33 +33,49 @@ impl From<LocalUtxo> for WalletkaAsset {
                 WalletkaAssetState::Settled
             },
             amount: Amount::new(value.txout.value, Currency::bitcoin()),
+            bitcoin_account: Some(account),
+            label: None,
         }
     }
 }
 
-impl From<CashuProof> for WalletkaAsset {
-    fn from(value: CashuProof) -> Self {
-        Self {
+impl TryFrom<CashuProof> for WalletkaAsset {
+    type Error = WalletkaError;
+
+    fn try_from(value: CashuProof) -> Result<Self, Self::Error> {
+        Ok(Self {
             layer: WalletkaLayer::Cashu,
             asset_location: WalletkaAssetLocation::Cashu {
-                loc: value.id.unwrap().id.to_string(),
+                loc: value.record_id()?,
+            },
+            asset_state: match value.state {
+                CashuProofState::Unspent => WalletkaAssetState::Settled,
+                CashuProofState::Reserved | CashuProofState::PendingSwap => {
+                    WalletkaAssetState::Waiting
+                }
+                CashuProofState::Spent => WalletkaAssetState::Spent,
             },
-            asset_state: WalletkaAssetState::Settled,
             amount: Amount::new(value.amount_sat, Currency::bitcoin()),
-        }
+            bitcoin_account: None,
+            label: None,
+        })
     }
 }
 
-impl From<PendingCashuToken> for WalletkaAsset {
-    fn from(value: PendingCashuToken) -> Self {
-        Self {
+impl TryFrom<PendingCashuToken> for WalletkaAsset {
+    type Error = WalletkaError;
+
+    fn try_from(value: PendingCashuToken) -> Result<Self, Self::Error> {
+        Ok(Self {
             layer: WalletkaLayer::Cashu,
             asset_location: WalletkaAssetLocation::Cashu {
-                loc: value.id.clone().unwrap().id.to_string(),
+                loc: value.record_id()?,
             },
             asset_state: WalletkaAssetState::Waiting,
             amount: Amount::new(value.amount_sat, Currency::bitcoin()),
-        }
+            bitcoin_account: None,
+            label: None,
+        })
     }
 }
 
@@ -69,6 +88,8 @@ impl From<Unspent> for WalletkaAsset {
             },
             asset_state: WalletkaAssetState::Unspendable,
             amount: Amount::new(value.utxo.btc_amount, Currency::bitcoin()),
+            bitcoin_account: None,
+            label: None,
         }
     }
 }
@@ -90,6 +111,8 @@ impl From<AssetNIA> for WalletkaAsset {
                     value.precision as u64,
                 ),
             ),
+            bitcoin_account: None,
+            label: None,
         }
     }
 }
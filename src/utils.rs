@@ -1,5 +1,6 @@
 use std::fs;
 
+use anyhow::{bail, Result};
 use bdk::{
     keys::{
         bip39::{Language, Mnemonic, WordCount},
@@ -45,3 +46,54 @@ pub fn load_mnemonic(path: &str) -> Result<Mnemonic, anyhow::Error> {
         Err(e) => Err(e.into()),
     }
 }
+
+/// Split URI query to pairs of lowercase key and decoded value
+pub(crate) fn query_pairs(query: &str) -> Result<Vec<(String, String)>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((key.to_lowercase(), percent_decode(value)?))
+        })
+        .collect()
+}
+
+pub(crate) fn percent_decode(value: &str) -> Result<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => match value
+                .get(i + 1..i + 3)
+                .map(|hex| u8::from_str_radix(hex, 16))
+            {
+                Some(Ok(byte)) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                _ => bail!("Invalid percent encoding in {}", value),
+            },
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    Ok(String::from_utf8(decoded)?)
+}
+
+pub(crate) fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -1,21 +1,69 @@
 use itertools::Itertools;
 
-use anyhow::Result;
-use bdk::bitcoin::{psbt::PartiallySignedTransaction, Address, Transaction};
-use log::info;
+use bdk::bitcoin::{
+    consensus::encode::serialize_hex, hashes::hex::FromHex, psbt::PartiallySignedTransaction,
+    Address, OutPoint, ScriptBuf, Transaction, TxOut, Txid,
+};
+use log::{debug, info, warn};
+use nostr_sdk::{FromBech32, PublicKey, Timestamp, ToBech32};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::Instant,
+};
+use surrealdb::{engine::local::Db, sql::Datetime};
+use tokio::{
+    runtime::{Handle, RuntimeFlavor},
+    sync::broadcast,
+    task,
+};
 
 use crate::{
-    enums::WalletkaAssetState,
-    io::entities::CashuMint,
-    types::{Amount, WalletkaAsset, WalletkaBalance},
-    wallets::{bitcoin::BitcoinWallet, cashu::CashuWallet, rgb::RgbWallet, NestedWallet},
+    enums::{
+        FeePolicy, LayerSyncStatus, MessageSignatureFormat, PaymentDestination,
+        TransactionDirection, TransactionStatus, WalletkaAssetState, WalletkaEvent, WalletkaLayer,
+    },
+    errors::WalletkaError,
+    io::{
+        clients::{NostrClient, PayjoinClient, PayjoinParams},
+        entities::{
+            CashuMint, CashuMintQuote, SilentPaymentOutput, Txo, WalletkaTransaction,
+            WalletkaTransactionEntry,
+        },
+        repositories::{
+            bitcoin_repository::BitcoinRepository, transaction_respository::TransactionRepository,
+        },
+    },
+    services::{HistoryManager, SyncScheduler},
+    types::{
+        Amount, BatchPaymentResult, BatchRecipient, Bip21Uri, BitcoinAccount, BitcoinTxPreview,
+        CashuQuote, Currency, LayerSyncResult, PaymentReceipt, SyncReport, SyncSchedulerConfig,
+        TransactionFilter, WalletkaAsset, WalletkaBalance, WalletkaHistoryItem,
+    },
+    wallets::{
+        bitcoin::BitcoinWallet,
+        cashu::{decode_token, CashuWallet},
+        rgb::RgbWallet,
+        silent_payments::SilentPaymentAddress,
+        NestedWallet,
+    },
 };
 
+/// Used for RGB transfers when no fee policy is given
+const DEFAULT_FEE_POLICY: FeePolicy = FeePolicy::ConfirmationTarget { blocks: 6 };
+
 pub struct Walletka
 {
     bitcoin_wallet: BitcoinWallet,
     cashu_wallet: CashuWallet,
-    rgb_wallet: RgbWallet,
+    rgb_wallet: Option<Arc<Mutex<RgbWallet>>>,
+    bitcoin_repository: BitcoinRepository<Db>,
+    history: HistoryManager<Db>,
+    nostr_client: Option<NostrClient>,
+    messages_checked_at: Timestamp,
+    sync_scheduler: Option<SyncScheduler>,
 }
 
 impl Walletka
@@ -23,42 +71,352 @@ impl Walletka
     pub fn new(
         bitcoin_wallet: BitcoinWallet,
         cashu_wallet: CashuWallet,
-        rgb_wallet: RgbWallet,
+        rgb_wallet: Option<RgbWallet>,
+        bitcoin_repository: BitcoinRepository<Db>,
+        transaction_repository: TransactionRepository<Db>,
+        nostr_client: Option<NostrClient>,
     ) -> Self {
         Self {
             bitcoin_wallet,
             cashu_wallet,
-            rgb_wallet,
+            rgb_wallet: rgb_wallet.map(|rgb_wallet| Arc::new(Mutex::new(rgb_wallet))),
+            bitcoin_repository,
+            history: HistoryManager::new(transaction_repository),
+            nostr_client,
+            messages_checked_at: Timestamp::now(),
+            sync_scheduler: None,
+        }
+    }
+
+    /// Subscribe to wallet events produced by sync and claims.
+    /// Slow subscribers lose the oldest events
+    pub fn subscribe(&self) -> broadcast::Receiver<WalletkaEvent> {
+        self.history.subscribe()
+    }
+
+    /// Sync wallets, failure of one layer doesn't stop sync of others
+    pub async fn sync(&mut self, light: bool) -> Result<SyncReport, WalletkaError> {
+        self.sync_with_progress(light, &|_| {}).await
+    }
+
+    /// Sync layers concurrently, `on_progress` is called when layer sync starts and finishes
+    pub async fn sync_with_progress(
+        &mut self,
+        light: bool,
+        on_progress: &(dyn Fn(LayerSyncResult) + Sync),
+    ) -> Result<SyncReport, WalletkaError> {
+        self.sync_layers(
+            &[WalletkaLayer::Blockchain, WalletkaLayer::Rgb],
+            light,
+            on_progress,
+        )
+        .await
+    }
+
+    /// Sync given layers concurrently, layers not held by the wallet are skipped
+    pub async fn sync_layers(
+        &mut self,
+        layers: &[WalletkaLayer],
+        light: bool,
+        on_progress: &(dyn Fn(LayerSyncResult) + Sync),
+    ) -> Result<SyncReport, WalletkaError> {
+        let utxos_before = utxo_outpoints(&self.bitcoin_wallet);
+
+        let results = {
+            let bitcoin_wallet = match layers.contains(&WalletkaLayer::Blockchain) {
+                true => Some(&self.bitcoin_wallet),
+                false => None,
+            };
+            let rgb_wallet = match layers.contains(&WalletkaLayer::Rgb) {
+                true => self.rgb_wallet.as_deref(),
+                false => None,
+            };
+            let sync = move || sync_wallets(bitcoin_wallet, rgb_wallet, light, on_progress);
+
+            // Layers are synced by blocking clients, multi-thread runtime worker is handed over
+            // meanwhile. Other runtimes can't hand the thread over and are blocked until sync ends
+            match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+                Ok(RuntimeFlavor::MultiThread) => task::block_in_place(sync),
+                _ => sync(),
+            }
+        };
+
+        for event in new_utxo_events(&self.bitcoin_wallet, utxos_before) {
+            self.history.emit(event);
+        }
+
+        if let Err(err) = self.update_history().await {
+            warn!("Can't update history: {}", err);
+        }
+
+        if let Err(err) = self.check_direct_messages().await {
+            warn!("Can't get nostr messages: {}", err);
+        }
+
+        if let Err(err) = self.reconcile_cashu_operations().await {
+            warn!("Can't reconcile Cashu operations: {}", err);
+        }
+
+        if let Err(err) = self.mint_paid_cashu_quotes().await {
+            warn!("Can't mint paid Cashu quotes: {}", err);
+        }
+
+        if let Err(err) = self.check_sent_cashu_tokens().await {
+            warn!("Can't check sent Cashu tokens: {}", err);
+        }
+
+        if let Err(err) = self.reclaim_expired_cashu_tokens().await {
+            warn!("Can't reclaim expired Cashu tokens: {}", err);
+        }
+
+        Ok(SyncReport { layers: results })
+    }
+
+    /// Sync blockchain and RGB layers in background on current tokio runtime.
+    /// Replaces running scheduler, should be stopped when the app goes to background
+    pub async fn start_sync_scheduler(
+        &mut self,
+        config: SyncSchedulerConfig,
+    ) -> Result<(), WalletkaError> {
+        self.stop_sync_scheduler().await;
+
+        let target = SyncTarget {
+            bitcoin_wallet: Mutex::new(self.bitcoin_wallet.sync_view()?),
+            rgb_wallet: self.rgb_wallet.clone(),
+            history: self.history.clone(),
+        };
+        self.sync_scheduler = Some(SyncScheduler::start(Arc::new(target), config)?);
+
+        Ok(())
+    }
+
+    /// Stop sync scheduler, waits for sync in progress to finish
+    pub async fn stop_sync_scheduler(&mut self) {
+        if let Some(sync_scheduler) = self.sync_scheduler.take() {
+            sync_scheduler.stop().await;
+        }
+    }
+
+    pub fn is_sync_scheduler_running(&self) -> bool {
+        self.sync_scheduler
+            .as_ref()
+            .is_some_and(|sync_scheduler| sync_scheduler.is_running())
+    }
+
+    /// Pause scheduled sync while offline, all layers are synced when back online
+    pub fn set_online(&self, online: bool) {
+        if let Some(sync_scheduler) = self.sync_scheduler.as_ref() {
+            sync_scheduler.set_online(online);
+        }
+    }
+
+    /// Emit nostr direct messages received since last check
+    async fn check_direct_messages(&mut self) -> Result<(), WalletkaError> {
+        let nostr_client = match &self.nostr_client {
+            Some(nostr_client) => nostr_client,
+            None => return Ok(()),
+        };
+
+        let checked_at = Timestamp::now();
+        let messages = nostr_client
+            .get_nip04_messages(self.messages_checked_at)
+            .await
+            .map_err(|err| WalletkaError::Nostr {
+                details: err.to_string(),
+            })?;
+
+        // Messages are checked again when any of them can't be emitted
+        let mut events = vec![];
+        for (event, message) in messages {
+            events.push(WalletkaEvent::NostrDirectMessage {
+                sender: event.pubkey.to_bech32()?,
+                message,
+            });
+        }
+
+        for event in events {
+            self.history.emit(event);
+        }
+        self.messages_checked_at = checked_at;
+
+        Ok(())
+    }
+
+    /// Finish or fail Cashu operations interrupted by crash, the mint may have been
+    /// unreachable since
+    pub(crate) async fn reconcile_cashu_operations(&mut self) -> Result<(), WalletkaError> {
+        for transaction in self.cashu_wallet.reconcile_operations().await? {
+            self.history.record(transaction).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Mint proofs of Cashu quotes paid since last check
+    async fn mint_paid_cashu_quotes(&mut self) -> Result<(), WalletkaError> {
+        for (quote, transaction) in self.cashu_wallet.mint_paid_quotes().await? {
+            self.record_cashu_mint(quote, transaction).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Confirm history entries of sent Cashu tokens redeemed by their recipients
+    async fn check_sent_cashu_tokens(&mut self) -> Result<(), WalletkaError> {
+        for transaction in self.cashu_wallet.check_sent_tokens().await? {
+            let transaction = self.history.record(transaction).await?;
+
+            self.history.emit(WalletkaEvent::CashuTokenRedeemed {
+                tx_id: transaction.tx_id.unwrap_or_default(),
+                amount_sat: transaction.amount.value,
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn reclaim_expired_cashu_tokens(&mut self) -> Result<(), WalletkaError> {
+        for transaction in self.cashu_wallet.reclaim_expired_tokens().await? {
+            self.record_cashu_reclaim(transaction).await?;
         }
+
+        Ok(())
     }
 
-    /// Sync wallets
-    pub async fn sync(&mut self, light: bool) -> Result<()> {
-        // Todo: Parallelize
-        self.bitcoin_wallet.sync()?;
-        self.rgb_wallet.sync(None, light)?;
+    /// Store onchain transactions and RGB transfers known to the wallets in history
+    async fn update_history(&mut self) -> Result<(), WalletkaError> {
+        let transactions = layer_transactions(&self.bitcoin_wallet, self.rgb_wallet.as_deref())?;
+
+        for transaction in transactions {
+            self.history.record(transaction).await?;
+        }
 
         Ok(())
     }
 
-    pub fn sign_psbt(&self, psbt: &mut PartiallySignedTransaction) -> Result<()> {
+    /// Get page of transaction history of all layers, newest first
+    pub async fn get_transactions(
+        &self,
+        filter: TransactionFilter,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<WalletkaHistoryItem>, WalletkaError> {
+        self.history.get_transactions(filter, offset, limit).await
+    }
+
+    /// Set counterparty contact and memo of transaction in history
+    pub async fn set_transaction_details(
+        &self,
+        id: String,
+        contact_id: Option<String>,
+        memo: Option<String>,
+    ) -> Result<WalletkaHistoryItem, WalletkaError> {
+        self.history.set_details(id, contact_id, memo).await
+    }
+
+    pub fn sign_psbt(&self, psbt: &mut PartiallySignedTransaction) -> Result<(), WalletkaError> {
         self.bitcoin_wallet.sign_psbt(psbt)
     }
 
     /// Broadcast transaction to the mempool
-    pub fn broadcast_tx(&self, transaction: &Transaction) -> Result<()> {
+    pub fn broadcast_tx(&self, transaction: &Transaction) -> Result<(), WalletkaError> {
         self.bitcoin_wallet.broadcast_tx(&transaction)
     }
 
+    // PSBT functions, PSBTs are base64 encoded
+
+    /// Parse and validate base64 PSBT
+    pub fn import_psbt(&self, psbt: &str) -> Result<PartiallySignedTransaction, WalletkaError> {
+        Ok(PartiallySignedTransaction::from_str(psbt.trim())?)
+    }
+
+    /// Create unsigned PSBT paying to address
+    pub fn create_psbt(
+        &self,
+        address: String,
+        amount_sat: u64,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<String>,
+    ) -> Result<String, WalletkaError> {
+        let address = self.bitcoin_wallet.parse_address(&address)?;
+        let utxos = parse_outpoints(utxos)?;
+
+        let psbt = self
+            .bitcoin_wallet
+            .create_psbt(address, amount_sat, true, fee_policy, utxos)?;
+
+        Ok(psbt.to_string())
+    }
+
+    /// Add signatures of Walletka keys to PSBT
+    pub fn sign_psbt_base64(&self, psbt: String) -> Result<String, WalletkaError> {
+        let mut psbt = self.import_psbt(&psbt)?;
+        self.sign_psbt(&mut psbt)?;
+
+        Ok(psbt.to_string())
+    }
+
+    /// Merge signatures from PSBTs of other signers
+    pub fn combine_psbts(&self, psbts: Vec<String>) -> Result<String, WalletkaError> {
+        let mut imported = vec![];
+        for psbt in psbts {
+            imported.push(self.import_psbt(&psbt)?);
+        }
+
+        let combined = self.bitcoin_wallet.combine_psbts(imported)?;
+
+        Ok(combined.to_string())
+    }
+
+    /// Finalize PSBT and extract raw transaction hex
+    pub fn finalize_psbt(&self, psbt: String) -> Result<String, WalletkaError> {
+        let mut psbt = self.import_psbt(&psbt)?;
+        self.bitcoin_wallet.finalize_psbt(&mut psbt)?;
+
+        Ok(serialize_hex(&psbt.extract_tx()))
+    }
+
+    /// Finalize PSBT and broadcast extracted transaction
+    pub fn broadcast_psbt(&self, psbt: String) -> Result<String, WalletkaError> {
+        let mut psbt = self.import_psbt(&psbt)?;
+        self.bitcoin_wallet.finalize_psbt(&mut psbt)?;
+
+        let tx = psbt.extract_tx();
+        self.broadcast_tx(&tx)?;
+
+        info!("PSBT transaction broadcasted: {}", tx.txid());
+        Ok(tx.txid().to_string())
+    }
+
     /// Get all assets held by Walletka
-    pub async fn get_assets(&self) -> Result<Vec<WalletkaAsset>> {
+    pub async fn get_assets(&self) -> Result<Vec<WalletkaAsset>, WalletkaError> {
         let mut walletka_assets: Vec<WalletkaAsset> = vec![];
 
+        let txos: HashMap<String, Txo> = self
+            .bitcoin_repository
+            .get_txos()
+            .await?
+            .into_iter()
+            .map(|t| (t.outpoint(), t))
+            .collect();
+
         let mut utxos: Vec<WalletkaAsset> = self
             .bitcoin_wallet
             .get_utxos()?
             .into_iter()
-            .map(WalletkaAsset::from)
+            .map(|(account, utxo)| {
+                let txo = txos.get(&utxo.outpoint.to_string());
+                let mut asset = WalletkaAsset::from((account, utxo));
+
+                if let Some(txo) = txo {
+                    asset.label = txo.label.clone();
+                    if txo.frozen {
+                        asset.asset_state = WalletkaAssetState::Unspendable;
+                    }
+                }
+
+                asset
+            })
             .collect();
 
         let mut cashu_tokens: Vec<WalletkaAsset> = self
@@ -66,31 +424,37 @@ impl Walletka
             .get_proofs()
             .await?
             .into_iter()
-            .map(WalletkaAsset::from)
-            .collect();
+            .map(WalletkaAsset::try_from)
+            .collect::<Result<_, _>>()?;
 
         let mut cashu_pending_tokens: Vec<WalletkaAsset> = self
             .cashu_wallet
             .get_pending_tokens()
             .await?
             .into_iter()
-            .map(WalletkaAsset::from)
-            .collect();
+            .filter(|t| !t.claimed)
+            .map(WalletkaAsset::try_from)
+            .collect::<Result<_, _>>()?;
 
-        let mut rgb_utxos: Vec<WalletkaAsset> = self
-            .rgb_wallet
-            .get_utxos()?
-            .into_iter()
-            .filter(|u| u.utxo.colorable)
-            .map(WalletkaAsset::from)
-            .collect();
+        let mut rgb_utxos: Vec<WalletkaAsset> = vec![];
+        let mut rgb_assets: Vec<WalletkaAsset> = vec![];
 
-        let mut rgb_assets: Vec<WalletkaAsset> = self
-            .rgb_wallet
-            .get_rgb20_assets()?
-            .into_iter()
-            .map(WalletkaAsset::from)
-            .collect();
+        if let Some(rgb_wallet) = self.rgb_wallet.as_deref() {
+            let rgb_wallet = lock_rgb(rgb_wallet);
+
+            rgb_utxos = rgb_wallet
+                .get_utxos()?
+                .into_iter()
+                .filter(|u| u.utxo.colorable)
+                .map(WalletkaAsset::from)
+                .collect();
+
+            rgb_assets = rgb_wallet
+                .get_rgb20_assets()?
+                .into_iter()
+                .map(WalletkaAsset::from)
+                .collect();
+        }
 
         walletka_assets.append(&mut utxos);
         walletka_assets.append(&mut cashu_tokens);
@@ -101,131 +465,1026 @@ impl Walletka
         Ok(walletka_assets)
     }
 
+    /// Watch-only Walletka holds no private keys and can't sign
+    pub fn is_watch_only(&self) -> bool {
+        self.bitcoin_wallet.is_watch_only()
+    }
+
+    /// Layers held by the wallet, RGB is left out in watch-only mode and with BIP39 passphrase
+    pub fn get_layers(&self) -> Vec<WalletkaLayer> {
+        let mut layers = vec![WalletkaLayer::Blockchain, WalletkaLayer::Cashu];
+        if self.rgb_wallet.is_some() {
+            layers.push(WalletkaLayer::Rgb);
+        }
+
+        layers
+    }
+
     /// Get onchain address
-    pub fn get_bitcoin_address(&self) -> Result<Address> {
+    pub fn get_bitcoin_address(&self) -> Result<Address, WalletkaError> {
         self.bitcoin_wallet.get_unused_address()
     }
 
-    /// Get all assets grouped by currency
-    pub async fn get_balance(&self, currency_symbol: Option<String>) -> Result<WalletkaBalance> {
-        let mut walletka_balance = WalletkaBalance::default();
-
-        let mut assets = self.get_assets().await?;
+    /// Get BIP21 URI receiving to unused address. With `cashu` the URI can be paid
+    /// by Cashu token sent to wallet's nostr public key, RGB invoice can be added as well
+    pub fn create_bip21_uri(
+        &self,
+        amount_sat: Option<u64>,
+        label: Option<String>,
+        message: Option<String>,
+        cashu: bool,
+        rgb_invoice: Option<String>,
+    ) -> Result<String, WalletkaError> {
+        let mut uri = Bip21Uri::new(self.bitcoin_wallet.get_unused_address()?.to_string());
+        uri.amount_sat = amount_sat;
+        uri.label = label;
+        uri.message = message;
 
-        if let Some(symbol) = currency_symbol {
-            assets = assets
-                .into_iter()
-                .filter(|a| a.amount.currency.symbol == symbol)
-                .collect();
+        if cashu {
+            uri.cashu_npub = match &self.nostr_client {
+                Some(nostr_client) => Some(nostr_client.pub_key.to_bech32()?),
+                None => {
+                    return Err(WalletkaError::Nostr {
+                        details: "Nostr is not available in watch-only mode".to_string(),
+                    })
+                }
+            };
+        }
+        if let Some(invoice) = rgb_invoice {
+            RgbWallet::parse_invoice(&invoice)?;
+            uri.rgb_invoice = Some(invoice);
         }
 
-        let by_currency = assets.into_iter().group_by(|a| a.amount.currency.clone());
+        Ok(uri.to_string())
+    }
 
-        for assets in by_currency.into_iter() {
-            let mut confirmed_value = 0;
-            let mut unconfirmed_value = 0;
-            let mut locked_value = 0;
+    /// Parse BIP21 URI, address must belong to wallet's network
+    pub fn parse_bip21_uri(&self, uri: String) -> Result<Bip21Uri, WalletkaError> {
+        let uri = Bip21Uri::from_str(&uri)?;
 
-            for asset in assets.1 {
-                if asset.asset_state == WalletkaAssetState::Settled {
-                    confirmed_value += asset.amount.value;
-                } else if asset.asset_state == WalletkaAssetState::Unspendable {
-                    // Todo: Add pending state
-                    locked_value += asset.amount.value;
-                } else {
-                    unconfirmed_value += asset.amount.value;
-                }
+        if let Err(err) = self.bitcoin_wallet.parse_address(&uri.address) {
+            return Err(WalletkaError::InvalidAddress {
+                details: err.to_string(),
+            });
+        }
+
+        Ok(uri)
+    }
+
+    /// Pay `bitcoin:` URI, BIP78 payjoin is used when the URI contains `pj` endpoint
+    pub fn pay_payjoin_uri(
+        &self,
+        uri: String,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<String, WalletkaError> {
+        let uri = self.parse_bip21_uri(uri)?;
+        let address = self.bitcoin_wallet.parse_address(&uri.address)?;
+        let amount_sat = match uri.amount_sat {
+            Some(amount_sat) => amount_sat,
+            None => {
+                return Err(WalletkaError::InvalidUri {
+                    details: "Amount is missing in URI".to_string(),
+                })
             }
+        };
 
-            if confirmed_value > 0 {
-                let confirmed_amount = Amount {
-                    currency: assets.0.clone(),
-                    value: confirmed_value,
-                };
-                walletka_balance.confirmed.push(confirmed_amount);
+        let (txid, _) = match uri.payjoin_endpoint {
+            Some(endpoint) => self
+                .bitcoin_wallet
+                .pay_payjoin(address, amount_sat, &endpoint, fee_policy)?,
+            None => {
+                self.bitcoin_wallet
+                    .pay_to_address(address, amount_sat, true, fee_policy, vec![])?
             }
+        };
 
-            if unconfirmed_value > 0 {
-                let unconfirmed_amount = Amount {
-                    currency: assets.0.clone(),
-                    value: unconfirmed_value,
-                };
+        info!("Onchain payment sent: {}", txid);
+        Ok(txid)
+    }
 
-                walletka_balance.unconfirmed.push(unconfirmed_amount);
+    /// Get `bitcoin:` URI with payjoin endpoint receiving to unused address
+    pub fn get_payjoin_uri(
+        &self,
+        endpoint: String,
+        amount_sat: Option<u64>,
+    ) -> Result<String, WalletkaError> {
+        let mut uri = Bip21Uri::new(self.bitcoin_wallet.get_unused_address()?.to_string());
+        uri.amount_sat = amount_sat;
+        uri.payjoin_endpoint = Some(endpoint);
+        uri.payjoin_output_substitution = false;
+
+        Ok(uri.to_string())
+    }
+
+    /// Handle payjoin request with base64 original PSBT and request query,
+    /// returns base64 payjoin proposal PSBT
+    pub fn create_payjoin_proposal(
+        &self,
+        original_psbt: String,
+        query: String,
+    ) -> Result<String, WalletkaError> {
+        let params = PayjoinParams::from_query(query.trim_start_matches('?')).map_err(|err| {
+            WalletkaError::Payjoin {
+                details: err.to_string(),
             }
+        })?;
 
-            if locked_value > 0 {
-                let locked_amount = Amount {
-                    currency: assets.0,
-                    value: locked_value,
+        let proposal = self
+            .bitcoin_wallet
+            .create_payjoin_proposal(&original_psbt, params)?;
+
+        Ok(proposal.to_string())
+    }
+
+    /// Serve single payjoin request on local HTTP endpoint, e.g. `127.0.0.1:3010`
+    pub fn serve_payjoin(&self, listen_address: String) -> Result<(), WalletkaError> {
+        PayjoinClient::serve_once(&listen_address, |original_psbt, params| {
+            let proposal = self
+                .bitcoin_wallet
+                .create_payjoin_proposal(original_psbt, params)?;
+
+            Ok(proposal.to_string())
+        })
+        .map_err(|err| WalletkaError::Payjoin {
+            details: err.to_string(),
+        })
+    }
+
+    /// Get BIP352 static address, payments to it are found by `scan_silent_payments`
+    pub fn get_silent_payment_address(&self) -> Result<String, WalletkaError> {
+        Ok(self
+            .bitcoin_wallet
+            .get_silent_payment_address()?
+            .to_string())
+    }
+
+    /// Scan blocks for payments to silent payment address, scans up to the tip
+    /// when `to_height` is not set. Returns outpoints (txid:vout) of found outputs
+    pub async fn scan_silent_payments(
+        &self,
+        from_height: u32,
+        to_height: Option<u32>,
+    ) -> Result<Vec<String>, WalletkaError> {
+        let to_height = match to_height {
+            Some(height) => height,
+            None => self.bitcoin_wallet.get_block_height()?,
+        };
+
+        let mut outpoints = vec![];
+        for height in from_height..=to_height {
+            for (outpoint, txout, tweak) in self.bitcoin_wallet.scan_silent_payments(height)? {
+                // Rescan must not make swept output spendable again
+                let spent = self
+                    .bitcoin_repository
+                    .get_silent_payment_output(&outpoint.txid.to_string(), outpoint.vout)
+                    .await?
+                    .map_or(false, |output| output.spent);
+
+                let output = SilentPaymentOutput {
+                    id: None,
+                    tx_id: outpoint.txid.to_string(),
+                    vout: outpoint.vout,
+                    amount_sats: txout.value,
+                    script_pubkey: txout.script_pubkey.to_hex_string(),
+                    tweak: tweak.iter().map(|b| format!("{b:02x}")).collect(),
+                    block_height: height,
+                    spent,
                 };
 
-                walletka_balance.locked.push(locked_amount);
+                let output = self
+                    .bitcoin_repository
+                    .save_silent_payment_output(output)
+                    .await?;
+                outpoints.push(output.outpoint());
             }
         }
 
-        Ok(walletka_balance)
+        Ok(outpoints)
     }
 
-    pub async fn claim_cashu_token(&mut self, token: String) -> Result<()> {
-        self.cashu_wallet.claim_token(token).await
+    /// Move all unspent silent payment outputs to the wallet,
+    /// returns `None` when there is nothing to sweep
+    pub async fn sweep_silent_payments(
+        &self,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<Option<String>, WalletkaError> {
+        let outputs = self
+            .bitcoin_repository
+            .get_unspent_silent_payment_outputs()
+            .await?;
+        if outputs.is_empty() {
+            return Ok(None);
+        }
+
+        let mut spends = vec![];
+        for output in outputs.iter() {
+            spends.push((
+                OutPoint::new(Txid::from_str(&output.tx_id)?, output.vout),
+                TxOut {
+                    value: output.amount_sats,
+                    script_pubkey: ScriptBuf::from_hex(&output.script_pubkey)?,
+                },
+                <[u8; 32]>::from_hex(&output.tweak)?,
+            ));
+        }
+
+        let txid = self
+            .bitcoin_wallet
+            .sweep_silent_payments(spends, fee_policy)?;
+
+        for mut output in outputs {
+            output.spent = true;
+            self.bitcoin_repository
+                .save_silent_payment_output(output)
+                .await?;
+        }
+
+        Ok(Some(txid))
     }
 
-    pub async fn get_cashu_mints(&self) -> Result<Vec<CashuMint>> {
-        Ok(self.cashu_wallet.get_mints())
+    /// Sign message with wallet address to prove its ownership
+    pub fn sign_message(
+        &self,
+        address: String,
+        message: String,
+        format: MessageSignatureFormat,
+    ) -> Result<String, WalletkaError> {
+        let address = self.bitcoin_wallet.parse_address(&address)?;
+
+        self.bitcoin_wallet.sign_message(&address, &message, format)
     }
 
-    pub async fn send_cashu_token(
-        &mut self,
-        keyset_id: String,
-        amount_sat: u64,
-        memo: Option<String>,
-    ) -> Result<String> {
-        let token = self
-            .cashu_wallet
-            .create_token_from_keyset(keyset_id, amount_sat, memo)
-            .await?;
+    /// Verify BIP322 simple or BIP137 signature of message
+    pub fn verify_message(
+        &self,
+        address: String,
+        message: String,
+        signature: String,
+    ) -> Result<bool, WalletkaError> {
+        let address = self.bitcoin_wallet.parse_address(&address)?;
 
-        Ok(token.convert_to_string()?)
+        self.bitcoin_wallet
+            .verify_message(&address, &message, &signature)
     }
 
-    // RGB functions
+    /// Send replaceable onchain payment, BDK default fee rate is used without fee policy.
+    /// Only given utxos (txid:vout) are spent, coin selection is used when none is given.
+    /// Silent payment addresses (`sp1...`) are supported
+    pub fn pay_to_address(
+        &self,
+        address: String,
+        amount_sat: u64,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<String>,
+    ) -> Result<String, WalletkaError> {
+        let (txid, _) = self.pay_onchain(address, amount_sat, fee_policy, utxos)?;
 
-    pub fn create_rgb_utxos(&mut self) -> Result<()> {
-        self.rgb_wallet.create_utxos()
+        Ok(txid)
     }
 
-    pub fn issue_rgb20_asset(
-        &mut self,
-        ticker: String,
-        name: String,
-        precision: u8,
-        amount: u64,
-    ) -> Result<String> {
-        info!("Issuing RGB20 asset");
+    /// Returns txid and fee of the payment
+    fn pay_onchain(
+        &self,
+        address: String,
+        amount_sat: u64,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<String>,
+    ) -> Result<(String, u64), WalletkaError> {
+        let utxos = parse_outpoints(utxos)?;
 
-        let asset = self.rgb_wallet
-            .issue_rgb20_asset(ticker, name, precision, amount)?;
+        let (txid, fee_sat) = match SilentPaymentAddress::from_str(&address) {
+            Ok(address) => self
+                .bitcoin_wallet
+                .pay_silent_payment(address, amount_sat, true, fee_policy, utxos)?,
+            Err(_) => {
+                let address = self.bitcoin_wallet.parse_address(&address)?;
+                self.bitcoin_wallet
+                    .pay_to_address(address, amount_sat, true, fee_policy, utxos)?
+            }
+        };
 
-        info!("RGB20 asset issued: {}", asset.asset_id);
-        Ok(asset.asset_id)
+        info!("Onchain payment sent: {}, fee {} sats", txid, fee_sat);
+        Ok((txid, fee_sat))
     }
 
-    pub fn create_rgb_invoice(
+    /// Pay to many recipients in one onchain transaction,
+    /// rest of the funds is sent to `send_max_to` address when set
+    pub async fn pay_batch(
         &self,
-        asset_id: Option<String>,
-        amount: Option<u64>,
-        duration_seconds: Option<u32>,
-        min_confirmations: Option<u8>,
-        transport_url: Option<String>,
-        blinded: bool,
-    ) -> Result<String> {
-        info!("Creating RGB invoice");
+        recipients: Vec<BatchRecipient>,
+        send_max_to: Option<String>,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<BatchPaymentResult, WalletkaError> {
+        let recipients = recipients
+            .into_iter()
+            .map(|r| Ok((self.bitcoin_wallet.parse_address(&r.address)?, r.amount_sat)))
+            .collect::<Result<Vec<_>, WalletkaError>>()?;
+        let send_max_to = match send_max_to {
+            Some(address) => Some(self.bitcoin_wallet.parse_address(&address)?),
+            None => None,
+        };
 
-        let invoice_data = self.rgb_wallet.create_invoice(
-            asset_id,
-            amount,
-            duration_seconds,
+        let result = self
+            .bitcoin_wallet
+            .pay_batch(recipients, send_max_to, true, fee_policy)?;
+        info!("Batch payment sent: {}", result.txid);
+
+        let transaction = WalletkaTransaction {
+            id: None,
+            layer: WalletkaLayer::Blockchain,
+            direction: TransactionDirection::Sent,
+            status: TransactionStatus::Pending,
+            tx_id: Some(result.txid.clone()),
+            amount: Amount::new(
+                result.outputs.iter().map(|o| o.amount_sat).sum(),
+                Currency::bitcoin(),
+            ),
+            fee_sat: Some(result.fee_sat),
+            datetime: Datetime::default(),
+            contact_id: None,
+            memo: None,
+            entries: result
+                .outputs
+                .iter()
+                .map(|output| WalletkaTransactionEntry {
+                    direction: TransactionDirection::Sent,
+                    address: output.address.clone(),
+                    amount_sat: output.amount_sat,
+                    vout: Some(output.vout),
+                })
+                .collect(),
+        };
+        self.history.record(transaction).await?;
+
+        Ok(result)
+    }
+
+    /// Get fee, size and inputs of onchain payment without sending it
+    pub fn preview_bitcoin_payment(
+        &self,
+        address: String,
+        amount_sat: u64,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<String>,
+    ) -> Result<BitcoinTxPreview, WalletkaError> {
+        let address = self.bitcoin_wallet.parse_address(&address)?;
+        let utxos = parse_outpoints(utxos)?;
+
+        self.bitcoin_wallet
+            .preview_payment(address, amount_sat, true, fee_policy, utxos)
+    }
+
+    /// Get stored txo of wallet utxo or create a new one
+    async fn get_wallet_txo(&self, outpoint: &OutPoint) -> Result<Txo, WalletkaError> {
+        if let Some(txo) = self
+            .bitcoin_repository
+            .get_txo(&outpoint.txid.to_string(), outpoint.vout)
+            .await?
+        {
+            return Ok(txo);
+        }
+
+        match self
+            .bitcoin_wallet
+            .get_utxos()?
+            .into_iter()
+            .find(|(_, utxo)| &utxo.outpoint == outpoint)
+        {
+            Some((_, utxo)) => Ok(Txo::from(utxo)),
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Utxo {} not found", outpoint),
+                })
+            }
+        }
+    }
+
+    /// Freeze utxo (txid:vout) so that coin selection never spends it
+    pub async fn set_utxo_frozen(
+        &mut self,
+        outpoint: String,
+        frozen: bool,
+    ) -> Result<(), WalletkaError> {
+        let outpoint = OutPoint::from_str(&outpoint)?;
+
+        let mut txo = self.get_wallet_txo(&outpoint).await?;
+        txo.frozen = frozen;
+        self.bitcoin_repository.save_txo(txo).await?;
+
+        self.bitcoin_wallet.set_utxo_frozen(outpoint, frozen);
+
+        info!("Utxo {} frozen: {}", outpoint, frozen);
+        Ok(())
+    }
+
+    /// Set user label of utxo (txid:vout)
+    pub async fn set_utxo_label(
+        &self,
+        outpoint: String,
+        label: Option<String>,
+    ) -> Result<(), WalletkaError> {
+        let outpoint = OutPoint::from_str(&outpoint)?;
+
+        let mut txo = self.get_wallet_txo(&outpoint).await?;
+        txo.label = label;
+        self.bitcoin_repository.save_txo(txo).await?;
+
+        Ok(())
+    }
+
+    /// Fee rate in sat/vB to confirm within target blocks
+    pub fn estimate_bitcoin_fee_rate(&self, target_blocks: u32) -> Result<f32, WalletkaError> {
+        Ok(self
+            .bitcoin_wallet
+            .estimate_fee_rate(target_blocks)?
+            .as_sat_per_vb())
+    }
+
+    /// Replace unconfirmed onchain transaction with one paying new fee rate in sat/vB
+    pub fn bump_fee(&self, txid: String, new_fee_rate: f32) -> Result<String, WalletkaError> {
+        self.bitcoin_wallet
+            .bump_fee(Txid::from_str(&txid)?, new_fee_rate)
+    }
+
+    /// Speed up unconfirmed onchain transaction by spending its output,
+    /// package fee rate is in sat/vB
+    pub fn cpfp(&self, txid: String, package_fee_rate: f32) -> Result<String, WalletkaError> {
+        self.bitcoin_wallet
+            .cpfp(Txid::from_str(&txid)?, package_fee_rate)
+    }
+
+    /// Get bitcoin accounts held by Walletka
+    pub fn get_bitcoin_accounts(&self) -> Vec<BitcoinAccount> {
+        self.bitcoin_wallet.get_accounts()
+    }
+
+    /// Set bitcoin account used for receiving and spending
+    pub fn set_bitcoin_account(&mut self, account: BitcoinAccount) -> Result<(), WalletkaError> {
+        self.bitcoin_wallet.set_active_account(account)
+    }
+
+    /// Sweep funds of bitcoin wallet created with legacy descriptors
+    pub fn sweep_legacy_bitcoin_wallet(&mut self) -> Result<Option<String>, WalletkaError> {
+        self.bitcoin_wallet.sweep_legacy_wallet()
+    }
+
+    /// Sweep funds of private key in WIF format to destination address or to this wallet
+    pub fn sweep_wif(
+        &self,
+        wif: String,
+        destination: Option<String>,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<Vec<String>, WalletkaError> {
+        let destination = match destination {
+            Some(address) => Some(self.bitcoin_wallet.parse_address(&address)?),
+            None => None,
+        };
+
+        self.bitcoin_wallet.sweep_wif(&wif, destination, fee_policy)
+    }
+
+    /// Send all spendable bitcoin funds to address
+    pub fn drain_to(
+        &self,
+        address: String,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<String, WalletkaError> {
+        let address = self.bitcoin_wallet.parse_address(&address)?;
+
+        self.bitcoin_wallet.drain_to(address, true, fee_policy)
+    }
+
+    /// Get all assets grouped by currency
+    pub async fn get_balance(
+        &self,
+        currency_symbol: Option<String>,
+    ) -> Result<WalletkaBalance, WalletkaError> {
+        let mut walletka_balance = WalletkaBalance::default();
+
+        let mut assets = self.get_assets().await?;
+
+        if let Some(symbol) = currency_symbol {
+            assets = assets
+                .into_iter()
+                .filter(|a| a.amount.currency.symbol == symbol)
+                .collect();
+        }
+
+        let by_currency = assets.into_iter().group_by(|a| a.amount.currency.clone());
+
+        for assets in by_currency.into_iter() {
+            let mut confirmed_value = 0;
+            let mut unconfirmed_value = 0;
+            let mut locked_value = 0;
+
+            for asset in assets.1 {
+                if asset.asset_state == WalletkaAssetState::Settled {
+                    confirmed_value += asset.amount.value;
+                } else if asset.asset_state == WalletkaAssetState::Unspendable {
+                    // Todo: Add pending state
+                    locked_value += asset.amount.value;
+                } else {
+                    unconfirmed_value += asset.amount.value;
+                }
+            }
+
+            if confirmed_value > 0 {
+                let confirmed_amount = Amount {
+                    currency: assets.0.clone(),
+                    value: confirmed_value,
+                };
+                walletka_balance.confirmed.push(confirmed_amount);
+            }
+
+            if unconfirmed_value > 0 {
+                let unconfirmed_amount = Amount {
+                    currency: assets.0.clone(),
+                    value: unconfirmed_value,
+                };
+
+                walletka_balance.unconfirmed.push(unconfirmed_amount);
+            }
+
+            if locked_value > 0 {
+                let locked_amount = Amount {
+                    currency: assets.0,
+                    value: locked_value,
+                };
+
+                walletka_balance.locked.push(locked_amount);
+            }
+        }
+
+        Ok(walletka_balance)
+    }
+
+    pub async fn claim_cashu_token(&mut self, token: String) -> Result<(), WalletkaError> {
+        let transaction = self.cashu_wallet.claim_token(token).await?;
+        let transaction = self.history.record(transaction).await?;
+
+        self.history.emit(WalletkaEvent::CashuTokenClaimed {
+            amount_sat: transaction.amount.value,
+            memo: transaction.memo,
+        });
+
+        Ok(())
+    }
+
+    pub async fn get_cashu_mints(&self) -> Result<Vec<CashuMint>, WalletkaError> {
+        Ok(self.cashu_wallet.get_mints())
+    }
+
+    pub async fn send_cashu_token(
+        &mut self,
+        keyset_id: String,
+        amount_sat: u64,
+        memo: Option<String>,
+    ) -> Result<String, WalletkaError> {
+        let (token, transaction) = self
+            .cashu_wallet
+            .create_token_from_keyset(keyset_id, amount_sat, memo)
+            .await?;
+
+        self.history.record(transaction).await?;
+
+        Ok(token)
+    }
+
+    /// Reclaim sent Cashu token the recipient didn't redeem yet,
+    /// `token_id` is the hash of the token
+    pub async fn reclaim_cashu_token(
+        &mut self,
+        token_id: String,
+    ) -> Result<WalletkaHistoryItem, WalletkaError> {
+        let transaction = self.cashu_wallet.reclaim_token(token_id).await?;
+        let transaction = self.record_cashu_reclaim(transaction).await?;
+
+        Ok(WalletkaHistoryItem::from(transaction))
+    }
+
+    async fn record_cashu_reclaim(
+        &self,
+        transaction: WalletkaTransaction,
+    ) -> Result<WalletkaTransaction, WalletkaError> {
+        let transaction = self.history.record(transaction).await?;
+
+        self.history.emit(WalletkaEvent::CashuTokenReclaimed {
+            tx_id: transaction.tx_id.clone().unwrap_or_default(),
+            amount_sat: transaction.amount.value,
+        });
+
+        Ok(transaction)
+    }
+
+    /// Pay Lightning invoice by Cashu proofs of the keyset
+    pub async fn melt_cashu(
+        &mut self,
+        invoice: String,
+        keyset_id: String,
+    ) -> Result<PaymentReceipt, WalletkaError> {
+        let transaction = self.cashu_wallet.melt(invoice, keyset_id).await?;
+        let transaction = self.history.record(transaction).await?;
+
+        Ok(PaymentReceipt::from(transaction))
+    }
+
+    /// Request Lightning invoice minting `amount_sat` of ecash from the mint once paid
+    pub async fn request_cashu_mint(
+        &mut self,
+        mint_url: String,
+        amount_sat: u64,
+    ) -> Result<CashuQuote, WalletkaError> {
+        let quote = self.cashu_wallet.request_mint(mint_url, amount_sat).await?;
+
+        Ok(CashuQuote::from(quote))
+    }
+
+    /// Check whether the quote's invoice is paid and mint its proofs
+    pub async fn check_cashu_mint_quote(
+        &mut self,
+        quote_id: String,
+    ) -> Result<CashuQuote, WalletkaError> {
+        let (quote, transaction) = self.cashu_wallet.mint_quote(quote_id).await?;
+
+        if let Some(transaction) = transaction {
+            self.record_cashu_mint(quote.clone(), transaction).await?;
+        }
+
+        Ok(CashuQuote::from(quote))
+    }
+
+    /// Mint quotes, newest first
+    pub async fn get_cashu_mint_quotes(&self) -> Result<Vec<CashuQuote>, WalletkaError> {
+        Ok(self
+            .cashu_wallet
+            .get_mint_quotes()
+            .await?
+            .into_iter()
+            .map(CashuQuote::from)
+            .collect())
+    }
+
+    async fn record_cashu_mint(
+        &self,
+        quote: CashuMintQuote,
+        transaction: WalletkaTransaction,
+    ) -> Result<(), WalletkaError> {
+        let transaction = self.history.record(transaction).await?;
+
+        self.history.emit(WalletkaEvent::CashuMinted {
+            quote_id: quote.quote_id,
+            amount_sat: transaction.amount.value,
+        });
+
+        Ok(())
+    }
+
+    // Payment router
+
+    /// Recognize bitcoin address or URI, silent payment address, Cashu token,
+    /// RGB invoice or nostr public key
+    pub fn parse_destination(
+        &self,
+        destination: String,
+    ) -> Result<PaymentDestination, WalletkaError> {
+        let destination = destination.trim();
+
+        if destination
+            .get(..8)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("bitcoin:"))
+        {
+            let uri = self.parse_bip21_uri(destination.to_string())?;
+
+            return Ok(PaymentDestination::BitcoinUri { uri });
+        }
+
+        if destination.starts_with("cashu") {
+            let (mint_url, amount_sat, memo) = decode_token(destination)?;
+
+            return Ok(PaymentDestination::CashuToken {
+                token: destination.to_string(),
+                mint_url,
+                amount_sat,
+                memo,
+            });
+        }
+
+        if destination.starts_with("rgb:") {
+            let invoice = RgbWallet::parse_invoice(destination)?;
+
+            return Ok(PaymentDestination::RgbInvoice {
+                invoice: destination.to_string(),
+                asset_id: invoice.asset_id,
+                amount: invoice.amount,
+            });
+        }
+
+        let npub = destination.strip_prefix("nostr:").unwrap_or(destination);
+        if npub.starts_with("npub1") {
+            PublicKey::from_bech32(npub)?;
+
+            return Ok(PaymentDestination::NostrPubkey {
+                npub: npub.to_string(),
+            });
+        }
+
+        if SilentPaymentAddress::from_str(destination).is_ok() {
+            return Ok(PaymentDestination::SilentPaymentAddress {
+                address: destination.to_string(),
+            });
+        }
+
+        match self.bitcoin_wallet.parse_address(destination) {
+            Ok(_) => Ok(PaymentDestination::BitcoinAddress {
+                address: destination.to_string(),
+            }),
+            Err(err) => {
+                return Err(WalletkaError::InvalidInput {
+                    details: format!("Unknown payment destination: {}", err),
+                })
+            }
+        }
+    }
+
+    /// Pay destination recognized by `parse_destination`. When more layers can pay it,
+    /// the one with sufficient balance and the lowest fee is used.
+    /// `amount` is in sats or RGB asset units and is required when destination doesn't contain it
+    pub async fn pay(
+        &mut self,
+        destination: String,
+        amount: Option<u64>,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<PaymentReceipt, WalletkaError> {
+        let routes = match self.parse_destination(destination)? {
+            PaymentDestination::BitcoinAddress { address }
+            | PaymentDestination::SilentPaymentAddress { address } => vec![PaymentRoute::Onchain {
+                address,
+                payjoin_endpoint: None,
+                amount_sat: payment_amount(None, amount)?,
+                memo: None,
+            }],
+            PaymentDestination::BitcoinUri { uri } => bip21_routes(uri, amount)?,
+            PaymentDestination::CashuToken { .. } => {
+                return Err(WalletkaError::InvalidInput {
+                    details: "Cashu token can't be paid, claim it instead".to_string(),
+                })
+            }
+            PaymentDestination::RgbInvoice {
+                invoice,
+                amount: invoice_amount,
+                ..
+            } => vec![PaymentRoute::Rgb {
+                invoice,
+                amount: payment_amount(invoice_amount, amount)?,
+            }],
+            PaymentDestination::NostrPubkey { npub } => vec![PaymentRoute::Cashu {
+                receiver: PublicKey::from_bech32(&npub)?,
+                amount_sat: payment_amount(None, amount)?,
+                memo: None,
+            }],
+        };
+
+        let route = self.select_route(routes, fee_policy).await?;
+        info!("Paying by {:?}", route);
+
+        self.pay_route(route, fee_policy).await
+    }
+
+    /// Route with the lowest fee among routes payable from current balance
+    async fn select_route(
+        &self,
+        routes: Vec<PaymentRoute>,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<PaymentRoute, WalletkaError> {
+        let mut estimates = vec![];
+        for route in routes {
+            let fee_sat = self.estimate_route_fee(&route, fee_policy).await;
+            estimates.push((route, fee_sat));
+        }
+
+        cheapest_route(estimates)
+    }
+
+    /// Fee in sats, `None` when it's known only once the payment is built.
+    /// Fails when the route can't be paid from current balance
+    async fn estimate_route_fee(
+        &self,
+        route: &PaymentRoute,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<Option<u64>, WalletkaError> {
+        match route {
+            PaymentRoute::Onchain {
+                address,
+                amount_sat,
+                ..
+            } => {
+                let script_address = match SilentPaymentAddress::from_str(address) {
+                    // Silent payment output is derived from selected inputs,
+                    // own address of similar size is used for estimation
+                    Ok(_) => self.bitcoin_wallet.get_unused_address()?,
+                    Err(_) => self.bitcoin_wallet.parse_address(address)?,
+                };
+
+                Ok(Some(
+                    self.bitcoin_wallet
+                        .preview_payment(script_address, *amount_sat, true, fee_policy, vec![])?
+                        .fee_sat,
+                ))
+            }
+            PaymentRoute::Cashu { amount_sat, .. } => {
+                // Mints of supported protocol version don't charge for swapping proofs,
+                // fee of the swap is reported in the receipt
+                match self.cashu_wallet.get_keyset_for_amount(*amount_sat).await? {
+                    Some(_) => Ok(Some(0)),
+                    None => {
+                        return Err(WalletkaError::InsufficientFunds {
+                            details: "Insufficient Cashu balance".to_string(),
+                        })
+                    }
+                }
+            }
+            PaymentRoute::Rgb { invoice, amount } => {
+                let asset_id = RgbWallet::parse_invoice(invoice)?.asset_id;
+                let spendable = self
+                    .rgb_wallet()?
+                    .get_rgb20_assets()?
+                    .into_iter()
+                    .find(|a| Some(&a.asset_id) == asset_id.as_ref())
+                    .map(|a| a.balance.spendable)
+                    .unwrap_or_default();
+
+                if spendable < *amount {
+                    return Err(WalletkaError::InsufficientFunds {
+                        details: "Insufficient RGB asset balance".to_string(),
+                    });
+                }
+
+                // Onchain fee of transfer is known once it's built
+                Ok(None)
+            }
+        }
+    }
+
+    async fn pay_route(
+        &mut self,
+        route: PaymentRoute,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<PaymentReceipt, WalletkaError> {
+        let mut deliver_to = None;
+
+        let (transaction, token) = match route {
+            PaymentRoute::Onchain {
+                address,
+                payjoin_endpoint,
+                amount_sat,
+                memo,
+            } => {
+                let (txid, fee_sat) = match payjoin_endpoint {
+                    Some(endpoint) => self.bitcoin_wallet.pay_payjoin(
+                        self.bitcoin_wallet.parse_address(&address)?,
+                        amount_sat,
+                        &endpoint,
+                        fee_policy,
+                    )?,
+                    None => self.pay_onchain(address.clone(), amount_sat, fee_policy, vec![])?,
+                };
+
+                let transaction = WalletkaTransaction {
+                    id: None,
+                    layer: WalletkaLayer::Blockchain,
+                    direction: TransactionDirection::Sent,
+                    status: TransactionStatus::Pending,
+                    tx_id: Some(txid),
+                    amount: Amount::new(amount_sat, Currency::bitcoin()),
+                    fee_sat: Some(fee_sat),
+                    datetime: Datetime::default(),
+                    contact_id: None,
+                    memo,
+                    entries: vec![WalletkaTransactionEntry {
+                        direction: TransactionDirection::Sent,
+                        address,
+                        amount_sat,
+                        vout: None,
+                    }],
+                };
+
+                (transaction, None)
+            }
+            PaymentRoute::Cashu {
+                receiver,
+                amount_sat,
+                memo,
+            } => {
+                if self.nostr_client.is_none() {
+                    return Err(WalletkaError::Nostr {
+                        details: "Nostr is not available in watch-only mode".to_string(),
+                    });
+                }
+                let keyset_id = match self.cashu_wallet.get_keyset_for_amount(amount_sat).await? {
+                    Some(keyset_id) => keyset_id,
+                    None => {
+                        return Err(WalletkaError::InsufficientFunds {
+                            details: "Insufficient Cashu balance".to_string(),
+                        })
+                    }
+                };
+
+                let (token, mut transaction) = self
+                    .cashu_wallet
+                    .create_token_from_keyset(keyset_id, amount_sat, memo)
+                    .await?;
+
+                transaction.entries.push(WalletkaTransactionEntry {
+                    direction: TransactionDirection::Sent,
+                    address: receiver.to_bech32()?,
+                    amount_sat,
+                    vout: None,
+                });
+                deliver_to = Some(receiver);
+
+                (transaction, Some(token))
+            }
+            PaymentRoute::Rgb { invoice, amount } => {
+                let fee_rate = self
+                    .bitcoin_wallet
+                    .get_fee_rate(fee_policy.unwrap_or(DEFAULT_FEE_POLICY))?
+                    .as_sat_per_vb();
+
+                let (asset, transfer) =
+                    self.rgb_wallet()?.send(&invoice, Some(amount), fee_rate)?;
+
+                (WalletkaTransaction::from((asset, transfer)), None)
+            }
+        };
+
+        let transaction = self.history.record(transaction).await?;
+        let mut receipt = PaymentReceipt::from(transaction);
+
+        // Token is recorded first, undelivered token stays pending in the wallet
+        // and is returned in receipt, funds already left the wallet
+        if let (Some(receiver), Some(token), Some(nostr_client)) =
+            (deliver_to, &token, &self.nostr_client)
+        {
+            if let Err(err) = nostr_client
+                .send_nip04_message(receiver, token.clone())
+                .await
+            {
+                warn!("Cashu token delivery failed: {}", err);
+                receipt.delivery_error = Some(err.to_string());
+            }
+        }
+
+        receipt.token = token;
+
+        Ok(receipt)
+    }
+
+    // RGB functions
+
+    /// RGB wallet is shared with the sync scheduler, lock must not be held across await
+    fn rgb_wallet(&self) -> Result<MutexGuard<'_, RgbWallet>, WalletkaError> {
+        match self.rgb_wallet.as_deref() {
+            Some(rgb_wallet) => Ok(lock_rgb(rgb_wallet)),
+            None => Err(rgb_unavailable()),
+        }
+    }
+
+    pub fn create_rgb_utxos(&mut self) -> Result<(), WalletkaError> {
+        self.rgb_wallet()?.create_utxos()
+    }
+
+    pub fn issue_rgb20_asset(
+        &mut self,
+        ticker: String,
+        name: String,
+        precision: u8,
+        amount: u64,
+    ) -> Result<String, WalletkaError> {
+        info!("Issuing RGB20 asset");
+
+        let asset = self
+            .rgb_wallet()?
+            .issue_rgb20_asset(ticker, name, precision, amount)?;
+
+        info!("RGB20 asset issued: {}", asset.asset_id);
+        Ok(asset.asset_id)
+    }
+
+    pub fn create_rgb_invoice(
+        &self,
+        asset_id: Option<String>,
+        amount: Option<u64>,
+        duration_seconds: Option<u32>,
+        min_confirmations: Option<u8>,
+        transport_url: Option<String>,
+        blinded: bool,
+    ) -> Result<String, WalletkaError> {
+        info!("Creating RGB invoice");
+
+        let invoice_data = self.rgb_wallet()?.create_invoice(
+            asset_id,
+            amount,
+            duration_seconds,
             min_confirmations,
             transport_url,
             blinded,
@@ -236,3 +1495,701 @@ impl Walletka
         Ok(invoice_data.invoice)
     }
 }
+
+/// Layer and parameters used to pay destination
+#[derive(Debug)]
+enum PaymentRoute {
+    Onchain {
+        address: String,
+        payjoin_endpoint: Option<String>,
+        amount_sat: u64,
+        memo: Option<String>,
+    },
+    /// Token sent in nostr direct message
+    Cashu {
+        receiver: PublicKey,
+        amount_sat: u64,
+        memo: Option<String>,
+    },
+    Rgb {
+        invoice: String,
+        amount: u64,
+    },
+}
+
+/// Routes of layers offered by BIP21 URI. `amount` applies to bitcoin layers,
+/// RGB route is offered when the invoice contains amount
+fn bip21_routes(uri: Bip21Uri, amount: Option<u64>) -> Result<Vec<PaymentRoute>, WalletkaError> {
+    let mut routes = vec![];
+
+    let rgb_amount = match &uri.rgb_invoice {
+        Some(invoice) => RgbWallet::parse_invoice(invoice)?.amount,
+        None => None,
+    };
+    let amount_sat = match payment_amount(uri.amount_sat, amount) {
+        Ok(amount_sat) => Some(amount_sat),
+        Err(_) if rgb_amount.is_some() && amount.is_none() => None,
+        Err(err) => return Err(err),
+    };
+
+    if let Some(amount_sat) = amount_sat {
+        let memo = uri.message.or(uri.label);
+
+        if let Some(npub) = uri.cashu_npub {
+            routes.push(PaymentRoute::Cashu {
+                receiver: PublicKey::from_bech32(&npub)?,
+                amount_sat,
+                memo: memo.clone(),
+            });
+        }
+        routes.push(PaymentRoute::Onchain {
+            address: uri.address,
+            payjoin_endpoint: uri.payjoin_endpoint,
+            amount_sat,
+            memo,
+        });
+    }
+    if let (Some(invoice), Some(amount)) = (uri.rgb_invoice, rgb_amount) {
+        routes.push(PaymentRoute::Rgb { invoice, amount });
+    }
+
+    Ok(routes)
+}
+
+/// Route with the lowest fee, routes with unknown fee are used when no other can pay.
+/// Error of the last route is returned when none can be paid
+fn cheapest_route(
+    estimates: Vec<(PaymentRoute, Result<Option<u64>, WalletkaError>)>,
+) -> Result<PaymentRoute, WalletkaError> {
+    let fee_order = |fee_sat: Option<u64>| (fee_sat.is_none(), fee_sat.unwrap_or_default());
+    let mut selected: Option<(Option<u64>, PaymentRoute)> = None;
+    let mut last_error = None;
+
+    for (route, fee_sat) in estimates {
+        match fee_sat {
+            Ok(fee_sat)
+                if selected
+                    .as_ref()
+                    .map_or(true, |(fee, _)| fee_order(fee_sat) < fee_order(*fee)) =>
+            {
+                selected = Some((fee_sat, route))
+            }
+            Ok(_) => {}
+            Err(err) => {
+                debug!("Can't pay by {:?}: {}", route, err);
+                last_error = Some(err);
+            }
+        }
+    }
+
+    match (selected, last_error) {
+        (Some((_, route)), _) => Ok(route),
+        (None, Some(err)) => Err(err),
+        (None, None) => {
+            return Err(WalletkaError::InvalidInput {
+                details: "No layer can pay the destination".to_string(),
+            })
+        }
+    }
+}
+
+/// Amount in destination takes precedence, conflicting amounts are refused
+fn payment_amount(
+    destination_amount: Option<u64>,
+    amount: Option<u64>,
+) -> Result<u64, WalletkaError> {
+    match (destination_amount, amount) {
+        (Some(destination_amount), Some(amount)) if destination_amount != amount => {
+            return Err(WalletkaError::InvalidInput {
+                details: format!(
+                    "Amount {} doesn't match amount {} of destination",
+                    amount, destination_amount
+                ),
+            })
+        }
+        (Some(amount), _) | (None, Some(amount)) => Ok(amount),
+        (None, None) => {
+            return Err(WalletkaError::InvalidInput {
+                details: "Amount is missing".to_string(),
+            })
+        }
+    }
+}
+
+/// Wallets synced by the sync scheduler. Bitcoin wallet is a view sharing databases
+/// with Walletka, so scheduled sync doesn't hold the Walletka lock
+pub(crate) struct SyncTarget {
+    bitcoin_wallet: Mutex<BitcoinWallet>,
+    rgb_wallet: Option<Arc<Mutex<RgbWallet>>>,
+    history: HistoryManager<Db>,
+}
+
+impl SyncTarget {
+    /// Sync given layers on blocking thread and store their transactions in history
+    pub(crate) async fn sync_layers(
+        self: Arc<Self>,
+        layers: Vec<WalletkaLayer>,
+        light: bool,
+    ) -> Result<SyncReport, WalletkaError> {
+        let target = self.clone();
+        let (results, events, transactions) = task::spawn_blocking(move || {
+            let bitcoin_wallet = target
+                .bitcoin_wallet
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let utxos_before = utxo_outpoints(&bitcoin_wallet);
+
+            let results = sync_wallets(
+                layers
+                    .contains(&WalletkaLayer::Blockchain)
+                    .then_some(&*bitcoin_wallet),
+                target
+                    .rgb_wallet
+                    .as_deref()
+                    .filter(|_| layers.contains(&WalletkaLayer::Rgb)),
+                light,
+                &|_| {},
+            );
+
+            (
+                results,
+                new_utxo_events(&bitcoin_wallet, utxos_before),
+                layer_transactions(&bitcoin_wallet, target.rgb_wallet.as_deref()),
+            )
+        })
+        .await
+        .map_err(|err| WalletkaError::Generic {
+            details: err.to_string(),
+        })?;
+
+        for event in events {
+            self.history.emit(event);
+        }
+
+        match transactions {
+            Ok(transactions) => {
+                for transaction in transactions {
+                    if let Err(err) = self.history.record(transaction).await {
+                        warn!("Can't update history: {}", err);
+                    }
+                }
+            }
+            Err(err) => warn!("Can't update history: {}", err),
+        }
+
+        Ok(SyncReport { layers: results })
+    }
+}
+
+/// Sync wallets concurrently, bitcoin wallet is not Sync and stays on current thread
+fn sync_wallets(
+    bitcoin_wallet: Option<&BitcoinWallet>,
+    rgb_wallet: Option<&Mutex<RgbWallet>>,
+    light: bool,
+    on_progress: &(dyn Fn(LayerSyncResult) + Sync),
+) -> Vec<LayerSyncResult> {
+    thread::scope(move |scope| {
+        let rgb_sync = rgb_wallet.map(|rgb_wallet| {
+            scope.spawn(move || {
+                sync_layer(WalletkaLayer::Rgb, on_progress, || {
+                    lock_rgb(rgb_wallet).sync(None, light)
+                })
+            })
+        });
+
+        let mut results = vec![];
+        if let Some(bitcoin_wallet) = bitcoin_wallet {
+            results.push(sync_layer(WalletkaLayer::Blockchain, on_progress, || {
+                sync_bitcoin(bitcoin_wallet)
+            }));
+        }
+
+        if let Some(rgb_sync) = rgb_sync {
+            results.push(rgb_sync.join().unwrap_or_else(|_| LayerSyncResult {
+                layer: WalletkaLayer::Rgb,
+                status: LayerSyncStatus::Failed {
+                    error: "RGB sync panicked".to_string(),
+                },
+                duration_ms: 0,
+                changes: 0,
+            }));
+        }
+
+        results
+    })
+}
+
+/// Sync layer and report its progress
+fn sync_layer(
+    layer: WalletkaLayer,
+    on_progress: &(dyn Fn(LayerSyncResult) + Sync),
+    sync: impl FnOnce() -> Result<u64, WalletkaError>,
+) -> LayerSyncResult {
+    on_progress(LayerSyncResult {
+        layer,
+        status: LayerSyncStatus::Syncing,
+        duration_ms: 0,
+        changes: 0,
+    });
+
+    let started = Instant::now();
+    let (status, changes) = match sync() {
+        Ok(changes) => (LayerSyncStatus::Synced, changes),
+        Err(err) => {
+            warn!("{:?} sync failed: {}", layer, err);
+            (
+                LayerSyncStatus::Failed {
+                    error: err.to_string(),
+                },
+                0,
+            )
+        }
+    };
+
+    let result = LayerSyncResult {
+        layer,
+        status,
+        duration_ms: started.elapsed().as_millis() as u64,
+        changes,
+    };
+    on_progress(result.clone());
+
+    result
+}
+
+/// Sync bitcoin wallet, returns number of new or newly confirmed transactions
+fn sync_bitcoin(bitcoin_wallet: &BitcoinWallet) -> Result<u64, WalletkaError> {
+    let transaction_states = || -> Result<HashSet<(Txid, bool)>, WalletkaError> {
+        Ok(bitcoin_wallet
+            .get_transactions()?
+            .into_iter()
+            .map(|t| (t.txid, t.confirmation_time.is_some()))
+            .collect())
+    };
+
+    let before = transaction_states()?;
+    bitcoin_wallet.sync()?;
+    let after = transaction_states()?;
+
+    Ok(after.difference(&before).count() as u64)
+}
+
+/// Outpoints of utxos, new utxos are not reported after sync when they are unknown
+fn utxo_outpoints(bitcoin_wallet: &BitcoinWallet) -> Option<HashSet<OutPoint>> {
+    match bitcoin_wallet.get_utxos() {
+        Ok(utxos) => Some(utxos.into_iter().map(|(_, utxo)| utxo.outpoint).collect()),
+        Err(err) => {
+            warn!("Can't get utxos before sync: {}", err);
+            None
+        }
+    }
+}
+
+fn new_utxo_events(
+    bitcoin_wallet: &BitcoinWallet,
+    utxos_before: Option<HashSet<OutPoint>>,
+) -> Vec<WalletkaEvent> {
+    let utxos_before = match utxos_before {
+        Some(utxos_before) => utxos_before,
+        None => return vec![],
+    };
+
+    match bitcoin_wallet.get_utxos() {
+        Ok(utxos) => utxos
+            .into_iter()
+            .filter(|(_, utxo)| !utxos_before.contains(&utxo.outpoint))
+            .map(|(_, utxo)| WalletkaEvent::NewUtxo {
+                outpoint: utxo.outpoint.to_string(),
+                amount_sat: utxo.txout.value,
+            })
+            .collect(),
+        Err(err) => {
+            warn!("Can't get utxos after sync: {}", err);
+            vec![]
+        }
+    }
+}
+
+/// Onchain transactions and RGB transfers known to the wallets
+fn layer_transactions(
+    bitcoin_wallet: &BitcoinWallet,
+    rgb_wallet: Option<&Mutex<RgbWallet>>,
+) -> Result<Vec<WalletkaTransaction>, WalletkaError> {
+    let mut transactions: Vec<WalletkaTransaction> = bitcoin_wallet
+        .get_transactions()?
+        .into_iter()
+        .map(WalletkaTransaction::from)
+        .collect();
+
+    if let Some(rgb_wallet) = rgb_wallet {
+        let rgb_wallet = lock_rgb(rgb_wallet);
+
+        for asset in rgb_wallet.get_rgb20_assets()? {
+            for transfer in rgb_wallet.list_transfers(asset.asset_id.clone())? {
+                transactions.push(WalletkaTransaction::from((asset.clone(), transfer)));
+            }
+        }
+    }
+
+    Ok(transactions)
+}
+
+/// Sync panicking while the RGB wallet is locked leaves it usable
+fn lock_rgb(rgb_wallet: &Mutex<RgbWallet>) -> MutexGuard<'_, RgbWallet> {
+    rgb_wallet.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn rgb_unavailable() -> WalletkaError {
+    WalletkaError::LayerUnavailable {
+        details: "RGB wallet is not available in watch-only mode or with BIP39 passphrase"
+            .to_string(),
+    }
+}
+
+fn parse_outpoints(outpoints: Vec<String>) -> Result<Vec<OutPoint>, WalletkaError> {
+    let mut parsed = vec![];
+
+    for outpoint in outpoints {
+        parsed.push(OutPoint::from_str(&outpoint)?);
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::WalletkaBuilder,
+        enums::{BitcoinScriptType, WatchOnlySource},
+    };
+    use anyhow::Result;
+    use bdk::bitcoin::{
+        bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey},
+        secp256k1::{Secp256k1, SecretKey},
+        Network,
+    };
+    use nostr_sdk::Keys;
+    use std::fs;
+
+    const TOKEN: &str = "cashuAeyJ0b2tlbiI6W3sibWludCI6Imh0dHBzOi8vODMzMy5zcGFjZTozMzM4IiwicHJvb2ZzIjpbeyJpZCI6IkRTQWw5bnZ2eWZ2YSIsImFtb3VudCI6Miwic2VjcmV0IjoiRWhwZW5uQzlxQjNpRmxXOEZaX3BadyIsIkMiOiIwMmMwMjAwNjdkYjcyN2Q1ODZiYzMxODNhZWNmOTdmY2I4MDBjM2Y0Y2M0NzU5ZjY5YzYyNmM5ZGI1ZDhmNWI1ZDQifSx7ImlkIjoiRFNBbDludnZ5ZnZhIiwiYW1vdW50Ijo4LCJzZWNyZXQiOiJUbVM2Q3YwWVQ1UFVfNUFUVktudWt3IiwiQyI6IjAyYWM5MTBiZWYyOGNiZTVkNzMyNTQxNWQ1YzI2MzAyNmYxNWY5Yjk2N2EwNzljYTk3NzlhYjZlNWMyZGIxMzNhNyJ9XX1dLCJtZW1vIjoiVGhhbmt5b3UuIn0=";
+
+    async fn watch_only_walletka(data_path: &str) -> Result<Walletka> {
+        let secp = Secp256k1::new();
+        let xprv = ExtendedPrivKey::new_master(Network::Regtest, &[1; 32])?
+            .derive_priv(&secp, &DerivationPath::from_str("m/84'/1'/0'")?)?;
+
+        let mut builder = WalletkaBuilder::default();
+        builder.set_data_path(data_path.to_string());
+        builder.set_watch_only(Some(WatchOnlySource::Xpub {
+            xpub: ExtendedPubKey::from_priv(&secp, &xprv).to_string(),
+            script_type: BitcoinScriptType::Bip84,
+        }));
+
+        Ok(builder.build().await?)
+    }
+
+    fn npub() -> Result<String> {
+        Ok(Keys::generate().public_key().to_bech32()?)
+    }
+
+    #[tokio::test]
+    async fn parse_destination() -> Result<()> {
+        let data_path = ".test_data_parse_destination";
+        let walletka = watch_only_walletka(data_path).await?;
+
+        let address = walletka.get_bitcoin_address()?.to_string();
+        assert_eq!(
+            walletka.parse_destination(format!(" {} ", address))?,
+            PaymentDestination::BitcoinAddress {
+                address: address.clone()
+            }
+        );
+
+        match walletka.parse_destination(format!("BITCOIN:{}?amount=0.0001", address))? {
+            PaymentDestination::BitcoinUri { uri } => {
+                assert_eq!(uri.address, address);
+                assert_eq!(uri.amount_sat, Some(10_000));
+            }
+            destination => panic!("Unexpected destination {:?}", destination),
+        }
+
+        let secp = Secp256k1::new();
+        let sp_address = SilentPaymentAddress {
+            scan_key: SecretKey::from_slice(&[1; 32])?.public_key(&secp),
+            spend_key: SecretKey::from_slice(&[2; 32])?.public_key(&secp),
+            mainnet: false,
+        }
+        .to_string();
+        assert_eq!(
+            walletka.parse_destination(sp_address.clone())?,
+            PaymentDestination::SilentPaymentAddress {
+                address: sp_address
+            }
+        );
+
+        assert_eq!(
+            walletka.parse_destination(TOKEN.to_string())?,
+            PaymentDestination::CashuToken {
+                token: TOKEN.to_string(),
+                mint_url: "https://8333.space:3338".to_string(),
+                amount_sat: 10,
+                memo: Some("Thankyou.".to_string()),
+            }
+        );
+
+        let npub = npub()?;
+        assert_eq!(
+            walletka.parse_destination(format!("nostr:{}", npub))?,
+            PaymentDestination::NostrPubkey { npub: npub.clone() }
+        );
+        assert_eq!(
+            walletka.parse_destination(npub.clone())?,
+            PaymentDestination::NostrPubkey { npub }
+        );
+
+        assert!(walletka
+            .parse_destination("rgb:invalid".to_string())
+            .is_err());
+        assert!(walletka
+            .parse_destination("npub1invalid".to_string())
+            .is_err());
+        assert!(matches!(
+            walletka.parse_destination("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string()),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            walletka.parse_destination("unknown".to_string()),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+
+        drop(walletka);
+        fs::remove_dir_all(data_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn payment_amount_precedence() {
+        assert_eq!(payment_amount(Some(1000), None).unwrap(), 1000);
+        assert_eq!(payment_amount(None, Some(2000)).unwrap(), 2000);
+        assert_eq!(payment_amount(Some(1000), Some(1000)).unwrap(), 1000);
+        assert!(matches!(
+            payment_amount(Some(1000), Some(2000)),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            payment_amount(None, None),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+    }
+
+    #[test]
+    fn bip21_routes_by_parameters() -> Result<()> {
+        let address = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string();
+
+        let mut uri = Bip21Uri::new(address.clone());
+        uri.amount_sat = Some(1000);
+        uri.label = Some("label".to_string());
+        uri.message = Some("message".to_string());
+        uri.payjoin_endpoint = Some("https://payjoin.example".to_string());
+        uri.cashu_npub = Some(npub()?);
+
+        let routes = bip21_routes(uri.clone(), None)?;
+        assert_eq!(routes.len(), 2);
+        assert!(matches!(
+            &routes[0],
+            PaymentRoute::Cashu { amount_sat: 1000, memo: Some(memo), .. } if memo == "message"
+        ));
+        assert!(matches!(
+            &routes[1],
+            PaymentRoute::Onchain {
+                address: route_address,
+                payjoin_endpoint: Some(_),
+                amount_sat: 1000,
+                memo: Some(_),
+            } if *route_address == address
+        ));
+
+        assert!(matches!(
+            bip21_routes(uri.clone(), Some(2000)),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+
+        uri.amount_sat = None;
+        uri.cashu_npub = None;
+        assert!(matches!(
+            bip21_routes(uri.clone(), None),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        let routes = bip21_routes(uri.clone(), Some(500))?;
+        assert_eq!(routes.len(), 1);
+        assert!(matches!(
+            routes[0],
+            PaymentRoute::Onchain {
+                amount_sat: 500,
+                ..
+            }
+        ));
+
+        uri.rgb_invoice = Some("rgb:invalid".to_string());
+        assert!(bip21_routes(uri, Some(500)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn cheapest_route_selection() -> Result<()> {
+        let onchain = |amount_sat| PaymentRoute::Onchain {
+            address: "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string(),
+            payjoin_endpoint: None,
+            amount_sat,
+            memo: None,
+        };
+        let insufficient = || WalletkaError::InsufficientFunds {
+            details: "Insufficient funds".to_string(),
+        };
+
+        let route = cheapest_route(vec![
+            (onchain(1), Ok(Some(300))),
+            (onchain(2), Err(insufficient())),
+            (onchain(3), Ok(Some(200))),
+            (onchain(4), Ok(Some(200))),
+        ])?;
+        assert!(matches!(route, PaymentRoute::Onchain { amount_sat: 3, .. }));
+
+        // Unknown fee is never preferred over known one
+        let route = cheapest_route(vec![
+            (onchain(1), Ok(None)),
+            (onchain(2), Ok(Some(u64::MAX))),
+        ])?;
+        assert!(matches!(route, PaymentRoute::Onchain { amount_sat: 2, .. }));
+
+        let route = cheapest_route(vec![
+            (onchain(1), Err(insufficient())),
+            (onchain(2), Ok(None)),
+        ])?;
+        assert!(matches!(route, PaymentRoute::Onchain { amount_sat: 2, .. }));
+
+        let route = cheapest_route(vec![
+            (onchain(1), Err(WalletkaError::Offline)),
+            (onchain(2), Err(insufficient())),
+        ]);
+        assert!(matches!(
+            route,
+            Err(WalletkaError::InsufficientFunds { .. })
+        ));
+
+        assert!(matches!(
+            cheapest_route(vec![]),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unfunded_wallet_routes() -> Result<()> {
+        let data_path = ".test_data_unfunded_routes";
+        let mut walletka = watch_only_walletka(data_path).await?;
+
+        let receiver = PublicKey::from_bech32(&npub()?)?;
+        let route = walletka
+            .select_route(
+                vec![PaymentRoute::Cashu {
+                    receiver,
+                    amount_sat: 1000,
+                    memo: None,
+                }],
+                None,
+            )
+            .await;
+        assert!(matches!(
+            route,
+            Err(WalletkaError::InsufficientFunds { .. })
+        ));
+
+        let address = walletka.get_bitcoin_address()?.to_string();
+        assert!(walletka
+            .pay(
+                address,
+                Some(1000),
+                Some(FeePolicy::FeeRate { sat_per_vb: 2.0 })
+            )
+            .await
+            .is_err());
+
+        let token = walletka.pay(TOKEN.to_string(), None, None).await;
+        assert!(matches!(token, Err(WalletkaError::InvalidInput { .. })));
+
+        drop(walletka);
+        fs::remove_dir_all(data_path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sync_on_current_thread_runtime() -> Result<()> {
+        let data_path = ".test_data_current_thread_sync";
+        let mut walletka = watch_only_walletka(data_path).await?;
+
+        // Wallet without chain backend reports failed layer instead of failing sync
+        let report = walletka.sync(true).await?;
+        assert_eq!(1, report.layers.len());
+        assert_eq!(WalletkaLayer::Blockchain, report.layers[0].layer);
+        assert!(matches!(
+            report.layers[0].status,
+            LayerSyncStatus::Failed { .. }
+        ));
+
+        drop(walletka);
+        fs::remove_dir_all(data_path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sync_scheduler_lifecycle() -> Result<()> {
+        let data_path = ".test_data_sync_scheduler";
+        let mut walletka = watch_only_walletka(data_path).await?;
+        assert!(!walletka.is_sync_scheduler_running());
+
+        walletka
+            .start_sync_scheduler(SyncSchedulerConfig::default())
+            .await?;
+        assert!(walletka.is_sync_scheduler_running());
+
+        // Wallet stays usable while scheduled sync runs
+        walletka.set_online(false);
+        walletka.get_bitcoin_address()?;
+        walletka.sync(true).await?;
+
+        walletka.stop_sync_scheduler().await;
+        assert!(!walletka.is_sync_scheduler_running());
+
+        drop(walletka);
+        fs::remove_dir_all(data_path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn watch_only_layers() -> Result<()> {
+        let data_path = ".test_data_watch_only_layers";
+        let mut walletka = watch_only_walletka(data_path).await?;
+
+        assert_eq!(
+            vec![WalletkaLayer::Blockchain, WalletkaLayer::Cashu],
+            walletka.get_layers()
+        );
+        assert!(matches!(
+            walletka.create_rgb_utxos(),
+            Err(WalletkaError::LayerUnavailable { .. })
+        ));
+
+        drop(walletka);
+        fs::remove_dir_all(data_path)?;
+
+        Ok(())
+    }
+}
@@ -1,37 +1,75 @@
-use anyhow::{bail, Result};
-use bdk::bitcoin::bip32::ExtendedPubKey;
-use bdk::bitcoin::psbt::PartiallySignedTransaction;
-use bdk::bitcoin::secp256k1::Secp256k1;
-use bdk::bitcoin::{Address, Network, Transaction};
-use bdk::blockchain::{AnyBlockchain, Blockchain, ElectrumBlockchain};
-use bdk::database::any::SledDbConfiguration;
-use bdk::database::{AnyDatabase, ConfigurableDatabase};
-use bdk::electrum_client::Client;
+use bdk::bitcoin::absolute::LockTime;
+use bdk::bitcoin::base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bdk::bitcoin::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+use bdk::bitcoin::hashes::Hash;
+use bdk::bitcoin::key::TweakedPublicKey;
+use bdk::bitcoin::psbt::{Input as PsbtInput, PartiallySignedTransaction};
+use bdk::bitcoin::secp256k1::{All, KeyPair, Message, Secp256k1};
+use bdk::bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bdk::bitcoin::{
+    taproot, Address, Network, OutPoint, PrivateKey, Script, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, Txid, Witness,
+};
+use bdk::database::{AnyDatabase, BatchOperations, Database, MemoryDatabase};
 use bdk::keys::{bip39::Mnemonic, DerivableKey, ExtendedKey};
+use bdk::miniscript::descriptor::{
+    Descriptor, DescriptorPublicKey, DescriptorSecretKey, DescriptorType,
+};
+use bdk::miniscript::psbt::PsbtExt;
 use bdk::template::Bip84;
 use bdk::wallet::AddressIndex;
-use bdk::{Balance, LocalUtxo, SignOptions, SyncOptions, Wallet as BdkWallet};
-use log::{debug, info};
-use crate::wallets::NestedWallet;
+use bdk::{
+    Balance, FeeRate, KeychainKind, LocalUtxo, SignOptions, TransactionDetails, Wallet as BdkWallet,
+};
+use log::{debug, info, warn};
+use sha256::digest;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
 
-pub struct BitcoinWallet {
+use crate::enums::{BitcoinScriptType, FeePolicy, MessageSignatureFormat, WatchOnlySource};
+use crate::errors::WalletkaError;
+use crate::io::clients::{BlockchainClient, ChainBackend, PayjoinClient, PayjoinParams};
+use crate::types::{BatchPaymentOutput, BatchPaymentResult, BitcoinAccount, BitcoinTxPreview};
+use crate::wallets::silent_payments::{self, SilentPaymentAddress, SilentPaymentKeys};
+use crate::wallets::{message_signing, NestedWallet};
+
+/// Sled tree used by wallets created before keychains were split and the passphrase applied
+const LEGACY_TREE_NAME: &str = "MAIN_WALLET";
+
+struct BitcoinAccountWallet {
+    account: BitcoinAccount,
     wallet: BdkWallet<AnyDatabase>,
-    pub xpub: ExtendedPubKey,
-    blockchain: Option<AnyBlockchain>,
+    xpub: Option<ExtendedPubKey>,
+    tree_name: String,
 }
 
-impl NestedWallet for BitcoinWallet {
+pub struct BitcoinWallet {
+    accounts: Vec<BitcoinAccountWallet>,
+    active_account: usize,
+    pub xpub: Option<ExtendedPubKey>,
+    watch_only: bool,
+    blockchain: Option<Arc<BlockchainClient>>,
+    sled_db: bdk::sled::Db,
+    legacy_wallet: Option<BdkWallet<AnyDatabase>>,
+    frozen_utxos: HashSet<OutPoint>,
+    silent_payment_keys: Option<SilentPaymentKeys>,
+}
 
- fn sync(&self) -> Result<()> {
+impl NestedWallet for BitcoinWallet {
+    fn sync(&self) -> Result<(), WalletkaError> {
         debug!("Syncing with blockchain...");
 
         match &self.blockchain {
             Some(blockchain) => {
-                self.wallet.sync(blockchain, SyncOptions::default())?;
+                for account in self.accounts.iter() {
+                    debug!("Syncing account {}", account.account);
+                    blockchain.sync_wallet(&account.wallet)?;
+                }
                 info!("Blockchain synced");
                 Ok(())
             }
-            None => bail!("Offline mode"),
+            None => return Err(WalletkaError::Offline),
         }
     }
 }
@@ -43,104 +81,2288 @@ impl BitcoinWallet {
         passphrase: Option<String>,
         electrum_url: String,
         data_path: String,
-    ) -> Result<BitcoinWallet, anyhow::Error> {
-        let client = Client::new(&electrum_url)?;
-        let blockchain = AnyBlockchain::from(ElectrumBlockchain::from(client));
+        accounts: Vec<BitcoinAccount>,
+    ) -> Result<BitcoinWallet, WalletkaError> {
+        let blockchain = BlockchainClient::new(vec![ChainBackend::Electrum { url: electrum_url }])?;
 
         let data_path = format!("{data_path}/bdk");
 
-        BitcoinWallet::new(network, mnemonic, passphrase, Some(blockchain), data_path)
+        BitcoinWallet::new(
+            network,
+            mnemonic,
+            passphrase,
+            Some(Arc::new(blockchain)),
+            data_path,
+            accounts,
+        )
     }
 
+    /// Create wallet holding given accounts. First account is active,
+    /// default BIP84 account is used when no account is provided
     pub fn new(
         network: Network,
         mnemonic: Mnemonic,
-        _passphrase: Option<String>,
-        blockchain: Option<AnyBlockchain>,
+        passphrase: Option<String>,
+        blockchain: Option<Arc<BlockchainClient>>,
         data_path: String,
-    ) -> Result<BitcoinWallet> {
+        accounts: Vec<BitcoinAccount>,
+    ) -> Result<BitcoinWallet, WalletkaError> {
         let data_path = format!("{data_path}/.bdk");
 
         let secp = Secp256k1::new();
 
-        let bdk_config = SledDbConfiguration {
-            path: data_path,
-            tree_name: "MAIN_WALLET".to_string(),
-        };
-        let database = AnyDatabase::from_config(&bdk_config.into())?;
-
         // Generate the extended key
-        let xkey: ExtendedKey = mnemonic.clone().into_extended_key()?;
+        let xkey: ExtendedKey = (mnemonic.clone(), passphrase.clone()).into_extended_key()?;
         // Get xprv from the extended key
-        let xprv = xkey.into_xprv(network).unwrap();
+        let xprv = master_xprv(xkey, network)?;
         let xpub = ExtendedPubKey::from_priv(&secp, &xprv);
 
         info!("Xpub:\n{}", xpub);
 
-        let descriptor = Bip84(xprv, bdk::KeychainKind::Internal);
-        let change_descriptor = Some(Bip84(xprv, bdk::KeychainKind::Internal));
+        let silent_payment_keys = SilentPaymentKeys::from_xprv(&secp, &xprv, coin_type(network))?;
+
+        let accounts = if accounts.is_empty() {
+            vec![BitcoinAccount::default()]
+        } else {
+            accounts
+        };
+
+        let sled_db = bdk::sled::open(&data_path)?;
+        let mut account_wallets = vec![];
+
+        // Without passphrase the legacy addresses are the change addresses of the default
+        // account, so only the derivation index needs to be carried over
+        let mut legacy_wallet = None;
+        let has_legacy_tree = sled_db
+            .tree_names()
+            .iter()
+            .any(|name| name.as_ref() == LEGACY_TREE_NAME.as_bytes());
+
+        if has_legacy_tree {
+            if passphrase.is_none() && accounts.contains(&BitcoinAccount::default()) {
+                migrate_legacy_tree(&sled_db)?;
+            } else {
+                info!("Legacy wallet found, funds have to be swept");
+                legacy_wallet = Some(legacy_bdk_wallet(&sled_db, &mnemonic, network)?);
+            }
+        }
+
+        for account in accounts {
+            if account_wallets
+                .iter()
+                .any(|a: &BitcoinAccountWallet| a.account == account)
+            {
+                return Err(WalletkaError::InvalidInput {
+                    details: format!("Account {} provided more than once", account),
+                });
+            }
+
+            let tree_name = account.tree_name();
+            let database = AnyDatabase::from(sled_db.open_tree(&tree_name)?);
+
+            let account_path = DerivationPath::from_str(&format!(
+                "m/{}'/{}'/{}'",
+                account.script_type.purpose(),
+                coin_type(network),
+                account.index
+            ))?;
+            let account_xpub =
+                ExtendedPubKey::from_priv(&secp, &xprv.derive_priv(&secp, &account_path)?);
+
+            info!("Account {} xpub:\n{}", account, account_xpub);
+
+            let descriptor = account_descriptor(&xprv, &account, network, KeychainKind::External);
+            let change_descriptor =
+                account_descriptor(&xprv, &account, network, KeychainKind::Internal);
+
+            let wallet = BdkWallet::new(
+                descriptor.as_str(),
+                Some(change_descriptor.as_str()),
+                network,
+                database,
+            )?;
+
+            account_wallets.push(BitcoinAccountWallet {
+                account,
+                wallet,
+                xpub: Some(account_xpub),
+                tree_name,
+            });
+        }
+
+        Ok(BitcoinWallet {
+            accounts: account_wallets,
+            active_account: 0,
+            xpub: Some(xpub),
+            watch_only: false,
+            blockchain,
+            sled_db,
+            legacy_wallet,
+            frozen_utxos: HashSet::new(),
+            silent_payment_keys: Some(silent_payment_keys),
+        })
+    }
+
+    /// Create wallet without private keys from account xpub or public descriptors
+    pub fn new_watch_only(
+        network: Network,
+        source: WatchOnlySource,
+        blockchain: Option<Arc<BlockchainClient>>,
+        data_path: String,
+    ) -> Result<BitcoinWallet, WalletkaError> {
+        let data_path = format!("{data_path}/.bdk");
+
+        let (xpub, descriptor, change_descriptor) = match source {
+            WatchOnlySource::Xpub { xpub, script_type } => {
+                // Key origin lets signing devices find their keys in created PSBTs
+                let (origin, xpub) = match xpub.trim().strip_prefix('[') {
+                    Some(xpub) => match xpub.split_once(']') {
+                        Some((origin, xpub)) => (format!("[{origin}]"), xpub),
+                        None => {
+                            return Err(WalletkaError::InvalidPubkey {
+                                details: "Key origin of xpub is not closed".to_string(),
+                            })
+                        }
+                    },
+                    None => (String::new(), xpub.trim()),
+                };
+                let xpub = ExtendedPubKey::from_str(xpub)?;
+                (
+                    Some(xpub),
+                    script_descriptor(script_type, &format!("{origin}{xpub}/0/*")),
+                    Some(script_descriptor(
+                        script_type,
+                        &format!("{origin}{xpub}/1/*"),
+                    )),
+                )
+            }
+            WatchOnlySource::Descriptors {
+                descriptor,
+                change_descriptor,
+            } => (None, descriptor, change_descriptor),
+        };
+
+        // Parsing as public descriptors refuses private keys
+        let parsed_descriptor = Descriptor::<DescriptorPublicKey>::from_str(&descriptor)?;
+        if let Some(change_descriptor) = &change_descriptor {
+            Descriptor::<DescriptorPublicKey>::from_str(change_descriptor)?;
+        }
+
+        let script_type = match parsed_descriptor.desc_type() {
+            DescriptorType::Pkh => BitcoinScriptType::Bip44,
+            DescriptorType::ShWpkh => BitcoinScriptType::Bip49,
+            DescriptorType::Wpkh => BitcoinScriptType::Bip84,
+            DescriptorType::Tr => BitcoinScriptType::Bip86,
+            desc_type => {
+                return Err(WalletkaError::InvalidDescriptor {
+                    details: format!("Unsupported watch-only descriptor type {:?}", desc_type),
+                })
+            }
+        };
+        let account = BitcoinAccount::new(script_type, 0);
+
+        info!("Watch-only descriptor:\n{}", descriptor);
+
+        let sled_db = bdk::sled::open(&data_path)?;
+        let tree_name = format!("WATCH_ONLY_{}", &digest(descriptor.as_str())[..16]);
+        let database = AnyDatabase::from(sled_db.open_tree(&tree_name)?);
 
-        // Todo: Create a BDK wallet structure using BIP 86 descriptor ("m/86'/0'/0'/0" and "m/86'/0'/0'/1")
         let wallet = BdkWallet::new(
-            descriptor,
-            change_descriptor,
+            descriptor.as_str(),
+            change_descriptor.as_deref(),
             network,
             database,
         )?;
 
         Ok(BitcoinWallet {
-            wallet,
+            accounts: vec![BitcoinAccountWallet {
+                account,
+                wallet,
+                xpub,
+                tree_name,
+            }],
+            active_account: 0,
             xpub,
+            watch_only: true,
             blockchain,
+            sled_db,
+            legacy_wallet: None,
+            frozen_utxos: HashSet::new(),
+            silent_payment_keys: None,
+        })
+    }
+
+    pub fn is_watch_only(&self) -> bool {
+        self.watch_only
+    }
+
+    /// Watch-only wallet of all accounts sharing their databases, synced by background
+    /// tasks while this wallet stays usable. Legacy wallet is left out
+    pub fn sync_view(&self) -> Result<BitcoinWallet, WalletkaError> {
+        let mut accounts = vec![];
+
+        for account in self.accounts.iter() {
+            let descriptor = match account.wallet.public_descriptor(KeychainKind::External)? {
+                Some(descriptor) => descriptor.to_string(),
+                None => {
+                    return Err(WalletkaError::InvalidDescriptor {
+                        details: format!("Account {} has no descriptor", account.account),
+                    })
+                }
+            };
+            let change_descriptor = account
+                .wallet
+                .public_descriptor(KeychainKind::Internal)?
+                .map(|d| d.to_string());
+
+            let wallet = BdkWallet::new(
+                descriptor.as_str(),
+                change_descriptor.as_deref(),
+                account.wallet.network(),
+                AnyDatabase::from(self.sled_db.open_tree(&account.tree_name)?),
+            )?;
+
+            accounts.push(BitcoinAccountWallet {
+                account: account.account,
+                wallet,
+                xpub: account.xpub,
+                tree_name: account.tree_name.clone(),
+            });
+        }
+
+        Ok(BitcoinWallet {
+            accounts,
+            active_account: self.active_account,
+            xpub: self.xpub,
+            watch_only: true,
+            blockchain: self.blockchain.clone(),
+            sled_db: self.sled_db.clone(),
+            legacy_wallet: None,
+            frozen_utxos: HashSet::new(),
+            silent_payment_keys: None,
         })
     }
 
+    fn ensure_can_sign(&self) -> Result<(), WalletkaError> {
+        if self.watch_only {
+            return Err(WalletkaError::WatchOnly);
+        }
+        Ok(())
+    }
+
+    /// Frozen utxos are excluded from automatic coin selection
+    pub fn set_utxo_frozen(&mut self, outpoint: OutPoint, frozen: bool) {
+        if frozen {
+            self.frozen_utxos.insert(outpoint);
+        } else {
+            self.frozen_utxos.remove(&outpoint);
+        }
+    }
+
+    pub fn get_frozen_utxos(&self) -> Vec<OutPoint> {
+        self.frozen_utxos.iter().cloned().collect()
+    }
+
+    /// Legacy wallet created with previous descriptors which needs to be swept
+    pub fn has_legacy_wallet(&self) -> bool {
+        self.legacy_wallet.is_some()
+    }
+
+    /// Move all funds of the legacy wallet to the active account and remove its data.
+    /// Returns txid of the sweep transaction if there was anything to sweep
+    pub fn sweep_legacy_wallet(&mut self) -> Result<Option<String>, WalletkaError> {
+        let txid = {
+            let legacy_wallet = match &self.legacy_wallet {
+                Some(wallet) => wallet,
+                None => return Ok(None),
+            };
+
+            match &self.blockchain {
+                Some(blockchain) => blockchain.sync_wallet(legacy_wallet)?,
+                None => return Err(WalletkaError::Offline),
+            }
+
+            if legacy_wallet.get_balance()?.get_spendable() > 0 {
+                let address = self.get_unused_address()?;
 
-    pub fn get_unused_address(&self) -> Result<Address> {
-        Ok(self.wallet.get_address(AddressIndex::LastUnused)?.address)
+                let mut builder = legacy_wallet.build_tx();
+                builder
+                    .drain_wallet()
+                    .drain_to(address.script_pubkey())
+                    .enable_rbf();
+
+                let mut psbt = builder.finish()?.0;
+                legacy_wallet.sign(&mut psbt, SignOptions::default())?;
+
+                let tx = psbt.extract_tx();
+                self.broadcast_tx(&tx)?;
+
+                info!("Legacy wallet swept in {}", tx.txid());
+                Some(tx.txid().to_string())
+            } else {
+                None
+            }
+        };
+
+        self.legacy_wallet = None;
+        self.sled_db.drop_tree(LEGACY_TREE_NAME)?;
+
+        Ok(txid)
     }
 
-    pub fn get_balance(&self) -> Result<Balance> {
-        Ok(self.wallet.get_balance()?)
+    /// Move all funds of private key in WIF format to destination, active account by default.
+    /// P2PKH, P2WPKH and P2TR outputs of the key are swept, one transaction per script type.
+    /// Failure of one script type is returned only when nothing else was swept
+    pub fn sweep_wif(
+        &self,
+        wif: &str,
+        destination: Option<Address>,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<Vec<String>, WalletkaError> {
+        let blockchain = match &self.blockchain {
+            Some(blockchain) => blockchain,
+            None => return Err(WalletkaError::Offline),
+        };
+
+        let network = self.wallet().network();
+        let key = PrivateKey::from_wif(wif.trim())?;
+        if (key.network == Network::Bitcoin) != (network == Network::Bitcoin) {
+            return Err(WalletkaError::InvalidInput {
+                details: format!("Private key is for {} network", key.network),
+            });
+        }
+
+        let destination = match destination {
+            Some(address) => address,
+            None => self.get_unused_address()?,
+        };
+        let fee_rate = match fee_policy {
+            Some(fee_policy) => Some(self.get_fee_rate(fee_policy)?),
+            None => None,
+        };
+
+        let script_types = match key.compressed {
+            true => vec!["pkh", "wpkh", "tr"],
+            false => vec!["pkh"],
+        };
+
+        let mut txids = vec![];
+        let mut last_error = None;
+        for script_type in script_types {
+            let descriptor = format!("{script_type}({wif})");
+
+            match self.sweep_descriptor(blockchain, &descriptor, &destination, fee_rate) {
+                Ok(Some(txid)) => {
+                    info!("Private key swept in {}", txid);
+                    txids.push(txid.to_string());
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(
+                        "Sweeping {} outputs of private key failed: {}",
+                        script_type, err
+                    );
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        if txids.is_empty() {
+            if let Some(err) = last_error {
+                return Err(err);
+            }
+            return Err(WalletkaError::InsufficientFunds {
+                details: "No funds found for private key".to_string(),
+            });
+        }
+
+        Ok(txids)
     }
 
-    pub fn get_utxos(&self) -> Result<Vec<LocalUtxo>> {
-        Ok(self.wallet.list_unspent()?)
+    /// Sweep all funds of single key descriptor, nothing is swept when it has no funds
+    fn sweep_descriptor(
+        &self,
+        blockchain: &BlockchainClient,
+        descriptor: &str,
+        destination: &Address,
+        fee_rate: Option<FeeRate>,
+    ) -> Result<Option<Txid>, WalletkaError> {
+        let wallet = BdkWallet::new(
+            descriptor,
+            None,
+            self.wallet().network(),
+            MemoryDatabase::new(),
+        )?;
+        blockchain.sync_wallet(&wallet)?;
+
+        if wallet.get_balance()?.get_spendable() == 0 {
+            return Ok(None);
+        }
+
+        let mut builder = wallet.build_tx();
+        builder
+            .drain_wallet()
+            .drain_to(destination.script_pubkey())
+            .enable_rbf();
+        if let Some(fee_rate) = fee_rate {
+            builder.fee_rate(fee_rate);
+        }
+
+        let mut psbt = builder.finish()?.0;
+        if !wallet.sign(&mut psbt, SignOptions::default())? {
+            return Err(WalletkaError::Bdk {
+                details: "Sweep transaction can't be finalized".to_string(),
+            });
+        }
+
+        let tx = psbt.extract_tx();
+        self.broadcast_tx(&tx)?;
+
+        Ok(Some(tx.txid()))
     }
 
-    pub fn pay_to_address(&self, address: Address, amount_sat: u64, rbf: bool) -> Result<String> {
-        let mut builder = self.wallet.build_tx();
+    /// Send all spendable funds of active account to address, frozen utxos are kept
+    pub fn drain_to(
+        &self,
+        address: Address,
+        rbf: bool,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<String, WalletkaError> {
+        self.ensure_can_sign()?;
+
+        let mut builder = self.wallet().build_tx();
 
         if rbf {
             builder.enable_rbf();
         }
-        builder.add_recipient(address.script_pubkey(), amount_sat);
-        // Todo
+        if let Some(fee_policy) = fee_policy {
+            builder.fee_rate(self.get_fee_rate(fee_policy)?);
+        }
+        builder
+            .unspendable(self.get_frozen_utxos())
+            .drain_wallet()
+            .drain_to(address.script_pubkey());
 
         let mut psbt = builder.finish()?.0;
         self.sign_psbt(&mut psbt)?;
 
+        let tx = psbt.extract_tx();
+        self.broadcast_tx(&tx)?;
+
+        info!("Wallet drained in {}", tx.txid());
+        Ok(tx.txid().to_string())
+    }
+
+    fn wallet(&self) -> &BdkWallet<AnyDatabase> {
+        &self.accounts[self.active_account].wallet
+    }
+
+    /// Accounts held by the wallet
+    pub fn get_accounts(&self) -> Vec<BitcoinAccount> {
+        self.accounts.iter().map(|a| a.account).collect()
+    }
+
+    pub fn get_active_account(&self) -> BitcoinAccount {
+        self.accounts[self.active_account].account
+    }
+
+    /// Set account used for receiving and spending
+    pub fn set_active_account(&mut self, account: BitcoinAccount) -> Result<(), WalletkaError> {
+        match self.accounts.iter().position(|a| a.account == account) {
+            Some(position) => {
+                self.active_account = position;
+                Ok(())
+            }
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Account {} not found", account),
+                })
+            }
+        }
+    }
+
+    pub fn get_account_xpub(
+        &self,
+        account: BitcoinAccount,
+    ) -> Result<ExtendedPubKey, WalletkaError> {
+        match self.accounts.iter().find(|a| a.account == account) {
+            Some(BitcoinAccountWallet {
+                xpub: Some(xpub), ..
+            }) => Ok(*xpub),
+            Some(_) => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Account {} has no xpub", account),
+                })
+            }
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Account {} not found", account),
+                })
+            }
+        }
+    }
+
+    pub fn get_unused_address(&self) -> Result<Address, WalletkaError> {
+        Ok(self.wallet().get_address(AddressIndex::LastUnused)?.address)
+    }
+
+    /// Get balance of all accounts
+    pub fn get_balance(&self) -> Result<Balance, WalletkaError> {
+        let mut balance = Balance::default();
+
+        for account in self.accounts.iter() {
+            balance = balance + account.wallet.get_balance()?;
+        }
+
+        Ok(balance)
+    }
+
+    /// Get utxos of all accounts
+    pub fn get_utxos(&self) -> Result<Vec<(BitcoinAccount, LocalUtxo)>, WalletkaError> {
+        let mut utxos = vec![];
+
+        for account in self.accounts.iter() {
+            for utxo in account.wallet.list_unspent()? {
+                utxos.push((account.account, utxo));
+            }
+        }
+
+        Ok(utxos)
+    }
+
+    /// Get transactions of all accounts, transfers between accounts are merged
+    pub fn get_transactions(&self) -> Result<Vec<TransactionDetails>, WalletkaError> {
+        let mut transactions: Vec<TransactionDetails> = vec![];
+
+        for account in self.accounts.iter() {
+            for details in account.wallet.list_transactions(false)? {
+                match transactions.iter_mut().find(|t| t.txid == details.txid) {
+                    Some(merged) => {
+                        merged.sent += details.sent;
+                        merged.received += details.received;
+                    }
+                    None => transactions.push(details),
+                }
+            }
+        }
+
+        Ok(transactions)
+    }
+
+    pub fn parse_address(&self, address: &str) -> Result<Address, WalletkaError> {
+        Ok(Address::from_str(address)?.require_network(self.wallet().network())?)
+    }
+
+    /// Fee rate estimated by blockchain backend to confirm within target blocks
+    pub fn estimate_fee_rate(&self, target_blocks: u32) -> Result<FeeRate, WalletkaError> {
+        let fee_rate = match &self.blockchain {
+            Some(blockchain) => blockchain.estimate_fee(target_blocks as usize)?,
+            None => return Err(WalletkaError::Offline),
+        };
+
+        // Backends return negative rate when there is not enough data for estimation
+        if fee_rate < FeeRate::default_min_relay_fee() {
+            return Ok(FeeRate::default_min_relay_fee());
+        }
+
+        Ok(fee_rate)
+    }
+
+    pub fn get_fee_rate(&self, fee_policy: FeePolicy) -> Result<FeeRate, WalletkaError> {
+        match fee_policy {
+            FeePolicy::FeeRate { sat_per_vb } => {
+                if !sat_per_vb.is_finite() || sat_per_vb < 1.0 {
+                    return Err(WalletkaError::InvalidInput {
+                        details: "Fee rate must be at least 1 sat/vB".to_string(),
+                    });
+                }
+                Ok(FeeRate::from_sat_per_vb(sat_per_vb))
+            }
+            FeePolicy::ConfirmationTarget { blocks } => self.estimate_fee_rate(blocks),
+        }
+    }
+
+    /// Inputs are picked by coin selection when no utxo is provided,
+    /// provided utxos are spent even when frozen
+    fn build_payment(
+        &self,
+        script_pubkey: ScriptBuf,
+        amount_sat: u64,
+        rbf: bool,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<OutPoint>,
+    ) -> Result<(PartiallySignedTransaction, TransactionDetails), WalletkaError> {
+        let mut builder = self.wallet().build_tx();
+
+        if rbf {
+            builder.enable_rbf();
+        }
+        if utxos.is_empty() {
+            builder.unspendable(self.get_frozen_utxos());
+        } else {
+            builder.add_utxos(&utxos)?.manually_selected_only();
+        }
+        if let Some(fee_policy) = fee_policy {
+            builder.fee_rate(self.get_fee_rate(fee_policy)?);
+        }
+        builder.add_recipient(script_pubkey, amount_sat);
+
+        Ok(builder.finish()?)
+    }
+
+    /// Returns txid and fee of broadcasted transaction
+    pub fn pay_to_address(
+        &self,
+        address: Address,
+        amount_sat: u64,
+        rbf: bool,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<OutPoint>,
+    ) -> Result<(String, u64), WalletkaError> {
+        self.ensure_can_sign()?;
+
+        let (mut psbt, details) =
+            self.build_payment(address.script_pubkey(), amount_sat, rbf, fee_policy, utxos)?;
+        self.sign_psbt(&mut psbt)?;
+
         let tx = psbt.extract_tx();
 
         match self.broadcast_tx(&tx) {
-            Ok(_) => Ok(tx.txid().to_string()),
+            Ok(_) => Ok((tx.txid().to_string(), details.fee.unwrap_or_default())),
             Err(err) => Err(err),
         }
     }
 
-    pub fn sign_psbt(&self, psbt: &mut PartiallySignedTransaction) -> Result<()> {
-        self.wallet.sign(psbt, SignOptions::default())?;
+    /// Build payment without signing and broadcasting it
+    pub fn preview_payment(
+        &self,
+        address: Address,
+        amount_sat: u64,
+        rbf: bool,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<OutPoint>,
+    ) -> Result<BitcoinTxPreview, WalletkaError> {
+        let (psbt, details) =
+            self.build_payment(address.script_pubkey(), amount_sat, rbf, fee_policy, utxos)?;
+
+        preview_psbt(self.wallet(), &psbt, details.fee.unwrap_or_default())
+    }
+
+    /// Pay to many recipients in one transaction.
+    /// All remaining non frozen funds go to `send_max_to` output when set
+    pub fn pay_batch(
+        &self,
+        recipients: Vec<(Address, u64)>,
+        send_max_to: Option<Address>,
+        rbf: bool,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<BatchPaymentResult, WalletkaError> {
+        self.ensure_can_sign()?;
+
+        if recipients.is_empty() && send_max_to.is_none() {
+            return Err(WalletkaError::InvalidInput {
+                details: "No recipients provided".to_string(),
+            });
+        }
+
+        let mut builder = self.wallet().build_tx();
+
+        if rbf {
+            builder.enable_rbf();
+        }
+        if let Some(fee_policy) = fee_policy {
+            builder.fee_rate(self.get_fee_rate(fee_policy)?);
+        }
+        builder.unspendable(self.get_frozen_utxos());
+        builder.set_recipients(
+            recipients
+                .iter()
+                .map(|(address, amount_sat)| (address.script_pubkey(), *amount_sat))
+                .collect(),
+        );
+        if let Some(address) = &send_max_to {
+            builder.drain_wallet().drain_to(address.script_pubkey());
+        }
+
+        let (mut psbt, details) = builder.finish()?;
+        self.sign_psbt(&mut psbt)?;
+
+        let tx = psbt.extract_tx();
+        let outputs = batch_outputs(&tx, &recipients, send_max_to.as_ref())?;
+
+        self.broadcast_tx(&tx)?;
+
+        Ok(BatchPaymentResult {
+            txid: tx.txid().to_string(),
+            fee_sat: details.fee.unwrap_or_default(),
+            outputs,
+        })
+    }
+
+    /// Build unsigned payment PSBT, can be created by watch-only wallet
+    pub fn create_psbt(
+        &self,
+        address: Address,
+        amount_sat: u64,
+        rbf: bool,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<OutPoint>,
+    ) -> Result<PartiallySignedTransaction, WalletkaError> {
+        Ok(self
+            .build_payment(address.script_pubkey(), amount_sat, rbf, fee_policy, utxos)?
+            .0)
+    }
+
+    /// Merge signatures and other data of PSBTs spending the same transaction
+    pub fn combine_psbts(
+        &self,
+        psbts: Vec<PartiallySignedTransaction>,
+    ) -> Result<PartiallySignedTransaction, WalletkaError> {
+        let mut psbts = psbts.into_iter();
+
+        let mut combined = match psbts.next() {
+            Some(psbt) => psbt,
+            None => {
+                return Err(WalletkaError::InvalidInput {
+                    details: "No PSBT to combine".to_string(),
+                })
+            }
+        };
+
+        for psbt in psbts {
+            combined.combine(psbt)?;
+        }
+
+        Ok(combined)
+    }
+
+    /// Finalize all inputs which aren't final yet, works for inputs of any
+    /// descriptor as long as PSBT carries scripts and enough signatures
+    pub fn finalize_psbt(
+        &self,
+        psbt: &mut PartiallySignedTransaction,
+    ) -> Result<(), WalletkaError> {
+        let secp = Secp256k1::verification_only();
+
+        for index in 0..psbt.inputs.len() {
+            let input = &psbt.inputs[index];
+            if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+                continue;
+            }
+
+            psbt.finalize_inp_mut(&secp, index)?;
+        }
+
         Ok(())
     }
 
-    pub fn broadcast_tx(&self, transaction: &Transaction) -> Result<()> {
-        match &self.blockchain {
-            Some(blockchain) => {
-                let tx = transaction;
-                blockchain.broadcast(tx)?;
-                Ok(())
+    /// Find utxo of any account
+    fn get_utxo(&self, outpoint: OutPoint) -> Result<Option<LocalUtxo>, WalletkaError> {
+        for account in self.accounts.iter() {
+            if let Some(utxo) = account.wallet.get_utxo(outpoint)? {
+                return Ok(Some(utxo));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Find account wallet which knows the transaction
+    fn find_tx(
+        &self,
+        txid: &Txid,
+    ) -> Result<(&BdkWallet<AnyDatabase>, TransactionDetails), WalletkaError> {
+        for account in self.accounts.iter() {
+            if let Some(details) = account.wallet.get_tx(txid, true)? {
+                return Ok((&account.wallet, details));
+            }
+        }
+
+        Err(WalletkaError::NotFound {
+            details: format!("Transaction {} not found", txid),
+        })
+    }
+
+    /// Replace unconfirmed transaction signaling RBF with the same one paying higher fee rate
+    pub fn bump_fee(&self, txid: Txid, fee_rate: f32) -> Result<String, WalletkaError> {
+        self.ensure_can_sign()?;
+
+        let (wallet, _) = self.find_tx(&txid)?;
+        let fee_rate = self.get_fee_rate(FeePolicy::FeeRate {
+            sat_per_vb: fee_rate,
+        })?;
+
+        let mut builder = wallet.build_fee_bump(txid)?;
+        builder
+            .fee_rate(fee_rate)
+            .enable_rbf()
+            .unspendable(self.get_frozen_utxos());
+
+        let mut psbt = builder.finish()?.0;
+        self.sign_psbt(&mut psbt)?;
+
+        let tx = psbt.extract_tx();
+        self.broadcast_tx(&tx)?;
+
+        info!("Transaction {} replaced by {}", txid, tx.txid());
+        Ok(tx.txid().to_string())
+    }
+
+    /// Spend our output of unconfirmed transaction so that parent and child
+    /// together pay target fee rate
+    pub fn cpfp(&self, txid: Txid, package_fee_rate: f32) -> Result<String, WalletkaError> {
+        self.ensure_can_sign()?;
+
+        let (wallet, details) = self.find_tx(&txid)?;
+        let package_fee_rate = self.get_fee_rate(FeePolicy::FeeRate {
+            sat_per_vb: package_fee_rate,
+        })?;
+
+        if details.confirmation_time.is_some() {
+            return Err(WalletkaError::InvalidInput {
+                details: format!("Transaction {} is already confirmed", txid),
+            });
+        }
+
+        let parent_tx = match details.transaction {
+            Some(tx) => tx,
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Transaction {} not available", txid),
+                })
             }
-            None => bail!("Offline mode!"),
+        };
+        let parent_fee = match details.fee {
+            Some(fee) => fee,
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Fee of transaction {} is unknown", txid),
+                })
+            }
+        };
+
+        let mut utxo = None;
+        for vout in 0..parent_tx.output.len() as u32 {
+            if let Some(local_utxo) = wallet.get_utxo(OutPoint { txid, vout })? {
+                if !local_utxo.is_spent {
+                    utxo = Some(local_utxo);
+                    break;
+                }
+            }
+        }
+        let utxo = match utxo {
+            Some(utxo) => utxo,
+            None => {
+                return Err(WalletkaError::InsufficientFunds {
+                    details: format!("No spendable output of transaction {}", txid),
+                })
+            }
+        };
+
+        let drain_address = wallet.get_internal_address(AddressIndex::New)?.address;
+
+        let build_child = |fee: Option<u64>| -> Result<
+            (PartiallySignedTransaction, TransactionDetails),
+            WalletkaError,
+        > {
+            let mut builder = wallet.build_tx();
+            builder
+                .add_utxo(utxo.outpoint)?
+                .manually_selected_only()
+                .drain_to(drain_address.script_pubkey())
+                .enable_rbf();
+
+            match fee {
+                Some(fee) => builder.fee_absolute(fee),
+                None => builder.fee_rate(package_fee_rate),
+            };
+
+            Ok(builder.finish()?)
+        };
+
+        // Build child first to learn its size
+        let (psbt, details) = build_child(None)?;
+        let child_vsize = preview_psbt(wallet, &psbt, details.fee.unwrap_or_default())?.vsize;
+
+        let package_vsize = parent_tx.vsize() as u64 + child_vsize;
+        let package_fee = package_fee_rate.fee_vb(package_vsize as usize);
+        let child_fee = package_fee.saturating_sub(parent_fee);
+
+        if child_fee < FeeRate::default_min_relay_fee().fee_vb(child_vsize as usize) {
+            return Err(WalletkaError::InvalidInput {
+                details: format!("Transaction {} already pays target fee rate", txid),
+            });
         }
+
+        let mut psbt = build_child(Some(child_fee))?.0;
+        self.sign_psbt(&mut psbt)?;
+
+        let tx = psbt.extract_tx();
+        self.broadcast_tx(&tx)?;
+
+        info!("Transaction {} bumped by child {}", txid, tx.txid());
+        Ok(tx.txid().to_string())
+    }
+
+    pub fn get_silent_payment_address(&self) -> Result<SilentPaymentAddress, WalletkaError> {
+        match &self.silent_payment_keys {
+            Some(keys) => Ok(keys.address(&Secp256k1::new(), self.wallet().network())),
+            None => Err(WalletkaError::WatchOnly),
+        }
+    }
+
+    /// Pay to silent payment address, output key is derived from inputs picked by coin selection.
+    /// Returns txid and fee of broadcasted transaction
+    pub fn pay_silent_payment(
+        &self,
+        address: SilentPaymentAddress,
+        amount_sat: u64,
+        rbf: bool,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<OutPoint>,
+    ) -> Result<(String, u64), WalletkaError> {
+        self.ensure_can_sign()?;
+
+        if !address.is_valid_for_network(self.wallet().network()) {
+            return Err(WalletkaError::InvalidAddress {
+                details: "Silent payment address is for another network".to_string(),
+            });
+        }
+
+        // Spend key stands in for the output key until inputs are known, outputs have same size
+        let placeholder = ScriptBuf::new_v1_p2tr_tweaked(
+            TweakedPublicKey::dangerous_assume_tweaked(address.spend_key.x_only_public_key().0),
+        );
+        let (mut psbt, details) =
+            self.build_payment(placeholder.clone(), amount_sat, rbf, fee_policy, utxos)?;
+
+        let secp = Secp256k1::new();
+        let mut input_keys = vec![];
+        for txin in psbt.unsigned_tx.input.iter() {
+            let utxo = match self.get_utxo(txin.previous_output)? {
+                Some(utxo) => utxo,
+                None => {
+                    return Err(WalletkaError::NotFound {
+                        details: format!("Unknown input {}", txin.previous_output),
+                    })
+                }
+            };
+            let private_key = match self.get_script_private_key(&secp, &utxo.txout.script_pubkey)? {
+                Some(private_key) => private_key,
+                None => {
+                    return Err(WalletkaError::Bdk {
+                        details: format!("Missing private key of input {}", txin.previous_output),
+                    })
+                }
+            };
+
+            input_keys.push(silent_payments::input_secret_key(
+                &secp,
+                &private_key.inner,
+                &utxo.txout.script_pubkey,
+            ));
+        }
+
+        let smallest_outpoint = match silent_payments::smallest_outpoint(&psbt.unsigned_tx.input) {
+            Some(outpoint) => outpoint,
+            None => {
+                return Err(WalletkaError::Bdk {
+                    details: "Transaction has no inputs".to_string(),
+                })
+            }
+        };
+        let output_key = silent_payments::sender_output_key(
+            &secp,
+            &input_keys,
+            &smallest_outpoint,
+            &address,
+            0,
+        )?;
+
+        for output in psbt.unsigned_tx.output.iter_mut() {
+            if output.script_pubkey == placeholder {
+                output.script_pubkey = ScriptBuf::new_v1_p2tr_tweaked(
+                    TweakedPublicKey::dangerous_assume_tweaked(output_key),
+                );
+            }
+        }
+
+        self.sign_psbt(&mut psbt)?;
+
+        let tx = psbt.extract_tx();
+        self.broadcast_tx(&tx)?;
+
+        info!("Silent payment sent: {}", tx.txid());
+        Ok((tx.txid().to_string(), details.fee.unwrap_or_default()))
+    }
+
+    /// Find outputs paying to silent payment address of the wallet in block.
+    /// Previous transactions of inputs are fetched from blockchain to get input keys
+    pub fn scan_silent_payments(
+        &self,
+        height: u32,
+    ) -> Result<Vec<(OutPoint, TxOut, [u8; 32])>, WalletkaError> {
+        let keys = match &self.silent_payment_keys {
+            Some(keys) => keys,
+            None => return Err(WalletkaError::WatchOnly),
+        };
+        let blockchain = match &self.blockchain {
+            Some(blockchain) => blockchain,
+            None => return Err(WalletkaError::Offline),
+        };
+
+        let block = blockchain.get_block(height)?;
+        let secp = Secp256k1::new();
+        let mut found = vec![];
+
+        'txs: for tx in block.txdata.iter().filter(|tx| {
+            !tx.is_coin_base() && tx.output.iter().any(|o| o.script_pubkey.is_v1_p2tr())
+        }) {
+            let mut input_keys = vec![];
+            for txin in tx.input.iter() {
+                let prev_tx = blockchain.get_tx(&txin.previous_output.txid)?;
+                let prevout = match prev_tx
+                    .as_ref()
+                    .and_then(|prev_tx| prev_tx.output.get(txin.previous_output.vout as usize))
+                {
+                    Some(prevout) => prevout,
+                    None => {
+                        debug!(
+                            "Skipping {}, previous output {} not found",
+                            tx.txid(),
+                            txin.previous_output
+                        );
+                        continue 'txs;
+                    }
+                };
+
+                if let Some(key) = silent_payments::input_public_key(txin, prevout) {
+                    input_keys.push(key);
+                }
+            }
+
+            let smallest_outpoint = match silent_payments::smallest_outpoint(&tx.input) {
+                Some(outpoint) => outpoint,
+                None => continue,
+            };
+
+            // Input keys may not be combinable, such transaction can't pay to silent payment
+            let outputs = match keys.scan(&secp, &input_keys, &smallest_outpoint, &tx.output) {
+                Ok(outputs) => outputs,
+                Err(err) => {
+                    debug!("Skipping {}: {}", tx.txid(), err);
+                    continue;
+                }
+            };
+
+            for (vout, tweak) in outputs {
+                info!("Silent payment found: {}:{}", tx.txid(), vout);
+                found.push((
+                    OutPoint::new(tx.txid(), vout as u32),
+                    tx.output[vout].clone(),
+                    tweak,
+                ));
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Spend outputs received to silent payment address to the active account
+    pub fn sweep_silent_payments(
+        &self,
+        outputs: Vec<(OutPoint, TxOut, [u8; 32])>,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<String, WalletkaError> {
+        let keys = match &self.silent_payment_keys {
+            Some(keys) => keys,
+            None => return Err(WalletkaError::WatchOnly),
+        };
+        if outputs.is_empty() {
+            return Err(WalletkaError::InsufficientFunds {
+                details: "No silent payment output to sweep".to_string(),
+            });
+        }
+
+        let fee_rate = match fee_policy {
+            Some(fee_policy) => self.get_fee_rate(fee_policy)?,
+            None => FeeRate::default_min_relay_fee(),
+        };
+        let input_value: u64 = outputs.iter().map(|(_, txout, _)| txout.value).sum();
+
+        // Inputs carry dummy signatures so the fee is computed from final size
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: outputs
+                .iter()
+                .map(|(outpoint, _, _)| TxIn {
+                    previous_output: *outpoint,
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    witness: Witness::from_slice(&[[0; 64]]),
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: input_value,
+                script_pubkey: self.get_unused_address()?.script_pubkey(),
+            }],
+        };
+
+        let fee = fee_rate.fee_vb(tx.vsize());
+        tx.output[0].value = match input_value.checked_sub(fee) {
+            Some(value) if value > tx.output[0].script_pubkey.dust_value().to_sat() => value,
+            _ => {
+                return Err(WalletkaError::InsufficientFunds {
+                    details: "Silent payment outputs can't pay the fee".to_string(),
+                })
+            }
+        };
+
+        let secp = Secp256k1::new();
+        let prevouts: Vec<TxOut> = outputs.iter().map(|(_, txout, _)| txout.clone()).collect();
+        let mut witnesses = vec![];
+        {
+            let mut sighash_cache = SighashCache::new(&tx);
+            for (index, (_, _, tweak)) in outputs.iter().enumerate() {
+                let keypair = KeyPair::from_secret_key(&secp, &keys.output_key(*tweak)?);
+                let sighash = sighash_cache.taproot_key_spend_signature_hash(
+                    index,
+                    &Prevouts::All(&prevouts),
+                    TapSighashType::Default,
+                )?;
+
+                let signature = taproot::Signature {
+                    sig: secp.sign_schnorr_no_aux_rand(
+                        &Message::from_slice(&sighash.to_byte_array())?,
+                        &keypair,
+                    ),
+                    hash_ty: TapSighashType::Default,
+                };
+                witnesses.push(Witness::from_slice(&[signature.to_vec()]));
+            }
+        }
+        for (input, witness) in tx.input.iter_mut().zip(witnesses) {
+            input.witness = witness;
+        }
+
+        self.broadcast_tx(&tx)?;
+
+        info!("Silent payments swept in {}", tx.txid());
+        Ok(tx.txid().to_string())
+    }
+
+    /// Get tip height of the blockchain
+    pub fn get_block_height(&self) -> Result<u32, WalletkaError> {
+        match &self.blockchain {
+            Some(blockchain) => Ok(blockchain.get_height()?),
+            None => return Err(WalletkaError::Offline),
+        }
+    }
+
+    /// Pay to BIP78 payjoin receiver endpoint.
+    /// Original transaction is broadcasted when payjoin fails. Returns txid and fee
+    /// of broadcasted transaction
+    pub fn pay_payjoin(
+        &self,
+        address: Address,
+        amount_sat: u64,
+        endpoint: &str,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<(String, u64), WalletkaError> {
+        self.ensure_can_sign()?;
+
+        let mut original = self
+            .build_payment(
+                address.script_pubkey(),
+                amount_sat,
+                true,
+                fee_policy,
+                vec![],
+            )?
+            .0;
+        self.sign_psbt(&mut original)?;
+
+        let original_tx = original.clone().extract_tx();
+        let original_fee = psbt_fee(&original)?;
+        let fee_rate = original_fee as f32 / original_tx.vsize() as f32;
+
+        let change_index = original_tx.output.iter().position(|output| {
+            output.script_pubkey != address.script_pubkey()
+                && self
+                    .wallet()
+                    .is_mine(&output.script_pubkey)
+                    .unwrap_or(false)
+        });
+        let max_fee_contribution = (input_vsize(self.wallet())? as f32 * fee_rate).ceil() as u64;
+
+        let params = PayjoinParams {
+            additional_fee_output_index: change_index,
+            max_additional_fee_contribution: change_index.map(|_| max_fee_contribution),
+            min_fee_rate: Some(fee_rate),
+            disable_output_substitution: true,
+        };
+
+        let payjoin = PayjoinClient::send_original(endpoint, &original.to_string(), &params)
+            .map_err(WalletkaError::from)
+            .and_then(|proposal| {
+                self.process_payjoin_proposal(&original, &proposal, &params, &address)
+            })
+            .and_then(|(tx, fee)| {
+                self.broadcast_tx(&tx)?;
+                Ok((tx, fee))
+            });
+
+        match payjoin {
+            Ok((tx, fee)) => {
+                info!("Payjoin sent: {}", tx.txid());
+                Ok((tx.txid().to_string(), fee))
+            }
+            Err(err) => {
+                warn!("Payjoin failed, broadcasting original transaction: {}", err);
+                self.broadcast_tx(&original_tx)?;
+                Ok((original_tx.txid().to_string(), original_fee))
+            }
+        }
+    }
+
+    /// Check payjoin proposal against the original PSBT and sign it.
+    /// Returns signed transaction and its fee
+    fn process_payjoin_proposal(
+        &self,
+        original: &PartiallySignedTransaction,
+        proposal: &str,
+        params: &PayjoinParams,
+        payee: &Address,
+    ) -> Result<(Transaction, u64), WalletkaError> {
+        let mut proposal = PartiallySignedTransaction::from_str(proposal)?;
+        let original_tx = &original.unsigned_tx;
+        let proposal_tx = proposal.unsigned_tx.clone();
+
+        if proposal_tx.version != original_tx.version
+            || proposal_tx.lock_time != original_tx.lock_time
+        {
+            return Err(WalletkaError::Payjoin {
+                details: "Payjoin proposal changed transaction version or lock time".to_string(),
+            });
+        }
+        if original_tx.input.iter().any(|original_input| {
+            !proposal_tx
+                .input
+                .iter()
+                .any(|input| input.previous_output == original_input.previous_output)
+        }) {
+            return Err(WalletkaError::Payjoin {
+                details: "Payjoin proposal is missing original input".to_string(),
+            });
+        }
+
+        // Our inputs get back their signing data, receiver inputs have to be signed
+        let mut has_receiver_input = false;
+        for (index, txin) in proposal_tx.input.iter().enumerate() {
+            match original_tx
+                .input
+                .iter()
+                .position(|input| input.previous_output == txin.previous_output)
+            {
+                Some(original_index) => {
+                    if txin.sequence != original_tx.input[original_index].sequence {
+                        return Err(WalletkaError::Payjoin {
+                            details: "Payjoin proposal changed input sequence".to_string(),
+                        });
+                    }
+
+                    let mut input = original.inputs[original_index].clone();
+                    input.final_script_sig = None;
+                    input.final_script_witness = None;
+                    proposal.inputs[index] = input;
+                }
+                None => {
+                    let input = &proposal.inputs[index];
+                    if input.final_script_sig.is_none() && input.final_script_witness.is_none() {
+                        return Err(WalletkaError::Payjoin {
+                            details: format!(
+                                "Receiver input {} is not signed",
+                                txin.previous_output
+                            ),
+                        });
+                    }
+                    if self.wallet().get_utxo(txin.previous_output)?.is_some() {
+                        return Err(WalletkaError::Payjoin {
+                            details: format!(
+                                "Receiver input {} belongs to the wallet",
+                                txin.previous_output
+                            ),
+                        });
+                    }
+                    has_receiver_input = true;
+                }
+            }
+        }
+        if !has_receiver_input {
+            return Err(WalletkaError::Payjoin {
+                details: "Receiver didn't contribute any input".to_string(),
+            });
+        }
+
+        for (index, output) in original_tx.output.iter().enumerate() {
+            let proposed = match proposal_tx
+                .output
+                .iter()
+                .find(|o| o.script_pubkey == output.script_pubkey)
+            {
+                Some(proposed) => proposed,
+                None => {
+                    return Err(WalletkaError::Payjoin {
+                        details: "Payjoin proposal is missing original output".to_string(),
+                    })
+                }
+            };
+
+            let max_decrease = match params.additional_fee_output_index == Some(index) {
+                true => params.max_additional_fee_contribution.unwrap_or_default(),
+                false => 0,
+            };
+
+            if output.script_pubkey == payee.script_pubkey() {
+                if proposed.value < output.value {
+                    return Err(WalletkaError::Payjoin {
+                        details: "Payjoin proposal decreased the payment".to_string(),
+                    });
+                }
+            } else if proposed.value + max_decrease < output.value {
+                return Err(WalletkaError::Payjoin {
+                    details: "Payjoin proposal takes too much from our output".to_string(),
+                });
+            }
+        }
+
+        self.sign_psbt(&mut proposal)?;
+        if proposal
+            .inputs
+            .iter()
+            .any(|i| i.final_script_sig.is_none() && i.final_script_witness.is_none())
+        {
+            return Err(WalletkaError::Payjoin {
+                details: "Payjoin transaction can't be finalized".to_string(),
+            });
+        }
+
+        let fee = psbt_fee(&proposal)?;
+        let tx = proposal.extract_tx();
+
+        // Compared in sat/kvB so that float rounding doesn't reject equal fee rate
+        if let Some(min_fee_rate) = params.min_fee_rate {
+            let min_sat_per_kvb = (min_fee_rate * 1000.0) as u64;
+            if fee * 1000 < min_sat_per_kvb * tx.vsize() as u64 {
+                return Err(WalletkaError::Payjoin {
+                    details: "Payjoin transaction fee rate is too low".to_string(),
+                });
+            }
+        }
+
+        Ok((tx, fee))
+    }
+
+    /// Contribute wallet input to incoming original payjoin PSBT,
+    /// returns signed payjoin proposal PSBT
+    pub fn create_payjoin_proposal(
+        &self,
+        original: &str,
+        params: PayjoinParams,
+    ) -> Result<PartiallySignedTransaction, WalletkaError> {
+        self.ensure_can_sign()?;
+
+        let original = PartiallySignedTransaction::from_str(original)?;
+        let original_tx = original.clone().extract_tx();
+
+        for (input, txin) in original.inputs.iter().zip(original_tx.input.iter()) {
+            if input.final_script_sig.is_none() && input.final_script_witness.is_none() {
+                return Err(WalletkaError::Payjoin {
+                    details: "Original PSBT is not signed".to_string(),
+                });
+            }
+            if self.wallet().get_utxo(txin.previous_output)?.is_some() {
+                return Err(WalletkaError::Payjoin {
+                    details: format!("Original PSBT spends wallet input {}", txin.previous_output),
+                });
+            }
+        }
+
+        let our_output = match original_tx.output.iter().position(|output| {
+            self.wallet()
+                .is_mine(&output.script_pubkey)
+                .unwrap_or(false)
+        }) {
+            Some(index) => index,
+            None => {
+                return Err(WalletkaError::Payjoin {
+                    details: "Original PSBT doesn't pay to the wallet".to_string(),
+                })
+            }
+        };
+
+        let utxo = match self
+            .wallet()
+            .list_unspent()?
+            .into_iter()
+            .find(|utxo| !utxo.is_spent && !self.frozen_utxos.contains(&utxo.outpoint))
+        {
+            Some(utxo) => utxo,
+            None => {
+                return Err(WalletkaError::Payjoin {
+                    details: "No utxo to contribute".to_string(),
+                })
+            }
+        };
+
+        let fee_rate = psbt_fee(&original)? as f32 / original_tx.vsize() as f32;
+        let additional_fee = (input_vsize(self.wallet())? as f32 * fee_rate).ceil() as u64;
+
+        let mut proposal = original.clone();
+        let mut receiver_fee = additional_fee;
+
+        // Sender pays for our input up to its maximum contribution
+        if let (Some(index), Some(max_contribution)) = (
+            params.additional_fee_output_index,
+            params.max_additional_fee_contribution,
+        ) {
+            if index != our_output && index < proposal.unsigned_tx.output.len() {
+                let output = &mut proposal.unsigned_tx.output[index];
+                let sender_fee = additional_fee.min(max_contribution).min(output.value);
+
+                output.value -= sender_fee;
+                receiver_fee -= sender_fee;
+            }
+        }
+
+        let output = &mut proposal.unsigned_tx.output[our_output];
+        output.value = match (output.value + utxo.txout.value).checked_sub(receiver_fee) {
+            Some(value) => value,
+            None => {
+                return Err(WalletkaError::Payjoin {
+                    details: "Contributed utxo can't pay its fee".to_string(),
+                })
+            }
+        };
+
+        proposal.unsigned_tx.input.push(TxIn {
+            previous_output: utxo.outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: original_tx.input[0].sequence,
+            witness: Witness::new(),
+        });
+        proposal
+            .inputs
+            .push(self.wallet().get_psbt_input(utxo.clone(), None, false)?);
+
+        self.sign_psbt(&mut proposal)?;
+
+        // Sender signs its inputs again, no key data of ours is shared
+        let our_input = proposal.inputs.len() - 1;
+        for (index, input) in proposal.inputs.iter_mut().enumerate() {
+            *input = match index == our_input {
+                true => {
+                    if input.final_script_sig.is_none() && input.final_script_witness.is_none() {
+                        return Err(WalletkaError::Payjoin {
+                            details: "Contributed input can't be finalized".to_string(),
+                        });
+                    }
+
+                    PsbtInput {
+                        witness_utxo: input.witness_utxo.clone(),
+                        non_witness_utxo: input.non_witness_utxo.clone(),
+                        final_script_sig: input.final_script_sig.clone(),
+                        final_script_witness: input.final_script_witness.clone(),
+                        ..Default::default()
+                    }
+                }
+                false => PsbtInput::default(),
+            };
+        }
+        for output in proposal.outputs.iter_mut() {
+            *output = Default::default();
+        }
+
+        info!("Payjoin proposal created with input {}", utxo.outpoint);
+        Ok(proposal)
+    }
+
+    /// Sign message with private key of wallet address
+    pub fn sign_message(
+        &self,
+        address: &Address,
+        message: &str,
+        format: MessageSignatureFormat,
+    ) -> Result<String, WalletkaError> {
+        self.ensure_can_sign()?;
+
+        let secp = Secp256k1::new();
+        let private_key = match self.get_script_private_key(&secp, &address.script_pubkey())? {
+            Some(private_key) => private_key,
+            None => {
+                return Err(WalletkaError::InvalidAddress {
+                    details: format!("Address {} doesn't belong to the wallet", address),
+                })
+            }
+        };
+
+        let signature = match format {
+            MessageSignatureFormat::Bip322Simple => {
+                message_signing::sign_bip322_simple(&secp, address, &private_key, message)?
+            }
+            MessageSignatureFormat::Bip137 => {
+                message_signing::sign_bip137(&secp, address, &private_key, message)?
+            }
+        };
+
+        Ok(signature)
+    }
+
+    /// Verify BIP322 simple or BIP137 message signature of any address
+    pub fn verify_message(
+        &self,
+        address: &Address,
+        message: &str,
+        signature: &str,
+    ) -> Result<bool, WalletkaError> {
+        let secp = Secp256k1::verification_only();
+
+        let is_bip137 = match BASE64.decode(signature.trim()) {
+            Ok(bytes) => message_signing::is_bip137_signature(&bytes),
+            Err(_) => {
+                return Err(WalletkaError::InvalidInput {
+                    details: "Signature is not base64 encoded".to_string(),
+                })
+            }
+        };
+
+        let valid = match is_bip137 {
+            true => message_signing::verify_bip137(&secp, address, message, signature)?,
+            false => message_signing::verify_bip322_simple(&secp, address, message, signature)?,
+        };
+
+        Ok(valid)
+    }
+
+    /// Derive private key of wallet script from account descriptor
+    fn get_script_private_key(
+        &self,
+        secp: &Secp256k1<All>,
+        script_pubkey: &Script,
+    ) -> Result<Option<PrivateKey>, WalletkaError> {
+        for account in self.accounts.iter() {
+            let (keychain, index) = match account
+                .wallet
+                .database()
+                .get_path_from_script_pubkey(script_pubkey)?
+            {
+                Some(path) => path,
+                None => continue,
+            };
+
+            let signers = account.wallet.get_signers(keychain);
+            for secret_key in signers.as_key_map(secp).into_values() {
+                match secret_key {
+                    DescriptorSecretKey::XPrv(xkey) => {
+                        let path = xkey
+                            .derivation_path
+                            .child(ChildNumber::from_normal_idx(index)?);
+                        return Ok(Some(xkey.xkey.derive_priv(secp, &path)?.to_priv()));
+                    }
+                    DescriptorSecretKey::Single(single) => return Ok(Some(single.key)),
+                    _ => continue,
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Sign inputs belonging to any of the accounts
+    pub fn sign_psbt(&self, psbt: &mut PartiallySignedTransaction) -> Result<(), WalletkaError> {
+        self.ensure_can_sign()?;
+
+        for account in self.accounts.iter() {
+            account.wallet.sign(psbt, SignOptions::default())?;
+        }
+        Ok(())
+    }
+
+    pub fn broadcast_tx(&self, transaction: &Transaction) -> Result<(), WalletkaError> {
+        match &self.blockchain {
+            Some(blockchain) => {
+                let tx = transaction;
+                blockchain.broadcast(tx)?;
+                Ok(())
+            }
+            None => return Err(WalletkaError::Offline),
+        }
+    }
+}
+
+/// Estimate size of signed transaction from descriptors of spent utxos
+fn preview_psbt(
+    wallet: &BdkWallet<AnyDatabase>,
+    psbt: &PartiallySignedTransaction,
+    fee_sat: u64,
+) -> Result<BitcoinTxPreview, WalletkaError> {
+    let mut weight = psbt.unsigned_tx.weight().to_wu() as usize;
+    let mut has_witness = false;
+    let mut inputs = vec![];
+
+    for txin in psbt.unsigned_tx.input.iter() {
+        let utxo = match wallet.get_utxo(txin.previous_output)? {
+            Some(utxo) => utxo,
+            None => {
+                return Err(WalletkaError::InvalidPsbt {
+                    details: format!("Unknown input {}", txin.previous_output),
+                })
+            }
+        };
+
+        let descriptor = wallet.get_descriptor_for_keychain(utxo.keychain);
+        weight += descriptor.max_weight_to_satisfy()?;
+        has_witness |= descriptor.desc_type().segwit_version().is_some();
+
+        inputs.push(txin.previous_output.to_string());
+    }
+
+    // Segwit marker and flag
+    if has_witness {
+        weight += 2;
+    }
+
+    let vsize = ((weight + 3) / 4) as u64;
+
+    Ok(BitcoinTxPreview {
+        fee_sat,
+        vsize,
+        fee_rate: fee_sat as f32 / vsize as f32,
+        inputs,
+    })
+}
+
+/// Map every recipient to its own output, same address may be paid more times.
+/// Output of `send_max_to` address takes any amount
+fn batch_outputs(
+    tx: &Transaction,
+    recipients: &[(Address, u64)],
+    send_max_to: Option<&Address>,
+) -> Result<Vec<BatchPaymentOutput>, WalletkaError> {
+    let mut used = HashSet::new();
+    let mut outputs = vec![];
+
+    let payees = recipients
+        .iter()
+        .map(|(address, amount_sat)| (address, Some(*amount_sat)))
+        .chain(send_max_to.map(|address| (address, None)));
+
+    for (address, amount_sat) in payees {
+        let script = address.script_pubkey();
+        let vout = match tx.output.iter().enumerate().position(|(vout, output)| {
+            !used.contains(&vout)
+                && output.script_pubkey == script
+                && amount_sat.map_or(true, |amount| output.value == amount)
+        }) {
+            Some(vout) => vout,
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Output for {} not found", address),
+                })
+            }
+        };
+
+        used.insert(vout);
+        outputs.push(BatchPaymentOutput {
+            address: address.to_string(),
+            amount_sat: tx.output[vout].value,
+            vout: vout as u32,
+        });
+    }
+
+    Ok(outputs)
+}
+
+/// Fee of PSBT computed from utxo data of its inputs
+fn psbt_fee(psbt: &PartiallySignedTransaction) -> Result<u64, WalletkaError> {
+    let mut input_value = 0;
+
+    for (input, txin) in psbt.inputs.iter().zip(psbt.unsigned_tx.input.iter()) {
+        input_value += match (&input.witness_utxo, &input.non_witness_utxo) {
+            (Some(txout), _) => txout.value,
+            (None, Some(tx)) => match tx.output.get(txin.previous_output.vout as usize) {
+                Some(txout) => txout.value,
+                None => {
+                    return Err(WalletkaError::InvalidPsbt {
+                        details: format!("Invalid utxo of input {}", txin.previous_output),
+                    })
+                }
+            },
+            (None, None) => {
+                return Err(WalletkaError::InvalidPsbt {
+                    details: format!("Missing utxo of input {}", txin.previous_output),
+                })
+            }
+        };
+    }
+
+    let output_value: u64 = psbt.unsigned_tx.output.iter().map(|o| o.value).sum();
+
+    match input_value.checked_sub(output_value) {
+        Some(fee) => Ok(fee),
+        None => {
+            return Err(WalletkaError::InvalidPsbt {
+                details: "PSBT outputs exceed its inputs".to_string(),
+            })
+        }
+    }
+}
+
+/// Virtual size of signed input spending wallet utxo
+fn input_vsize(wallet: &BdkWallet<AnyDatabase>) -> Result<u64, WalletkaError> {
+    // Outpoint, sequence and script length
+    let base_weight = (32 + 4 + 4 + 1) * 4;
+    let descriptor = wallet.get_descriptor_for_keychain(KeychainKind::External);
+
+    Ok(((base_weight + descriptor.max_weight_to_satisfy()? + 3) / 4) as u64)
+}
+
+fn coin_type(network: Network) -> u32 {
+    match network {
+        Network::Bitcoin => 0,
+        _ => 1,
+    }
+}
+
+/// Carry over last derivation index of the legacy wallet to the default account change keychain
+fn migrate_legacy_tree(sled_db: &bdk::sled::Db) -> Result<(), WalletkaError> {
+    info!("Migrating legacy wallet");
+
+    let legacy_tree = sled_db.open_tree(LEGACY_TREE_NAME)?;
+    let legacy_index = legacy_tree
+        .get_last_index(KeychainKind::External)?
+        .max(legacy_tree.get_last_index(KeychainKind::Internal)?);
+
+    if let Some(legacy_index) = legacy_index {
+        let mut tree = sled_db.open_tree(BitcoinAccount::default().tree_name())?;
+
+        if tree.get_last_index(KeychainKind::Internal)? < Some(legacy_index) {
+            debug!("Setting change index to {}", legacy_index);
+            tree.set_last_index(KeychainKind::Internal, legacy_index)?;
+        }
+    }
+
+    sled_db.drop_tree(LEGACY_TREE_NAME)?;
+
+    info!("Legacy wallet migrated");
+    Ok(())
+}
+
+/// Master private key derived from mnemonic
+fn master_xprv(xkey: ExtendedKey, network: Network) -> Result<ExtendedPrivKey, WalletkaError> {
+    match xkey.into_xprv(network) {
+        Some(xprv) => Ok(xprv),
+        None => Err(WalletkaError::InvalidMnemonic {
+            details: "Mnemonic doesn't derive extended private key".to_string(),
+        }),
+    }
+}
+
+/// Wallet with descriptors used before keychains were split, passphrase was ignored
+fn legacy_bdk_wallet(
+    sled_db: &bdk::sled::Db,
+    mnemonic: &Mnemonic,
+    network: Network,
+) -> Result<BdkWallet<AnyDatabase>, WalletkaError> {
+    let xkey: ExtendedKey = mnemonic.clone().into_extended_key()?;
+    let xprv = master_xprv(xkey, network)?;
+
+    let database = AnyDatabase::from(sled_db.open_tree(LEGACY_TREE_NAME)?);
+
+    let wallet = BdkWallet::new(
+        Bip84(xprv, KeychainKind::Internal),
+        Some(Bip84(xprv, KeychainKind::Internal)),
+        network,
+        database,
+    )?;
+
+    Ok(wallet)
+}
+
+fn account_descriptor(
+    xprv: &ExtendedPrivKey,
+    account: &BitcoinAccount,
+    network: Network,
+    keychain: KeychainKind,
+) -> String {
+    let keychain = match keychain {
+        KeychainKind::External => 0,
+        KeychainKind::Internal => 1,
+    };
+    let path = format!(
+        "{}'/{}'/{}'/{}/*",
+        account.script_type.purpose(),
+        coin_type(network),
+        account.index,
+        keychain
+    );
+
+    script_descriptor(account.script_type, &format!("{xprv}/{path}"))
+}
+
+fn script_descriptor(script_type: BitcoinScriptType, key: &str) -> String {
+    match script_type {
+        BitcoinScriptType::Bip44 => format!("pkh({key})"),
+        BitcoinScriptType::Bip49 => format!("sh(wpkh({key}))"),
+        BitcoinScriptType::Bip84 => format!("wpkh({key})"),
+        BitcoinScriptType::Bip86 => format!("tr({key})"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bdk::database::SyncTime;
+    use bdk::BlockTime;
+
+    use super::*;
+    use anyhow::Result;
+
+    const MNEMONIC: &str =
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    /// Offline regtest wallet with confirmed utxo of every amount in the default account,
+    /// returned tree is the account database to store more transactions
+    fn funded_wallet(amounts: &[u64]) -> Result<(BitcoinWallet, bdk::sled::Tree)> {
+        let network = Network::Regtest;
+        let xprv = ExtendedPrivKey::new_master(network, &Mnemonic::parse(MNEMONIC)?.to_seed(""))?;
+        let account = BitcoinAccount::default();
+
+        let sled_db = bdk::sled::Config::new().temporary(true).open()?;
+        let mut tree = sled_db.open_tree(account.tree_name())?;
+        let wallet = BdkWallet::new(
+            account_descriptor(&xprv, &account, network, KeychainKind::External).as_str(),
+            Some(account_descriptor(&xprv, &account, network, KeychainKind::Internal).as_str()),
+            network,
+            AnyDatabase::from(tree.clone()),
+        )?;
+
+        tree.set_sync_time(SyncTime {
+            block_time: BlockTime {
+                height: 100,
+                timestamp: 0,
+            },
+        })?;
+        for (index, amount_sat) in amounts.iter().enumerate() {
+            let tx = Transaction {
+                version: 2,
+                lock_time: LockTime::ZERO,
+                input: vec![TxIn {
+                    previous_output: OutPoint::new(Txid::all_zeros(), index as u32),
+                    ..Default::default()
+                }],
+                output: vec![TxOut {
+                    value: *amount_sat,
+                    script_pubkey: wallet.get_address(AddressIndex::New)?.script_pubkey(),
+                }],
+            };
+            store_tx(&mut tree, &tx, 0, Some(1))?;
+        }
+
+        let wallet = BitcoinWallet {
+            accounts: vec![BitcoinAccountWallet {
+                tree_name: account.tree_name(),
+                account,
+                wallet,
+                xpub: None,
+            }],
+            active_account: 0,
+            xpub: None,
+            watch_only: false,
+            blockchain: None,
+            sled_db,
+            legacy_wallet: None,
+            frozen_utxos: HashSet::new(),
+            silent_payment_keys: None,
+        };
+
+        Ok((wallet, tree))
+    }
+
+    /// Store transaction as synced, outputs of wallet scripts become utxos
+    fn store_tx(
+        tree: &mut bdk::sled::Tree,
+        tx: &Transaction,
+        fee: u64,
+        height: Option<u32>,
+    ) -> Result<()> {
+        let mut sent = 0;
+        for txin in tx.input.iter() {
+            if let Some(mut utxo) = tree.get_utxo(&txin.previous_output)? {
+                sent += utxo.txout.value;
+                utxo.is_spent = true;
+                tree.set_utxo(&utxo)?;
+            }
+        }
+
+        let mut received = 0;
+        for (vout, txout) in tx.output.iter().enumerate() {
+            if let Some((keychain, _)) = tree.get_path_from_script_pubkey(&txout.script_pubkey)? {
+                received += txout.value;
+                tree.set_utxo(&LocalUtxo {
+                    outpoint: OutPoint::new(tx.txid(), vout as u32),
+                    txout: txout.clone(),
+                    keychain,
+                    is_spent: false,
+                })?;
+            }
+        }
+
+        tree.set_tx(&TransactionDetails {
+            transaction: Some(tx.clone()),
+            txid: tx.txid(),
+            received,
+            sent,
+            fee: Some(fee),
+            confirmation_time: height.map(|height| BlockTime {
+                height,
+                timestamp: 0,
+            }),
+        })?;
+
+        Ok(())
+    }
+
+    /// Unsigned payment of the wallet stored as unconfirmed transaction
+    fn store_payment(
+        wallet: &BitcoinWallet,
+        tree: &mut bdk::sled::Tree,
+        amount_sat: u64,
+        sat_per_vb: f32,
+    ) -> Result<Transaction> {
+        let (mut psbt, details) = wallet.build_payment(
+            test_address(0).script_pubkey(),
+            amount_sat,
+            true,
+            Some(FeePolicy::FeeRate { sat_per_vb }),
+            vec![],
+        )?;
+        wallet.sign_psbt(&mut psbt)?;
+
+        let tx = psbt.extract_tx();
+        store_tx(tree, &tx, details.fee.unwrap_or_default(), None)?;
+
+        Ok(tx)
+    }
+
+    fn test_address(index: u8) -> Address {
+        Address::p2wsh(&ScriptBuf::from(vec![index]), Network::Regtest)
+    }
+
+    #[test]
+    fn migrate_legacy_index() -> Result<()> {
+        let data_path = ".test_data_legacy";
+
+        if Path::new(data_path).exists() {
+            std::fs::remove_dir_all(data_path)?;
+        }
+
+        let sled_db = bdk::sled::open(data_path)?;
+        let mut legacy_tree = sled_db.open_tree(LEGACY_TREE_NAME)?;
+        legacy_tree.set_last_index(KeychainKind::External, 7)?;
+        legacy_tree.set_last_index(KeychainKind::Internal, 3)?;
+
+        migrate_legacy_tree(&sled_db)?;
+
+        let tree = sled_db.open_tree(BitcoinAccount::default().tree_name())?;
+        assert_eq!(Some(7), tree.get_last_index(KeychainKind::Internal)?);
+        assert!(!sled_db
+            .tree_names()
+            .iter()
+            .any(|name| name.as_ref() == LEGACY_TREE_NAME.as_bytes()));
+
+        std::fs::remove_dir_all(data_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn sync_view_shares_database() -> Result<()> {
+        let (wallet, mut tree) = funded_wallet(&[50_000])?;
+        let view = wallet.sync_view()?;
+
+        assert!(view.is_watch_only());
+        assert_eq!(wallet.get_unused_address()?, view.get_unused_address()?);
+        assert_eq!(1, view.get_utxos()?.len());
+
+        // Transactions synced by the view are seen by the wallet
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 10),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: 20_000,
+                script_pubkey: view.get_unused_address()?.script_pubkey(),
+            }],
+        };
+        store_tx(&mut tree, &tx, 0, Some(1))?;
+
+        assert_eq!(2, wallet.get_utxos()?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fee_rate_validation() -> Result<()> {
+        let (wallet, _) = funded_wallet(&[])?;
+
+        for sat_per_vb in [0.0, 0.5, -2.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                wallet.get_fee_rate(FeePolicy::FeeRate { sat_per_vb }),
+                Err(WalletkaError::InvalidInput { .. })
+            ));
+        }
+        assert_eq!(
+            FeeRate::from_sat_per_vb(2.5),
+            wallet.get_fee_rate(FeePolicy::FeeRate { sat_per_vb: 2.5 })?
+        );
+        assert!(matches!(
+            wallet.get_fee_rate(FeePolicy::ConfirmationTarget { blocks: 6 }),
+            Err(WalletkaError::Offline)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn preview_payment() -> Result<()> {
+        let (wallet, _) = funded_wallet(&[50_000, 30_000])?;
+        let fee_policy = Some(FeePolicy::FeeRate { sat_per_vb: 3.0 });
+
+        let preview = wallet.preview_payment(test_address(0), 20_000, true, fee_policy, vec![])?;
+        assert_eq!(1, preview.inputs.len());
+        assert!((preview.fee_rate - 3.0).abs() < 0.1);
+        assert_eq!(
+            preview.fee_sat as f32 / preview.vsize as f32,
+            preview.fee_rate
+        );
+
+        let outpoint = wallet
+            .get_utxos()?
+            .into_iter()
+            .find(|(_, utxo)| utxo.txout.value == 30_000)
+            .map(|(_, utxo)| utxo.outpoint)
+            .unwrap();
+        let preview =
+            wallet.preview_payment(test_address(0), 20_000, true, fee_policy, vec![outpoint])?;
+        assert_eq!(vec![outpoint.to_string()], preview.inputs);
+
+        assert!(matches!(
+            wallet.preview_payment(
+                test_address(0),
+                20_000,
+                true,
+                Some(FeePolicy::FeeRate { sat_per_vb: 0.5 }),
+                vec![]
+            ),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn bump_fee() -> Result<()> {
+        let (wallet, mut tree) = funded_wallet(&[50_000])?;
+        let funding_txid = wallet.get_utxos()?[0].1.outpoint.txid;
+        let tx = store_payment(&wallet, &mut tree, 20_000, 2.0)?;
+
+        assert!(matches!(
+            wallet.bump_fee(tx.txid(), 0.5),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            wallet.bump_fee(Txid::all_zeros(), 5.0),
+            Err(WalletkaError::NotFound { .. })
+        ));
+        assert!(matches!(
+            wallet.bump_fee(funding_txid, 5.0),
+            Err(WalletkaError::Bdk { .. })
+        ));
+        // Replacement is built and signed, only broadcast fails without blockchain
+        assert!(matches!(
+            wallet.bump_fee(tx.txid(), 5.0),
+            Err(WalletkaError::Offline)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cpfp() -> Result<()> {
+        let (wallet, mut tree) = funded_wallet(&[50_000])?;
+        let funding_txid = wallet.get_utxos()?[0].1.outpoint.txid;
+        let parent = store_payment(&wallet, &mut tree, 20_000, 5.0)?;
+
+        assert!(matches!(
+            wallet.cpfp(parent.txid(), 0.5),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            wallet.cpfp(funding_txid, 10.0),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        // Parent alone pays more than target package fee rate
+        assert!(matches!(
+            wallet.cpfp(parent.txid(), 1.5),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            wallet.cpfp(parent.txid(), 20.0),
+            Err(WalletkaError::Offline)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pay_batch_validation() -> Result<()> {
+        let (wallet, _) = funded_wallet(&[50_000])?;
+
+        assert!(matches!(
+            wallet.pay_batch(vec![], None, true, None),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            wallet.pay_batch(
+                vec![(test_address(0), 10_000)],
+                None,
+                true,
+                Some(FeePolicy::FeeRate { sat_per_vb: 0.5 })
+            ),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            wallet.pay_batch(
+                vec![(test_address(0), 10_000), (test_address(0), 10_000)],
+                Some(test_address(1)),
+                true,
+                Some(FeePolicy::FeeRate { sat_per_vb: 2.0 })
+            ),
+            Err(WalletkaError::Offline)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn batch_output_mapping() -> Result<()> {
+        let output = |index: u8, value: u64| TxOut {
+            value,
+            script_pubkey: test_address(index).script_pubkey(),
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![
+                output(0, 1_000),
+                output(1, 2_000),
+                output(0, 3_000),
+                output(0, 1_000),
+                output(2, 5_000),
+            ],
+        };
+
+        let outputs = batch_outputs(
+            &tx,
+            &[
+                (test_address(0), 1_000),
+                (test_address(0), 3_000),
+                (test_address(0), 1_000),
+                (test_address(1), 2_000),
+            ],
+            Some(&test_address(2)),
+        )?;
+        assert_eq!(
+            vec![0, 2, 3, 1, 4],
+            outputs.iter().map(|o| o.vout).collect::<Vec<_>>()
+        );
+        assert_eq!(5_000, outputs[4].amount_sat);
+        assert_eq!(test_address(2).to_string(), outputs[4].address);
+
+        // Same output can't be mapped to more recipients
+        assert!(matches!(
+            batch_outputs(
+                &tx,
+                &[(test_address(1), 2_000), (test_address(1), 2_000)],
+                None
+            ),
+            Err(WalletkaError::NotFound { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn watch_only_xpub_with_key_origin() -> Result<()> {
+        let data_path = ".test_data_watch_only";
+
+        if Path::new(data_path).exists() {
+            std::fs::remove_dir_all(data_path)?;
+        }
+
+        let secp = Secp256k1::new();
+        let xprv =
+            ExtendedPrivKey::new_master(Network::Regtest, &Mnemonic::parse(MNEMONIC)?.to_seed(""))?;
+        let path = DerivationPath::from_str("m/84'/1'/0'")?;
+        let xpub = ExtendedPubKey::from_priv(&secp, &xprv.derive_priv(&secp, &path)?);
+        let origin = format!("[{}/84'/1'/0']", xprv.fingerprint(&secp));
+
+        let wallet = BitcoinWallet::new_watch_only(
+            Network::Regtest,
+            WatchOnlySource::Xpub {
+                xpub: format!("{origin}{xpub}"),
+                script_type: BitcoinScriptType::Bip84,
+            },
+            None,
+            data_path.to_string(),
+        )?;
+
+        assert!(wallet.is_watch_only());
+        assert_eq!(Some(xpub), wallet.xpub);
+        assert!(wallet
+            .wallet()
+            .get_descriptor_for_keychain(KeychainKind::External)
+            .to_string()
+            .starts_with(&format!("wpkh({origin}{xpub}/0/*)")));
+
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        })?;
+        assert!(matches!(
+            wallet.sign_psbt(&mut psbt),
+            Err(WalletkaError::WatchOnly)
+        ));
+        assert!(matches!(
+            wallet.sign_message(
+                &wallet.get_unused_address()?,
+                "message",
+                MessageSignatureFormat::Bip322Simple
+            ),
+            Err(WalletkaError::WatchOnly)
+        ));
+
+        std::fs::remove_dir_all(data_path)?;
+
+        Ok(())
     }
 }
@@ -1,38 +1,65 @@
-use anyhow::{bail, Result};
 use cashu_sdk::{
     client::{minreq_client::HttpClient, Client},
     nuts::{Id, Proof, Token},
     url::UncheckedUrl,
     wallet::Wallet,
-    Amount,
+    Amount, Bolt11Invoice,
 };
+use chrono::{DateTime, Duration, Utc};
 use itertools::Itertools;
-use log::{debug, info};
+use log::{debug, info, warn};
 use nostr_sdk::Url;
 use std::{collections::HashMap, str::FromStr};
-use surrealdb::sql::Datetime;
+use surrealdb::sql::{Datetime, Thing};
 
-use crate::io::{
-    entities::{CashuMint, CashuProof, PendingCashuToken, PendingCashuTokenSource},
-    repositories::cashu_repository::CashuRepository,
+use crate::{
+    enums::{CashuQuoteState, TransactionDirection, TransactionStatus, WalletkaLayer},
+    errors::WalletkaError,
+    io::{
+        entities::{
+            CashuMint, CashuMintQuote, CashuOperation, CashuOperationKind, CashuProof,
+            CashuProofState, PendingCashuToken, PendingCashuTokenSource, WalletkaTransaction,
+            WalletkaTransactionEntry,
+        },
+        repositories::cashu_repository::CashuRepository,
+    },
+    types::{Amount as WalletkaAmount, Currency},
 };
 
+/// Unpaid mint quotes are polled less often after this time
+const MINT_QUOTE_EXPIRY_HOURS: i64 = 24;
+/// Expired mint quotes are still polled in this interval, the mint may have been unreachable
+const EXPIRED_MINT_QUOTES_CHECK_HOURS: i64 = 6;
+
 pub struct CashuWallet
 {
     client: HttpClient,
     repository: CashuRepository,
     mints: HashMap<String, CashuMint>,
+    /// Unredeemed sent tokens are reclaimed after this time
+    reclaim_after: Option<Duration>,
+    expired_quotes_checked_at: Option<DateTime<Utc>>,
 }
 
 impl CashuWallet
 {
-    pub async fn new(repository: CashuRepository) -> Result<Self> {
+    pub async fn new(
+        repository: CashuRepository,
+        reclaim_after_secs: Option<u64>,
+    ) -> Result<Self, WalletkaError> {
         let client = HttpClient {};
 
+        let reclaim_after = match reclaim_after_secs {
+            Some(secs) => Some(reclaim_duration(secs)?),
+            None => None,
+        };
+
         let mut wallet = Self {
             client,
             repository,
             mints: HashMap::new(),
+            reclaim_after,
+            expired_quotes_checked_at: None,
         };
 
         wallet.reload_mints().await?;
@@ -40,7 +67,300 @@ impl CashuWallet
         Ok(wallet)
     }
 
-    async fn reload_mints(&mut self) -> Result<()> {
+    /// Finish operations interrupted by crash with proofs recorded from the mint
+    /// or according to state at the mint. Operations stay journaled while the mint
+    /// is unreachable. Returns history entries of finished and failed operations
+    pub async fn reconcile_operations(
+        &mut self,
+    ) -> Result<Vec<WalletkaTransaction>, WalletkaError> {
+        let mut transactions = vec![];
+
+        for operation in self.repository.get_operations().await? {
+            if operation.failed {
+                continue;
+            }
+
+            match self.reconcile_operation(&operation).await {
+                Ok(transaction) => transactions.extend(transaction),
+                Err(err) => warn!("Can't reconcile {:?}: {}", operation.kind, err),
+            }
+        }
+
+        Ok(transactions)
+    }
+
+    async fn reconcile_operation(
+        &mut self,
+        operation: &CashuOperation,
+    ) -> Result<Option<WalletkaTransaction>, WalletkaError> {
+        if operation.new_proofs.is_some() {
+            info!("Finishing interrupted {:?}", operation.kind);
+            self.complete_operation(operation).await?;
+
+            return Ok(operation_transaction(
+                operation,
+                completed_status(operation.kind),
+            ));
+        }
+
+        match operation.kind {
+            CashuOperationKind::Send | CashuOperationKind::Melt => {
+                self.reconcile_own_proofs(operation).await?;
+
+                Ok(None)
+            }
+            CashuOperationKind::Claim | CashuOperationKind::Reclaim => {
+                self.retry_claim(operation).await
+            }
+            CashuOperationKind::Mint => self.retry_mint(operation).await,
+        }
+    }
+
+    /// Claim is retried while all proofs of its token are spendable. Token spent
+    /// meanwhile can't be claimed, the mint may have issued proofs which were lost
+    async fn retry_claim(
+        &mut self,
+        operation: &CashuOperation,
+    ) -> Result<Option<WalletkaTransaction>, WalletkaError> {
+        let token = match &operation.reference {
+            Some(token) => token.clone(),
+            None => return self.fail_operation(operation).await,
+        };
+        let decoded_token = Token::from_str(&token).map_err(|err| WalletkaError::Cashu {
+            details: err.to_string(),
+        })?;
+
+        let mut spendable = vec![];
+        let mut pending = vec![];
+        for mint_proofs in decoded_token.token {
+            let (mint_spendable, mint_pending) = self
+                .check_proofs(&mint_proofs.mint, mint_proofs.proofs)
+                .await?;
+            spendable.extend(mint_spendable);
+            pending.extend(mint_pending);
+        }
+
+        if pending.iter().any(|pending| *pending) {
+            info!("Token of interrupted {:?} is pending", operation.kind);
+            return Ok(None);
+        }
+        if spendable.iter().any(|spendable| !*spendable) {
+            warn!(
+                "Interrupted {:?} of {} sats at {} can't be retried, its new proofs may be lost",
+                operation.kind, operation.amount_sat, operation.mint_url
+            );
+            return self.fail_operation(operation).await;
+        }
+
+        info!("Retrying interrupted {:?}", operation.kind);
+        self.release_operation(operation.clone()).await?;
+        self.claim(token, operation.kind).await?;
+
+        Ok(operation_transaction(
+            operation,
+            completed_status(operation.kind),
+        ))
+    }
+
+    /// Mint is retried until its quote expires, the mint may have issued proofs
+    /// which were lost
+    async fn retry_mint(
+        &mut self,
+        operation: &CashuOperation,
+    ) -> Result<Option<WalletkaTransaction>, WalletkaError> {
+        let quote = match &operation.reference {
+            Some(quote_id) => self.repository.get_mint_quote(quote_id).await?,
+            None => None,
+        };
+        let quote = match quote {
+            Some(quote) => quote,
+            None => return self.fail_operation(operation).await,
+        };
+
+        let minted = match self
+            .get_mint_wallet(UncheckedUrl::new(quote.mint_url.clone()))
+            .await
+        {
+            Ok(wallet) => wallet
+                .mint(Amount::from_sat(quote.amount_sat), &quote.quote_id)
+                .await
+                .map_err(|err| WalletkaError::Cashu {
+                    details: err.to_string(),
+                }),
+            Err(err) => Err(err),
+        };
+
+        match minted {
+            Ok(proofs) => {
+                info!("Retried interrupted mint of quote {}", quote.quote_id);
+
+                let mut operation = operation.clone();
+                operation.new_proofs = Some(proofs.iter().map(CashuProof::from).collect());
+                self.repository.record_operation_result(&operation).await?;
+                self.complete_operation(&operation).await?;
+
+                Ok(operation_transaction(
+                    &operation,
+                    TransactionStatus::Confirmed,
+                ))
+            }
+            Err(err)
+                if Utc::now() - quote.datetime.0 > Duration::hours(MINT_QUOTE_EXPIRY_HOURS) =>
+            {
+                warn!(
+                    "Interrupted mint of quote {} can't be retried, its proofs may be lost: {}",
+                    quote.quote_id, err
+                );
+                self.fail_operation(operation).await
+            }
+            Err(err) => {
+                debug!(
+                    "Interrupted mint of quote {} not retried: {}",
+                    quote.quote_id, err
+                );
+
+                Ok(None)
+            }
+        }
+    }
+
+    /// Keep operation which can't be finished in journal for manual recovery,
+    /// mint quote is failed and sent token isn't reported redeemed.
+    /// Returns failed history entry of the operation
+    async fn fail_operation(
+        &self,
+        operation: &CashuOperation,
+    ) -> Result<Option<WalletkaTransaction>, WalletkaError> {
+        self.repository.fail_operation(operation.id.clone()).await?;
+        self.settle_reference(operation, CashuQuoteState::Failed)
+            .await?;
+
+        Ok(operation_transaction(operation, TransactionStatus::Failed))
+    }
+
+    /// Proofs not spent at the mint are released, spent proofs are marked spent.
+    /// Operation stays journaled while the mint reports its proofs pending
+    async fn reconcile_own_proofs(&self, operation: &CashuOperation) -> Result<(), WalletkaError> {
+        let proofs = self
+            .repository
+            .get_proofs_by_ids(operation.proofs.clone())
+            .await?;
+
+        let (spendable, pending) = self
+            .check_proofs(
+                &UncheckedUrl::new(operation.mint_url.clone()),
+                proofs.iter().map(|p| p.into()).collect(),
+            )
+            .await?;
+        if pending.iter().any(|pending| *pending) {
+            info!("Interrupted {:?} is still pending", operation.kind);
+            return Ok(());
+        }
+
+        let mut spent = vec![];
+        let mut released = vec![];
+        for (proof, spendable) in proofs.into_iter().zip(spendable) {
+            match spendable {
+                true => released.push(proof),
+                false => spent.push(proof),
+            }
+        }
+
+        match spent.is_empty() {
+            true => info!("Releasing proofs of interrupted {:?}", operation.kind),
+            false => warn!(
+                "Interrupted {:?} of {} sats was processed by {}, its new proofs are lost",
+                operation.kind, operation.amount_sat, operation.mint_url
+            ),
+        }
+
+        self.repository
+            .finish_operation(
+                operation.id.clone(),
+                proof_ids(&spent),
+                proof_ids(&released),
+                vec![],
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Finish operation with proofs recorded from the mint, own proofs of the operation
+    /// are spent. Returns amount of the new proofs
+    async fn complete_operation(
+        &mut self,
+        operation: &CashuOperation,
+    ) -> Result<u64, WalletkaError> {
+        let new_proofs = operation.new_proofs.clone().unwrap_or_default();
+        let amount = self
+            .add_proof_mints(
+                operation.mint_url.clone(),
+                &new_proofs.iter().map(|p| p.into()).collect::<Vec<Proof>>(),
+            )
+            .await?;
+
+        // Sent token is stored along with change
+        let token = match (operation.kind, &operation.reference) {
+            (CashuOperationKind::Send, Some(token)) => Some(PendingCashuToken {
+                id: None,
+                claimed: false,
+                datetime: Datetime::default(),
+                token: token.clone(),
+                source: PendingCashuTokenSource::Sent,
+                amount_sat: operation.amount_sat,
+            }),
+            _ => None,
+        };
+
+        self.repository
+            .finish_operation(
+                operation.id.clone(),
+                operation.proofs.clone(),
+                vec![],
+                new_proofs,
+                token,
+            )
+            .await?;
+
+        self.settle_reference(operation, CashuQuoteState::Issued)
+            .await?;
+
+        Ok(amount)
+    }
+
+    /// Set state of mint quote of the operation, reclaimed token is marked claimed
+    async fn settle_reference(
+        &self,
+        operation: &CashuOperation,
+        quote_state: CashuQuoteState,
+    ) -> Result<(), WalletkaError> {
+        match (operation.kind, &operation.reference) {
+            (CashuOperationKind::Mint, Some(quote_id)) => {
+                if let Some(mut quote) = self.repository.get_mint_quote(quote_id).await? {
+                    quote.state = quote_state;
+                    self.repository.save_mint_quote(quote).await?;
+                }
+            }
+            (CashuOperationKind::Reclaim, Some(token)) => {
+                if let Some(token) = self
+                    .repository
+                    .get_pending_token(&sha256::digest(token.as_str()))
+                    .await?
+                {
+                    self.repository
+                        .set_pending_token_claimed(token.record_id()?)
+                        .await?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    async fn reload_mints(&mut self) -> Result<(), WalletkaError> {
         let mints = self.repository.get_mints().await?;
 
         debug!("Loaded {} mints", mints.len());
@@ -54,18 +374,25 @@ impl CashuWallet
         Ok(())
     }
 
-    async fn get_mint_wallet(&self, mint_url: UncheckedUrl) -> Result<Wallet<HttpClient>> {
-        let mint_keys = self
-            .client
-            .get_mint_keys(Url::from_str(&mint_url.to_string())?)
-            .await?;
+    async fn get_mint_wallet(
+        &self,
+        mint_url: UncheckedUrl,
+    ) -> Result<Wallet<HttpClient>, WalletkaError> {
+        let url = parse_mint_url(&mint_url)?;
+        let mint_keys =
+            self.client
+                .get_mint_keys(url)
+                .await
+                .map_err(|err| WalletkaError::Cashu {
+                    details: err.to_string(),
+                })?;
 
         let wallet = Wallet::new(self.client.clone(), mint_url.clone(), mint_keys);
 
         Ok(wallet)
     }
 
-    async fn add_mint(&mut self, mint: CashuMint) -> Result<CashuMint> {
+    async fn add_mint(&mut self, mint: CashuMint) -> Result<CashuMint, WalletkaError> {
         if self.mints.contains_key(&mint.keyset_id) {
             return Ok(self.mints[&mint.keyset_id].to_owned());
         }
@@ -81,42 +408,113 @@ impl CashuWallet
         mints
     }
 
-    pub async fn claim_token(&mut self, token: String) -> Result<()> {
+    /// Claim token into the wallet, returns history entry of the claim
+    pub async fn claim_token(
+        &mut self,
+        token: String,
+    ) -> Result<WalletkaTransaction, WalletkaError> {
+        self.claim(token, CashuOperationKind::Claim).await
+    }
+
+    async fn claim(
+        &mut self,
+        token: String,
+        kind: CashuOperationKind,
+    ) -> Result<WalletkaTransaction, WalletkaError> {
         debug!("Claiming cashu token...");
 
-        let decoded_token = Token::from_str(&token)?;
-        let _memo = decoded_token.memo;
+        let decoded_token = Token::from_str(&token).map_err(|err| WalletkaError::Cashu {
+            details: err.to_string(),
+        })?;
+        let memo = decoded_token.memo;
         let proofs = decoded_token.token;
-        let mint_url = proofs.first().unwrap().mint.clone();
+        let mint_url = match proofs.first() {
+            Some(proofs) => proofs.mint.clone(),
+            None => {
+                return Err(WalletkaError::Cashu {
+                    details: "Token contains no proofs".to_string(),
+                })
+            }
+        };
 
         let wallet = self.get_mint_wallet(mint_url.clone()).await?;
+
+        let mut operation = self
+            .repository
+            .begin_operation(
+                CashuOperation {
+                    id: None,
+                    kind,
+                    mint_url: mint_url.to_string(),
+                    proofs: vec![],
+                    amount_sat: proofs
+                        .iter()
+                        .flat_map(|m| m.proofs.iter())
+                        .map(|p| p.amount.to_sat())
+                        .sum(),
+                    reference: Some(token.clone()),
+                    new_proofs: None,
+                    failed: false,
+                    datetime: Datetime::default(),
+                },
+                CashuProofState::Unspent,
+            )
+            .await?;
+
         let new_proofs = match wallet.receive(&token).await {
             Ok(proofs) => proofs,
             Err(err) => {
                 log::error!("{}", err);
-                bail!("Can't claim token!")
+                self.release_operation(operation).await?;
+
+                return Err(WalletkaError::Cashu {
+                    details: format!("Can't claim token: {}", err),
+                });
             }
         };
 
-        let amount = self
-            .store_proofs(mint_url.clone().to_string(), new_proofs)
-            .await?;
+        operation.new_proofs = Some(new_proofs.iter().map(CashuProof::from).collect());
+        self.repository.record_operation_result(&operation).await?;
+        let amount = self.complete_operation(&operation).await?;
 
         info!("Claimed {} sats from {}", amount, mint_url);
 
-        // Todo: store tx
-
-        Ok(())
+        Ok(token_transaction(
+            &token,
+            TransactionDirection::Received,
+            TransactionStatus::Confirmed,
+            amount,
+            memo,
+        ))
     }
 
-    pub async fn get_proofs(&self) -> Result<Vec<CashuProof>> {
-        self.repository.get_proofs().await
+    /// Proofs held by the wallet, including proofs used by operations in flight
+    pub async fn get_proofs(&self) -> Result<Vec<CashuProof>, WalletkaError> {
+        Ok(self
+            .repository
+            .get_proofs()
+            .await?
+            .into_iter()
+            .filter(|p| p.state != CashuProofState::Spent)
+            .collect())
     }
 
-    async fn store_proofs(&mut self, mint_url: String, proofs: Vec<Proof>) -> Result<u64> {
+    /// Add mints of unknown keysets, returns amount of the proofs
+    async fn add_proof_mints(
+        &mut self,
+        mint_url: String,
+        proofs: &[Proof],
+    ) -> Result<u64, WalletkaError> {
         let mut amount = 0;
         for proof in proofs {
-            let id = proof.id.clone().unwrap();
+            let id = match proof.id {
+                Some(id) => id,
+                None => {
+                    return Err(WalletkaError::NotFound {
+                        details: "Keyset id of proof not found".to_string(),
+                    })
+                }
+            };
             if !self.mints.contains_key(&id.to_string()) {
                 self.add_mint(CashuMint {
                     keyset_id: id.to_string(),
@@ -127,39 +525,59 @@ impl CashuWallet
             }
 
             amount += proof.amount.to_sat();
-            self.repository
-                .store_proof(CashuProof::from(&proof))
-                .await?;
         }
 
         Ok(amount)
     }
 
-    pub async fn get_pending_tokens(&self) -> Result<Vec<PendingCashuToken>> {
+    /// Keyset with the highest balance covering `amount_sat`
+    pub async fn get_keyset_for_amount(
+        &self,
+        amount_sat: u64,
+    ) -> Result<Option<String>, WalletkaError> {
+        let balances = self
+            .get_proofs()
+            .await?
+            .into_iter()
+            .filter(|p| p.state == CashuProofState::Unspent)
+            .filter_map(|p| p.keyset_id.map(|id| (id.to_string(), p.amount_sat)))
+            .into_grouping_map()
+            .sum();
+
+        Ok(balances
+            .into_iter()
+            .filter(|(keyset_id, balance)| {
+                *balance >= amount_sat && self.mints.contains_key(keyset_id)
+            })
+            .max_by_key(|(_, balance)| *balance)
+            .map(|(keyset_id, _)| keyset_id))
+    }
+
+    pub async fn get_pending_tokens(&self) -> Result<Vec<PendingCashuToken>, WalletkaError> {
         let tokens = self.repository.get_pending_tokens().await?;
 
         Ok(tokens)
     }
 
-    pub async fn create_token_from_keyset(
-        &mut self,
-        keyset_id: String,
+    /// Smallest unspent proofs of the keyset covering `amount_sat`
+    async fn select_proofs(
+        &self,
+        keyset_id: &str,
         amount_sat: u64,
-        memo: Option<String>,
-    ) -> Result<Token> {
-        let proofs = self.get_proofs().await?;
-        let mint = &self.mints[&keyset_id];
+    ) -> Result<Vec<CashuProof>, WalletkaError> {
+        let id = Id::try_from_base64(keyset_id).map_err(|err| WalletkaError::InvalidInput {
+            details: err.to_string(),
+        })?;
 
         let mut selected_proofs: Vec<CashuProof> = vec![];
         let mut value_to_send = 0;
 
-        let sorted_proofs: Vec<CashuProof> = proofs
+        let sorted_proofs: Vec<CashuProof> = self
+            .get_proofs()
+            .await?
             .into_iter()
-            .filter(|p| {
-                p.keyset_id.is_some()
-                    && p.keyset_id.unwrap() == Id::try_from_base64(&keyset_id).unwrap()
-            })
-            .sorted_by(|p1, p2| p1.amount_sat.partial_cmp(&p2.amount_sat).unwrap())
+            .filter(|p| p.state == CashuProofState::Unspent && p.keyset_id == Some(id))
+            .sorted_by_key(|p| p.amount_sat)
             .collect();
 
         for proof in sorted_proofs {
@@ -177,49 +595,1248 @@ impl CashuWallet
             }
         }
 
+        if value_to_send < amount_sat {
+            return Err(WalletkaError::InsufficientFunds {
+                details: format!("Insufficient balance of keyset {}", keyset_id),
+            });
+        }
+
         info!("Selected amount to send: {} sats", value_to_send);
 
+        Ok(selected_proofs)
+    }
+
+    /// Create token from proofs of the keyset, change is kept in the wallet.
+    /// Returns the token and history entry of the payment
+    pub async fn create_token_from_keyset(
+        &mut self,
+        keyset_id: String,
+        amount_sat: u64,
+        memo: Option<String>,
+    ) -> Result<(String, WalletkaTransaction), WalletkaError> {
+        let mint_url = match self.mints.get(&keyset_id) {
+            Some(mint) => mint.mint_url.clone(),
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Keyset {} not found", keyset_id),
+                })
+            }
+        };
+        let selected_proofs = self.select_proofs(&keyset_id, amount_sat).await?;
+        let selected_amount: u64 = selected_proofs.iter().map(|p| p.amount_sat).sum();
+
         let wallet = self
-            .get_mint_wallet(UncheckedUrl::new(mint.mint_url.clone()))
+            .get_mint_wallet(UncheckedUrl::new(mint_url.clone()))
+            .await?;
+
+        let mut operation = self
+            .repository
+            .begin_operation(
+                CashuOperation {
+                    id: None,
+                    kind: CashuOperationKind::Send,
+                    mint_url: mint_url.clone(),
+                    proofs: proof_ids(&selected_proofs),
+                    amount_sat,
+                    reference: None,
+                    new_proofs: None,
+                    failed: false,
+                    datetime: Datetime::default(),
+                },
+                CashuProofState::PendingSwap,
+            )
             .await?;
 
-        let result = wallet
+        let result = match wallet
             .send(
                 Amount::from_sat(amount_sat),
                 selected_proofs.iter().map(|p| p.into()).collect(),
             )
+            .await
+        {
+            Ok(result) => result,
+            Err(err) => {
+                warn!("Send of {} sats failed: {}", amount_sat, err);
+
+                // Mint may have swapped the proofs, operation stays journaled
+                // and is reconciled during sync when the mint can't be asked
+                if let Err(err) = self.reconcile_own_proofs(&operation).await {
+                    warn!("Can't reconcile failed send: {}", err);
+                }
+
+                return Err(WalletkaError::Cashu {
+                    details: err.to_string(),
+                });
+            }
+        };
+
+        let token = Token::new(
+            UncheckedUrl::new(mint_url.clone()),
+            result.send_proofs,
+            memo.clone(),
+        )
+        .map_err(|err| WalletkaError::Cashu {
+            details: err.to_string(),
+        })?
+        .convert_to_string()
+        .map_err(|err| WalletkaError::Cashu {
+            details: err.to_string(),
+        })?;
+
+        operation.new_proofs = Some(result.change_proofs.iter().map(CashuProof::from).collect());
+        operation.reference = Some(token.clone());
+        self.repository.record_operation_result(&operation).await?;
+        let change = self.complete_operation(&operation).await?;
+        let fee_sat = selected_amount.saturating_sub(amount_sat + change);
+
+        info!("Changed amount: {} sats, fee {} sats", change, fee_sat);
+
+        let mut transaction = token_transaction(
+            &token,
+            TransactionDirection::Sent,
+            TransactionStatus::Pending,
+            amount_sat,
+            memo,
+        );
+        transaction.fee_sat = Some(fee_sat);
+
+        Ok((token, transaction))
+    }
+
+    /// Pay Lightning invoice by proofs of the keyset, the mint returns unused fee reserve
+    /// as change. Returns history entry of the payment
+    pub async fn melt(
+        &mut self,
+        invoice: String,
+        keyset_id: String,
+    ) -> Result<WalletkaTransaction, WalletkaError> {
+        let bolt11 =
+            Bolt11Invoice::from_str(&invoice).map_err(|err| WalletkaError::InvalidInput {
+                details: err.to_string(),
+            })?;
+        let amount_sat = invoice_amount_sat(bolt11.amount_milli_satoshis())?;
+        let mint_url = match self.mints.get(&keyset_id) {
+            Some(mint) => mint.mint_url.clone(),
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Mint of keyset {} not found", keyset_id),
+                })
+            }
+        };
+
+        let wallet = self
+            .get_mint_wallet(UncheckedUrl::new(mint_url.clone()))
             .await?;
+        let fee_reserve =
+            wallet
+                .check_fee(bolt11.clone())
+                .await
+                .map_err(|err| WalletkaError::Cashu {
+                    details: err.to_string(),
+                })?;
+
+        let (mut operation, selected_proofs) = self
+            .reserve_melt_proofs(
+                mint_url.clone(),
+                &keyset_id,
+                amount_sat,
+                fee_reserve.to_sat(),
+            )
+            .await?;
+        let selected_amount: u64 = selected_proofs.iter().map(|p| p.amount_sat).sum();
+
+        let melted = match wallet
+            .melt(
+                bolt11.clone(),
+                selected_proofs.iter().map(|p| p.into()).collect(),
+                fee_reserve,
+            )
+            .await
+        {
+            Ok(melted) if melted.paid => melted,
+            Ok(_) => {
+                self.release_operation(operation).await?;
+
+                return Err(WalletkaError::Cashu {
+                    details: "Mint didn't pay the invoice".to_string(),
+                });
+            }
+            Err(err) => {
+                warn!("Melt of {} sats failed: {}", amount_sat, err);
+
+                // Payment may still be in flight, proofs stay reserved while the mint
+                // reports them pending or can't be asked
+                if let Err(err) = self.reconcile_own_proofs(&operation).await {
+                    warn!("Can't reconcile failed melt: {}", err);
+                }
+
+                return Err(WalletkaError::Cashu {
+                    details: err.to_string(),
+                });
+            }
+        };
+
+        let change_proofs = melted.change.unwrap_or_default();
+        operation.new_proofs = Some(change_proofs.iter().map(CashuProof::from).collect());
+        self.repository.record_operation_result(&operation).await?;
+        let change = self.complete_operation(&operation).await?;
+        let fee_sat = selected_amount.saturating_sub(amount_sat + change);
+
+        info!(
+            "Melted {} sats at {}, fee {} sats",
+            amount_sat, mint_url, fee_sat
+        );
+
+        let mut transaction = cashu_transaction(
+            bolt11.payment_hash().to_string(),
+            TransactionDirection::Sent,
+            TransactionStatus::Confirmed,
+            amount_sat,
+            None,
+        );
+        transaction.fee_sat = Some(fee_sat);
+        transaction.entries.push(WalletkaTransactionEntry {
+            direction: TransactionDirection::Sent,
+            address: invoice,
+            amount_sat,
+            vout: None,
+        });
+
+        Ok(transaction)
+    }
+
+    /// Reserve proofs covering invoice amount and fee reserve until the melt is finished
+    async fn reserve_melt_proofs(
+        &self,
+        mint_url: String,
+        keyset_id: &str,
+        amount_sat: u64,
+        fee_reserve_sat: u64,
+    ) -> Result<(CashuOperation, Vec<CashuProof>), WalletkaError> {
+        let selected_proofs = self
+            .select_proofs(keyset_id, amount_sat + fee_reserve_sat)
+            .await?;
+
+        let operation = self
+            .repository
+            .begin_operation(
+                CashuOperation {
+                    id: None,
+                    kind: CashuOperationKind::Melt,
+                    mint_url,
+                    proofs: proof_ids(&selected_proofs),
+                    amount_sat,
+                    reference: None,
+                    new_proofs: None,
+                    failed: false,
+                    datetime: Datetime::default(),
+                },
+                CashuProofState::Reserved,
+            )
+            .await?;
+
+        Ok((operation, selected_proofs))
+    }
+
+    /// Make proofs of operation spendable again, the mint didn't spend them
+    async fn release_operation(&self, operation: CashuOperation) -> Result<(), WalletkaError> {
+        self.repository
+            .finish_operation(operation.id, vec![], operation.proofs, vec![], None)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Mark sent tokens claimed once the mint reports all their proofs spent.
+    /// Returns history entries of redeemed tokens
+    pub async fn check_sent_tokens(&mut self) -> Result<Vec<WalletkaTransaction>, WalletkaError> {
+        let mut redeemed = vec![];
+
+        let tokens = self
+            .get_pending_tokens()
+            .await?
+            .into_iter()
+            .filter(|t| !t.claimed && matches!(t.source, PendingCashuTokenSource::Sent));
+
+        for token in tokens {
+            match self.is_token_spent(&token.token).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    warn!("Can't check state of sent token: {}", err);
+                    continue;
+                }
+            }
 
-        for used_proof in selected_proofs {
             self.repository
-                .delete_proof(used_proof.id.unwrap().id.to_string())
+                .set_pending_token_claimed(token.record_id()?)
                 .await?;
+
+            info!("Sent token of {} sats was redeemed", token.amount_sat);
+
+            redeemed.push(token_transaction(
+                &token.token,
+                TransactionDirection::Sent,
+                TransactionStatus::Confirmed,
+                token.amount_sat,
+                None,
+            ));
         }
 
-        let token_to_send = Token::new(
-            UncheckedUrl::new(mint.mint_url.clone()),
-            result.send_proofs,
-            memo,
-        )?;
+        Ok(redeemed)
+    }
 
-        // Store sent token
-        self.repository
-            .add_pending_token(PendingCashuToken {
+    /// Swap proofs of unredeemed sent token back into the wallet.
+    /// Returns history entry of the cancelled payment
+    pub async fn reclaim_token(
+        &mut self,
+        token_id: String,
+    ) -> Result<WalletkaTransaction, WalletkaError> {
+        let token = self.reclaimable_token(&token_id).await?;
+
+        // Token is marked claimed along with storing its proofs
+        self.claim(token.token.clone(), CashuOperationKind::Reclaim)
+            .await?;
+
+        info!("Reclaimed sent token of {} sats", token.amount_sat);
+
+        Ok(token_transaction(
+            &token.token,
+            TransactionDirection::Sent,
+            TransactionStatus::Failed,
+            token.amount_sat,
+            None,
+        ))
+    }
+
+    /// Sent token which wasn't redeemed yet
+    async fn reclaimable_token(&self, token_id: &str) -> Result<PendingCashuToken, WalletkaError> {
+        let token = match self.repository.get_pending_token(token_id).await? {
+            Some(token) if matches!(token.source, PendingCashuTokenSource::Sent) => token,
+            _ => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Sent token {} not found", token_id),
+                })
+            }
+        };
+        if token.claimed {
+            return Err(WalletkaError::Cashu {
+                details: "Token was already redeemed".to_string(),
+            });
+        }
+
+        Ok(token)
+    }
+
+    /// Reclaim sent tokens not redeemed within configured time
+    pub async fn reclaim_expired_tokens(
+        &mut self,
+    ) -> Result<Vec<WalletkaTransaction>, WalletkaError> {
+        let reclaim_after = match self.reclaim_after {
+            Some(reclaim_after) => reclaim_after,
+            None => return Ok(vec![]),
+        };
+
+        let expired: Vec<PendingCashuToken> = self
+            .get_pending_tokens()
+            .await?
+            .into_iter()
+            .filter(|t| {
+                !t.claimed
+                    && matches!(t.source, PendingCashuTokenSource::Sent)
+                    && Utc::now() - t.datetime.0 > reclaim_after
+            })
+            .collect();
+
+        let mut reclaimed = vec![];
+        for token in expired {
+            let token_id = token.record_id()?;
+
+            match self.reclaim_token(token_id.clone()).await {
+                Ok(transaction) => reclaimed.push(transaction),
+                Err(err) => warn!("Can't reclaim sent token {}: {}", token_id, err),
+            }
+        }
+
+        Ok(reclaimed)
+    }
+
+    /// Whether all proofs of the token are spent according to their mints,
+    /// proofs of pending melt are not redeemed yet
+    async fn is_token_spent(&self, token: &str) -> Result<bool, WalletkaError> {
+        let decoded_token = Token::from_str(token).map_err(|err| WalletkaError::Cashu {
+            details: err.to_string(),
+        })?;
+
+        for mint_proofs in decoded_token.token {
+            let (spendable, pending) = self
+                .check_proofs(&mint_proofs.mint, mint_proofs.proofs)
+                .await?;
+
+            if !proofs_redeemed(&spendable, &pending) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Whether proofs are spendable and whether they are used by pending melt,
+    /// in order of the proofs
+    async fn check_proofs(
+        &self,
+        mint_url: &UncheckedUrl,
+        proofs: Vec<Proof>,
+    ) -> Result<(Vec<bool>, Vec<bool>), WalletkaError> {
+        let status = self
+            .client
+            .post_check_spendable(
+                parse_mint_url(mint_url)?,
+                proofs.into_iter().map(|p| p.into()).collect(),
+            )
+            .await
+            .map_err(|err| WalletkaError::Cashu {
+                details: err.to_string(),
+            })?;
+
+        Ok((status.spendable, status.pending))
+    }
+
+    /// Request Lightning invoice from the mint, proofs are minted once it's paid
+    pub async fn request_mint(
+        &mut self,
+        mint_url: String,
+        amount_sat: u64,
+    ) -> Result<CashuMintQuote, WalletkaError> {
+        if amount_sat == 0 {
+            return Err(WalletkaError::InvalidInput {
+                details: "Amount must be greater than zero".to_string(),
+            });
+        }
+
+        let wallet = self
+            .get_mint_wallet(UncheckedUrl::new(mint_url.clone()))
+            .await?;
+        let response = wallet
+            .request_mint(Amount::from_sat(amount_sat))
+            .await
+            .map_err(|err| WalletkaError::Cashu {
+                details: err.to_string(),
+            })?;
+
+        info!("Requested mint of {} sats from {}", amount_sat, mint_url);
+
+        let quote = self
+            .repository
+            .save_mint_quote(CashuMintQuote {
                 id: None,
-                claimed: false,
-                datetime: Datetime::default(),
-                token: token_to_send.clone().convert_to_string().unwrap(),
-                source: PendingCashuTokenSource::Sent,
+                quote_id: response.hash,
+                mint_url,
                 amount_sat,
+                request: response.pr.to_string(),
+                state: CashuQuoteState::Unpaid,
+                datetime: Datetime::default(),
             })
             .await?;
 
-        let amount = self
-            .store_proofs(mint.mint_url.clone(), result.change_proofs)
+        Ok(quote)
+    }
+
+    pub async fn get_mint_quotes(&self) -> Result<Vec<CashuMintQuote>, WalletkaError> {
+        self.repository.get_mint_quotes().await
+    }
+
+    /// Mint proofs of the quote if its invoice is paid.
+    /// Returns updated quote and history entry of the mint when proofs were issued
+    pub async fn mint_quote(
+        &mut self,
+        quote_id: String,
+    ) -> Result<(CashuMintQuote, Option<WalletkaTransaction>), WalletkaError> {
+        let mut quote = match self.repository.get_mint_quote(&quote_id).await? {
+            Some(quote) => quote,
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Mint quote {} not found", quote_id),
+                })
+            }
+        };
+
+        if matches!(
+            quote.state,
+            CashuQuoteState::Issued | CashuQuoteState::Failed
+        ) {
+            return Ok((quote, None));
+        }
+
+        let wallet = self
+            .get_mint_wallet(UncheckedUrl::new(quote.mint_url.clone()))
+            .await?;
+
+        let mut operation = self
+            .repository
+            .begin_operation(
+                CashuOperation {
+                    id: None,
+                    kind: CashuOperationKind::Mint,
+                    mint_url: quote.mint_url.clone(),
+                    proofs: vec![],
+                    amount_sat: quote.amount_sat,
+                    reference: Some(quote.quote_id.clone()),
+                    new_proofs: None,
+                    failed: false,
+                    datetime: Datetime::default(),
+                },
+                CashuProofState::Unspent,
+            )
+            .await?;
+
+        let proofs = match wallet
+            .mint(Amount::from_sat(quote.amount_sat), &quote.quote_id)
+            .await
+        {
+            Ok(proofs) => proofs,
+            Err(err) => {
+                self.release_operation(operation).await?;
+
+                // Mint refuses to issue proofs until the invoice is paid or it may be
+                // unreachable, expired quote is still polled in case it was paid
+                debug!("Quote {} not minted: {}", quote.quote_id, err);
+
+                if quote.state == CashuQuoteState::Unpaid
+                    && Utc::now() - quote.datetime.0 > Duration::hours(MINT_QUOTE_EXPIRY_HOURS)
+                {
+                    quote.state = CashuQuoteState::Expired;
+                    quote = self.repository.save_mint_quote(quote).await?;
+                }
+
+                return Ok((quote, None));
+            }
+        };
+
+        // Quote is marked issued along with storing the proofs
+        operation.new_proofs = Some(proofs.iter().map(CashuProof::from).collect());
+        self.repository.record_operation_result(&operation).await?;
+        let amount = self.complete_operation(&operation).await?;
+
+        info!("Minted {} sats from {}", amount, quote.mint_url);
+
+        quote.state = CashuQuoteState::Issued;
+
+        let transaction = cashu_transaction(
+            quote.quote_id.clone(),
+            TransactionDirection::Received,
+            TransactionStatus::Confirmed,
+            amount,
+            None,
+        );
+
+        Ok((quote, Some(transaction)))
+    }
+
+    /// Mint proofs of all unpaid quotes which were paid in the meantime,
+    /// expired quotes are checked once in `EXPIRED_MINT_QUOTES_CHECK_HOURS`
+    pub async fn mint_paid_quotes(
+        &mut self,
+    ) -> Result<Vec<(CashuMintQuote, WalletkaTransaction)>, WalletkaError> {
+        let mut minted = vec![];
+
+        let mut quotes = self
+            .repository
+            .get_mint_quotes_by_state(CashuQuoteState::Unpaid)
+            .await?;
+        if self.expired_quotes_checked_at.map_or(true, |checked_at| {
+            Utc::now() - checked_at > Duration::hours(EXPIRED_MINT_QUOTES_CHECK_HOURS)
+        }) {
+            quotes.extend(
+                self.repository
+                    .get_mint_quotes_by_state(CashuQuoteState::Expired)
+                    .await?,
+            );
+            self.expired_quotes_checked_at = Some(Utc::now());
+        }
+
+        // Quotes of interrupted mints are retried by reconciliation
+        let journaled: Vec<String> = self
+            .repository
+            .get_operations()
+            .await?
+            .into_iter()
+            .filter(|o| matches!(o.kind, CashuOperationKind::Mint))
+            .filter_map(|o| o.reference)
+            .collect();
+        quotes.retain(|quote| !journaled.contains(&quote.quote_id));
+
+        for quote in quotes {
+            match self.mint_quote(quote.quote_id.clone()).await {
+                Ok((quote, Some(transaction))) => minted.push((quote, transaction)),
+                Ok(_) => {}
+                Err(err) => warn!("Can't check mint quote {}: {}", quote.quote_id, err),
+            }
+        }
+
+        Ok(minted)
+    }
+}
+
+/// Time after which unredeemed sent tokens are reclaimed
+pub fn reclaim_duration(secs: u64) -> Result<Duration, WalletkaError> {
+    match i64::try_from(secs).ok().and_then(Duration::try_seconds) {
+        Some(duration) => Ok(duration),
+        None => Err(WalletkaError::InvalidInput {
+            details: format!("Reclaim time of {} seconds is out of range", secs),
+        }),
+    }
+}
+
+fn parse_mint_url(mint_url: &UncheckedUrl) -> Result<Url, WalletkaError> {
+    Url::from_str(&mint_url.to_string()).map_err(|err| WalletkaError::Cashu {
+        details: err.to_string(),
+    })
+}
+
+/// Proofs are redeemed when none is spendable and none is used by pending melt,
+/// the payment of receiver may still fail and return them
+fn proofs_redeemed(spendable: &[bool], pending: &[bool]) -> bool {
+    !spendable.iter().chain(pending).any(|state| *state)
+}
+
+/// Amount of invoice in sats, proofs can't pay fractions of sat
+fn invoice_amount_sat(amount_msat: Option<u64>) -> Result<u64, WalletkaError> {
+    match amount_msat {
+        Some(amount_msat) if amount_msat % 1000 == 0 => Ok(amount_msat / 1000),
+        Some(amount_msat) => Err(WalletkaError::InvalidInput {
+            details: format!("Invoice amount {} msat is not whole sats", amount_msat),
+        }),
+        None => Err(WalletkaError::InvalidInput {
+            details: "Invoice amount is missing".to_string(),
+        }),
+    }
+}
+
+/// Mint url, amount and memo of encoded token
+pub fn decode_token(token: &str) -> Result<(String, u64, Option<String>), WalletkaError> {
+    let decoded_token = Token::from_str(token).map_err(|err| WalletkaError::Cashu {
+        details: err.to_string(),
+    })?;
+
+    let mint_url = match decoded_token.token.first() {
+        Some(mint_proofs) => mint_proofs.mint.to_string(),
+        None => {
+            return Err(WalletkaError::Cashu {
+                details: "Token contains no proofs".to_string(),
+            })
+        }
+    };
+    let amount_sat = decoded_token
+        .token
+        .iter()
+        .flat_map(|m| m.proofs.iter())
+        .map(|p| p.amount.to_sat())
+        .sum();
+
+    Ok((mint_url, amount_sat, decoded_token.memo))
+}
+
+/// Status of history entry of finished operation, reclaim cancels the sent payment
+fn completed_status(kind: CashuOperationKind) -> TransactionStatus {
+    match kind {
+        CashuOperationKind::Send => TransactionStatus::Pending,
+        CashuOperationKind::Reclaim => TransactionStatus::Failed,
+        CashuOperationKind::Melt | CashuOperationKind::Claim | CashuOperationKind::Mint => {
+            TransactionStatus::Confirmed
+        }
+    }
+}
+
+/// History entry of journaled operation, melts don't keep their invoice
+fn operation_transaction(
+    operation: &CashuOperation,
+    status: TransactionStatus,
+) -> Option<WalletkaTransaction> {
+    let reference = operation.reference.as_deref()?;
+
+    match operation.kind {
+        CashuOperationKind::Send | CashuOperationKind::Reclaim => Some(token_transaction(
+            reference,
+            TransactionDirection::Sent,
+            status,
+            operation.amount_sat,
+            None,
+        )),
+        CashuOperationKind::Claim => Some(token_transaction(
+            reference,
+            TransactionDirection::Received,
+            status,
+            operation.amount_sat,
+            None,
+        )),
+        CashuOperationKind::Mint => Some(cashu_transaction(
+            reference.to_string(),
+            TransactionDirection::Received,
+            status,
+            operation.amount_sat,
+            None,
+        )),
+        CashuOperationKind::Melt => None,
+    }
+}
+
+fn proof_ids(proofs: &[CashuProof]) -> Vec<Thing> {
+    proofs.iter().filter_map(|p| p.id.clone()).collect()
+}
+
+/// History entry of sent or claimed token, identified by hash of the token
+pub fn token_transaction(
+    token: &str,
+    direction: TransactionDirection,
+    status: TransactionStatus,
+    amount_sat: u64,
+    memo: Option<String>,
+) -> WalletkaTransaction {
+    cashu_transaction(sha256::digest(token), direction, status, amount_sat, memo)
+}
+
+fn cashu_transaction(
+    tx_id: String,
+    direction: TransactionDirection,
+    status: TransactionStatus,
+    amount_sat: u64,
+    memo: Option<String>,
+) -> WalletkaTransaction {
+    WalletkaTransaction {
+        id: None,
+        layer: WalletkaLayer::Cashu,
+        direction,
+        status,
+        tx_id: Some(tx_id),
+        amount: WalletkaAmount::new(amount_sat, Currency::bitcoin()),
+        fee_sat: None,
+        datetime: Datetime::default(),
+        contact_id: None,
+        memo,
+        entries: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use anyhow::Result;
+    use surrealdb::{engine::local::Mem, Surreal};
+
+    const KEYSET_ID: &str = "DSAl9nvvyfva";
+    /// Proofs of 1, 2, 4, 8 and 16 sats
+    const PROOFS_TOKEN: &str = "cashuAeyJ0b2tlbiI6W3sibWludCI6Imh0dHBzOi8vODMzMy5zcGFjZTozMzM4IiwicHJvb2ZzIjpbeyJpZCI6IkRTQWw5bnZ2eWZ2YSIsImFtb3VudCI6MSwic2VjcmV0IjoicHJvb2YwMSIsIkMiOiIwMmMwMjAwNjdkYjcyN2Q1ODZiYzMxODNhZWNmOTdmY2I4MDBjM2Y0Y2M0NzU5ZjY5YzYyNmM5ZGI1ZDhmNWI1ZDQifSx7ImlkIjoiRFNBbDludnZ5ZnZhIiwiYW1vdW50IjoyLCJzZWNyZXQiOiJwcm9vZjAyIiwiQyI6IjAyYzAyMDA2N2RiNzI3ZDU4NmJjMzE4M2FlY2Y5N2ZjYjgwMGMzZjRjYzQ3NTlmNjljNjI2YzlkYjVkOGY1YjVkNCJ9LHsiaWQiOiJEU0FsOW52dnlmdmEiLCJhbW91bnQiOjQsInNlY3JldCI6InByb29mMDQiLCJDIjoiMDJjMDIwMDY3ZGI3MjdkNTg2YmMzMTgzYWVjZjk3ZmNiODAwYzNmNGNjNDc1OWY2OWM2MjZjOWRiNWQ4ZjViNWQ0In0seyJpZCI6IkRTQWw5bnZ2eWZ2YSIsImFtb3VudCI6OCwic2VjcmV0IjoicHJvb2YwOCIsIkMiOiIwMmMwMjAwNjdkYjcyN2Q1ODZiYzMxODNhZWNmOTdmY2I4MDBjM2Y0Y2M0NzU5ZjY5YzYyNmM5ZGI1ZDhmNWI1ZDQifSx7ImlkIjoiRFNBbDludnZ5ZnZhIiwiYW1vdW50IjoxNiwic2VjcmV0IjoicHJvb2YxNiIsIkMiOiIwMmMwMjAwNjdkYjcyN2Q1ODZiYzMxODNhZWNmOTdmY2I4MDBjM2Y0Y2M0NzU5ZjY5YzYyNmM5ZGI1ZDhmNWI1ZDQifV19XX0=";
+
+    fn token_proofs() -> Vec<CashuProof> {
+        Token::from_str(PROOFS_TOKEN)
+            .unwrap()
+            .token
+            .iter()
+            .flat_map(|m| m.proofs.iter())
+            .map(CashuProof::from)
+            .collect()
+    }
+
+    async fn wallet_with_proofs(proofs: Vec<CashuProof>) -> Result<CashuWallet> {
+        let db = Surreal::new::<Mem>(()).await?;
+        db.use_ns("test").use_db("test").await?;
+
+        let repository = CashuRepository::new(db);
+        for proof in proofs {
+            repository.store_proof(proof).await?;
+        }
+
+        Ok(CashuWallet::new(repository, None).await?)
+    }
+
+    fn operation(
+        kind: CashuOperationKind,
+        proofs: &[CashuProof],
+        amount_sat: u64,
+        reference: Option<&str>,
+    ) -> CashuOperation {
+        CashuOperation {
+            id: None,
+            kind,
+            mint_url: "https://8333.space:3338".to_string(),
+            proofs: proof_ids(proofs),
+            amount_sat,
+            reference: reference.map(|r| r.to_string()),
+            new_proofs: None,
+            failed: false,
+            datetime: Datetime::default(),
+        }
+    }
+
+    /// Local mint answering every request with `body`
+    fn serve_mint(body: &'static str) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request);
+
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        Ok(url)
+    }
+
+    fn token(mint_url: &str, proofs: &[CashuProof]) -> Result<String> {
+        let token = Token::new(
+            UncheckedUrl::new(mint_url.to_string()),
+            proofs.iter().map(|p| p.into()).collect(),
+            None,
+        )?;
+
+        Ok(token.convert_to_string()?)
+    }
+
+    fn mint_quote(quote_id: &str, mint_url: &str, age_hours: i64) -> CashuMintQuote {
+        CashuMintQuote {
+            id: None,
+            quote_id: quote_id.to_string(),
+            mint_url: mint_url.to_string(),
+            amount_sat: 100,
+            request: format!("lnbc{}", quote_id),
+            state: CashuQuoteState::Unpaid,
+            datetime: Datetime::from(Utc::now() - Duration::hours(age_hours)),
+        }
+    }
+
+    async fn proof_states(wallet: &CashuWallet) -> Result<Vec<(u64, CashuProofState)>> {
+        Ok(wallet
+            .repository
+            .get_proofs()
+            .await?
+            .into_iter()
+            .map(|p| (p.amount_sat, p.state))
+            .sorted_by_key(|(amount_sat, _)| *amount_sat)
+            .collect())
+    }
+
+    #[test]
+    fn invoice_amount() {
+        assert_eq!(invoice_amount_sat(Some(21_000)).unwrap(), 21);
+        assert!(matches!(
+            invoice_amount_sat(Some(21_500)),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            invoice_amount_sat(None),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+    }
+
+    fn pending_token(
+        token: &str,
+        source: PendingCashuTokenSource,
+        claimed: bool,
+    ) -> PendingCashuToken {
+        PendingCashuToken {
+            id: None,
+            claimed,
+            datetime: Datetime::default(),
+            token: token.to_string(),
+            source,
+            amount_sat: 31,
+        }
+    }
+
+    #[test]
+    fn reclaim_time_range() {
+        assert_eq!(reclaim_duration(3_600).unwrap(), Duration::hours(1));
+        assert!(matches!(
+            reclaim_duration(i64::MAX as u64),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            reclaim_duration(u64::MAX),
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn reclaimable_tokens() -> Result<()> {
+        let wallet = wallet_with_proofs(token_proofs()).await?;
+
+        let unspent = wallet
+            .repository
+            .add_pending_token(pending_token(
+                PROOFS_TOKEN,
+                PendingCashuTokenSource::Sent,
+                false,
+            ))
+            .await?;
+        let redeemed = wallet
+            .repository
+            .add_pending_token(pending_token(
+                "cashuAredeemed",
+                PendingCashuTokenSource::Sent,
+                true,
+            ))
+            .await?;
+        let received = wallet
+            .repository
+            .add_pending_token(pending_token(
+                "cashuAreceived",
+                PendingCashuTokenSource::Received,
+                false,
+            ))
+            .await?;
+        let id = |token: &PendingCashuToken| sha256::digest(token.token.clone());
+
+        let token = wallet.reclaimable_token(&id(&unspent)).await?;
+        assert_eq!(PROOFS_TOKEN, token.token);
+        assert_eq!(31, token.amount_sat);
+
+        assert!(matches!(
+            wallet.reclaimable_token(&id(&redeemed)).await,
+            Err(WalletkaError::Cashu { .. })
+        ));
+        assert!(matches!(
+            wallet.reclaimable_token(&id(&received)).await,
+            Err(WalletkaError::NotFound { .. })
+        ));
+        assert!(matches!(
+            wallet.reclaimable_token("unknown").await,
+            Err(WalletkaError::NotFound { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reclaim_time_out_of_range() -> Result<()> {
+        let db = Surreal::new::<Mem>(()).await?;
+        db.use_ns("test").use_db("test").await?;
+
+        assert!(matches!(
+            CashuWallet::new(CashuRepository::new(db), Some(u64::MAX)).await,
+            Err(WalletkaError::InvalidInput { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pending_proofs_are_not_redeemed() {
+        assert!(proofs_redeemed(&[false, false], &[false, false]));
+        assert!(!proofs_redeemed(&[false, true], &[false, false]));
+        assert!(!proofs_redeemed(&[false, false], &[true, false]));
+        assert!(!proofs_redeemed(&[true, true], &[false, false]));
+    }
+
+    #[tokio::test]
+    async fn melt_reserves_proofs_with_fee_reserve() -> Result<()> {
+        let wallet = wallet_with_proofs(token_proofs()).await?;
+
+        // 5 sats and 2 sats of fee reserve are covered by the smallest proofs
+        let (operation, selected) = wallet
+            .reserve_melt_proofs("https://8333.space:3338".to_string(), KEYSET_ID, 5, 2)
             .await?;
+        assert_eq!(
+            vec![1, 2, 4],
+            selected.iter().map(|p| p.amount_sat).collect::<Vec<u64>>()
+        );
+        assert_eq!(
+            vec![
+                (1, CashuProofState::Reserved),
+                (2, CashuProofState::Reserved),
+                (4, CashuProofState::Reserved),
+                (8, CashuProofState::Unspent),
+                (16, CashuProofState::Unspent),
+            ],
+            proof_states(&wallet).await?
+        );
+        assert_eq!(1, wallet.repository.get_operations().await?.len());
 
-        info!("Changed amount: {} sats", amount);
+        // Reserved proofs can't be selected again
+        assert!(matches!(
+            wallet
+                .reserve_melt_proofs("https://8333.space:3338".to_string(), KEYSET_ID, 20, 5)
+                .await,
+            Err(WalletkaError::InsufficientFunds { .. })
+        ));
 
-        Ok(token_to_send)
+        // Proofs of unpaid melt are released
+        wallet.release_operation(operation).await?;
+        assert!(proof_states(&wallet)
+            .await?
+            .iter()
+            .all(|(_, state)| *state == CashuProofState::Unspent));
+        assert!(wallet.repository.get_operations().await?.is_empty());
+
+        let (_, selected) = wallet
+            .reserve_melt_proofs("https://8333.space:3338".to_string(), KEYSET_ID, 20, 5)
+            .await?;
+        assert_eq!(
+            vec![1, 2, 4, 8, 16],
+            selected.iter().map(|p| p.amount_sat).collect::<Vec<u64>>()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reconcile_own_proofs_by_mint_state() -> Result<()> {
+        let spendable =
+            serve_mint(r#"{"spendable":[true,true,true],"pending":[false,false,false]}"#)?;
+        let spent =
+            serve_mint(r#"{"spendable":[false,false,false],"pending":[false,false,false]}"#)?;
+        let pending = serve_mint(r#"{"spendable":[false],"pending":[true]}"#)?;
+        let wallet = wallet_with_proofs(token_proofs()).await?;
+
+        // Failed melt whose proofs the mint didn't spend is released
+        let (operation, _) = wallet
+            .reserve_melt_proofs(spendable, KEYSET_ID, 5, 2)
+            .await?;
+        wallet.reconcile_own_proofs(&operation).await?;
+        assert!(proof_states(&wallet)
+            .await?
+            .iter()
+            .all(|(_, state)| *state == CashuProofState::Unspent));
+        assert!(wallet.repository.get_operations().await?.is_empty());
+
+        // Proofs spent by the mint are marked spent
+        let (operation, _) = wallet.reserve_melt_proofs(spent, KEYSET_ID, 5, 2).await?;
+        wallet.reconcile_own_proofs(&operation).await?;
+        assert!(wallet.repository.get_operations().await?.is_empty());
+
+        // Proofs of melt in flight stay reserved
+        let (operation, _) = wallet.reserve_melt_proofs(pending, KEYSET_ID, 8, 0).await?;
+        wallet.reconcile_own_proofs(&operation).await?;
+        assert_eq!(1, wallet.repository.get_operations().await?.len());
+
+        assert_eq!(
+            vec![
+                (1, CashuProofState::Spent),
+                (2, CashuProofState::Spent),
+                (4, CashuProofState::Spent),
+                (8, CashuProofState::Reserved),
+                (16, CashuProofState::Unspent),
+            ],
+            proof_states(&wallet).await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reconcile_recorded_operations() -> Result<()> {
+        let mut proofs = token_proofs();
+        let change = proofs.pop().unwrap();
+        let mut wallet = wallet_with_proofs(proofs).await?;
+
+        // Send interrupted after the mint returned change
+        let spent = wallet.select_proofs(KEYSET_ID, 3).await?;
+        let mut send = wallet
+            .repository
+            .begin_operation(
+                operation(CashuOperationKind::Send, &spent, 3, None),
+                CashuProofState::PendingSwap,
+            )
+            .await?;
+        send.new_proofs = Some(vec![change]);
+        send.reference = Some("cashuAsent".to_string());
+        wallet.repository.record_operation_result(&send).await?;
+
+        // Mint interrupted after the mint returned proofs
+        wallet
+            .repository
+            .save_mint_quote(mint_quote("issued", "https://8333.space:3338", 0))
+            .await?;
+        let mut issued = wallet
+            .repository
+            .begin_operation(
+                operation(CashuOperationKind::Mint, &[], 100, Some("issued")),
+                CashuProofState::Unspent,
+            )
+            .await?;
+        issued.new_proofs = Some(vec![]);
+        wallet.repository.record_operation_result(&issued).await?;
+        assert_eq!(2, wallet.repository.get_operations().await?.len());
+
+        let transactions = wallet.reconcile_operations().await?;
+        assert_eq!(
+            vec![TransactionStatus::Pending, TransactionStatus::Confirmed],
+            transactions
+                .iter()
+                .map(|t| t.status)
+                .sorted_by_key(|status| *status != TransactionStatus::Pending)
+                .collect::<Vec<TransactionStatus>>()
+        );
+
+        assert_eq!(
+            vec![
+                (1, CashuProofState::Spent),
+                (2, CashuProofState::Spent),
+                (4, CashuProofState::Unspent),
+                (8, CashuProofState::Unspent),
+                (16, CashuProofState::Unspent),
+            ],
+            proof_states(&wallet).await?
+        );
+        assert!(wallet.repository.get_operations().await?.is_empty());
+
+        let tokens = wallet.get_pending_tokens().await?;
+        assert_eq!(1, tokens.len());
+        assert_eq!("cashuAsent", tokens[0].token);
+        assert_eq!(3, tokens[0].amount_sat);
+        assert!(!tokens[0].claimed);
+
+        let state = |quote: Option<CashuMintQuote>| quote.unwrap().state;
+        assert_eq!(
+            CashuQuoteState::Issued,
+            state(wallet.repository.get_mint_quote("issued").await?)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reconcile_interrupted_claims() -> Result<()> {
+        let spent = serve_mint(r#"{"spendable":[false,false],"pending":[false,false]}"#)?;
+        let pending = serve_mint(r#"{"spendable":[false],"pending":[true]}"#)?;
+        let mut wallet = wallet_with_proofs(vec![]).await?;
+
+        // Claim and reclaim interrupted before the mint returned proofs
+        let proofs = token_proofs();
+        let claimed = token(&spent, &proofs[..2])?;
+        let reclaimed = token(&spent, &proofs[2..4])?;
+        let in_melt = token(&pending, &proofs[4..])?;
+
+        wallet
+            .repository
+            .add_pending_token(pending_token(
+                &reclaimed,
+                PendingCashuTokenSource::Sent,
+                false,
+            ))
+            .await?;
+        for (kind, mint_url, token) in [
+            (CashuOperationKind::Claim, &spent, &claimed),
+            (CashuOperationKind::Reclaim, &spent, &reclaimed),
+            (CashuOperationKind::Claim, &pending, &in_melt),
+        ] {
+            wallet
+                .repository
+                .begin_operation(
+                    CashuOperation {
+                        mint_url: mint_url.clone(),
+                        ..operation(kind, &[], 3, Some(token.as_str()))
+                    },
+                    CashuProofState::Unspent,
+                )
+                .await?;
+        }
+
+        // Spent tokens can't be claimed anymore, the pending one is checked again later
+        let transactions = wallet.reconcile_operations().await?;
+        assert_eq!(
+            vec![
+                (
+                    sha256::digest(claimed.as_str()),
+                    TransactionDirection::Received
+                ),
+                (
+                    sha256::digest(reclaimed.as_str()),
+                    TransactionDirection::Sent
+                ),
+            ],
+            transactions
+                .iter()
+                .map(|t| (t.tx_id.clone().unwrap(), t.direction))
+                .sorted_by_key(|(_, direction)| *direction != TransactionDirection::Received)
+                .collect::<Vec<(String, TransactionDirection)>>()
+        );
+        assert!(transactions
+            .iter()
+            .all(|t| t.status == TransactionStatus::Failed && t.amount.value == 3));
+
+        // Reclaimed token isn't reported redeemed
+        assert!(wallet.get_pending_tokens().await?[0].claimed);
+        assert!(wallet.check_sent_tokens().await?.is_empty());
+
+        let operations = wallet.repository.get_operations().await?;
+        assert_eq!(3, operations.len());
+        for operation in operations {
+            assert_eq!(
+                operation.reference.as_deref() != Some(in_melt.as_str()),
+                operation.failed
+            );
+        }
+
+        assert!(wallet.reconcile_operations().await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reconcile_interrupted_mints() -> Result<()> {
+        let mint_url = "http://127.0.0.1:1";
+        let mut wallet = wallet_with_proofs(vec![]).await?;
+
+        // Mints interrupted before the mint returned proofs, the mint is unreachable
+        for (quote_id, age_hours) in [("recent", 0), ("expired", MINT_QUOTE_EXPIRY_HOURS + 1)] {
+            wallet
+                .repository
+                .save_mint_quote(mint_quote(quote_id, mint_url, age_hours))
+                .await?;
+            wallet
+                .repository
+                .begin_operation(
+                    CashuOperation {
+                        mint_url: mint_url.to_string(),
+                        ..operation(CashuOperationKind::Mint, &[], 100, Some(quote_id))
+                    },
+                    CashuProofState::Unspent,
+                )
+                .await?;
+        }
+
+        // Mint of recent quote is retried later, expired one can't succeed anymore
+        let transactions = wallet.reconcile_operations().await?;
+        assert_eq!(1, transactions.len());
+        assert_eq!(Some("expired".to_string()), transactions[0].tx_id);
+        assert_eq!(TransactionDirection::Received, transactions[0].direction);
+        assert_eq!(TransactionStatus::Failed, transactions[0].status);
+
+        let state = |quote: Option<CashuMintQuote>| quote.unwrap().state;
+        assert_eq!(
+            CashuQuoteState::Unpaid,
+            state(wallet.repository.get_mint_quote("recent").await?)
+        );
+        assert_eq!(
+            CashuQuoteState::Failed,
+            state(wallet.repository.get_mint_quote("expired").await?)
+        );
+
+        let operations = wallet.repository.get_operations().await?;
+        assert_eq!(2, operations.len());
+        for operation in operations {
+            assert_eq!(
+                operation.reference.as_deref() == Some("expired"),
+                operation.failed
+            );
+        }
+
+        // Quote of journaled mint isn't polled meanwhile
+        assert!(wallet.mint_paid_quotes().await?.is_empty());
+
+        Ok(())
     }
 }
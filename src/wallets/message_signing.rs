@@ -0,0 +1,405 @@
+//! Message signing with BIP322 "simple" signatures for segwit and taproot
+//! addresses and BIP137 signatures for legacy software compatibility
+
+use anyhow::{bail, Result};
+use bdk::bitcoin::{
+    absolute::LockTime,
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    blockdata::opcodes::all::{OP_PUSHBYTES_0, OP_RETURN},
+    consensus::{deserialize, serialize},
+    ecdsa,
+    hashes::{sha256, Hash, HashEngine},
+    key::TapTweak,
+    script::Builder,
+    secp256k1::{
+        ecdsa::{RecoverableSignature, RecoveryId},
+        KeyPair, Message, Secp256k1, Signing, Verification, XOnlyPublicKey,
+    },
+    sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType},
+    sign_message::signed_msg_hash,
+    taproot, Address, OutPoint, PrivateKey, PublicKey, Script, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, Txid, Witness,
+};
+
+const BIP322_TAG: &[u8] = b"BIP0322-signed-message";
+
+/// BIP137 header of P2PKH signature with uncompressed key,
+/// compressed P2PKH, P2SH-P2WPKH and P2WPKH follow by 4
+const BIP137_HEADER_BASE: u8 = 27;
+
+/// Tagged hash of the message signed by BIP322
+pub fn bip322_message_hash(message: &str) -> sha256::Hash {
+    let tag = sha256::Hash::hash(BIP322_TAG);
+
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_byte_array());
+    engine.input(tag.as_byte_array());
+    engine.input(message.as_bytes());
+
+    sha256::Hash::from_engine(engine)
+}
+
+/// Sign message with BIP322 simple signature, returns base64 encoded witness
+pub fn sign_bip322_simple<C: Signing>(
+    secp: &Secp256k1<C>,
+    address: &Address,
+    private_key: &PrivateKey,
+    message: &str,
+) -> Result<String> {
+    let script_pubkey = address.script_pubkey();
+    let to_spend = bip322_to_spend(&script_pubkey, message);
+    let to_sign = bip322_to_sign(&to_spend, Witness::new());
+
+    let public_key = private_key.public_key(secp);
+
+    let witness = if script_pubkey.is_v0_p2wpkh() {
+        if p2wpkh_script(&public_key)? != script_pubkey {
+            bail!("Private key doesn't belong to address {}", address);
+        }
+
+        let script_code = match script_pubkey.p2wpkh_script_code() {
+            Some(script_code) => script_code,
+            None => bail!("Invalid P2WPKH script"),
+        };
+        let sighash = SighashCache::new(&to_sign).segwit_signature_hash(
+            0,
+            &script_code,
+            0,
+            EcdsaSighashType::All,
+        )?;
+
+        let signature = ecdsa::Signature {
+            sig: secp.sign_ecdsa(
+                &Message::from_slice(&sighash.to_byte_array())?,
+                &private_key.inner,
+            ),
+            hash_ty: EcdsaSighashType::All,
+        };
+
+        Witness::from_slice(&[signature.to_vec(), public_key.to_bytes()])
+    } else if script_pubkey.is_v1_p2tr() {
+        let keypair = KeyPair::from_secret_key(secp, &private_key.inner);
+        let (internal_key, _) = keypair.x_only_public_key();
+
+        if ScriptBuf::new_v1_p2tr(secp, internal_key, None) != script_pubkey {
+            bail!("Private key doesn't belong to address {}", address);
+        }
+
+        let sighash = SighashCache::new(&to_sign).taproot_key_spend_signature_hash(
+            0,
+            &Prevouts::All(&to_spend.output),
+            TapSighashType::Default,
+        )?;
+
+        let tweaked_keypair = keypair.tap_tweak(secp, None).to_inner();
+        let signature = taproot::Signature {
+            sig: secp.sign_schnorr_no_aux_rand(
+                &Message::from_slice(&sighash.to_byte_array())?,
+                &tweaked_keypair,
+            ),
+            hash_ty: TapSighashType::Default,
+        };
+
+        Witness::from_slice(&[signature.to_vec()])
+    } else {
+        bail!("BIP322 simple signature is supported for P2WPKH and P2TR addresses only");
+    };
+
+    Ok(BASE64.encode(serialize(&witness)))
+}
+
+/// Verify BIP322 simple signature of P2WPKH or P2TR address
+pub fn verify_bip322_simple<C: Verification>(
+    secp: &Secp256k1<C>,
+    address: &Address,
+    message: &str,
+    signature: &str,
+) -> Result<bool> {
+    let witness: Witness = deserialize(&BASE64.decode(signature.trim())?)?;
+
+    let script_pubkey = address.script_pubkey();
+    let to_spend = bip322_to_spend(&script_pubkey, message);
+    let to_sign = bip322_to_sign(&to_spend, witness.clone());
+
+    if script_pubkey.is_v0_p2wpkh() {
+        let (signature, public_key) = match (witness.len(), witness.nth(0), witness.nth(1)) {
+            (2, Some(signature), Some(public_key)) => (
+                ecdsa::Signature::from_slice(signature)?,
+                PublicKey::from_slice(public_key)?,
+            ),
+            _ => return Ok(false),
+        };
+
+        if p2wpkh_script(&public_key)? != script_pubkey {
+            return Ok(false);
+        }
+
+        let script_code = match script_pubkey.p2wpkh_script_code() {
+            Some(script_code) => script_code,
+            None => bail!("Invalid P2WPKH script"),
+        };
+        let sighash = SighashCache::new(&to_sign).segwit_signature_hash(
+            0,
+            &script_code,
+            0,
+            signature.hash_ty,
+        )?;
+
+        Ok(secp
+            .verify_ecdsa(
+                &Message::from_slice(&sighash.to_byte_array())?,
+                &signature.sig,
+                &public_key.inner,
+            )
+            .is_ok())
+    } else if script_pubkey.is_v1_p2tr() {
+        let signature = match (witness.len(), witness.nth(0)) {
+            (1, Some(signature)) => taproot::Signature::from_slice(signature)?,
+            _ => return Ok(false),
+        };
+
+        let output_key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..])?;
+
+        let sighash = SighashCache::new(&to_sign).taproot_key_spend_signature_hash(
+            0,
+            &Prevouts::All(&to_spend.output),
+            signature.hash_ty,
+        )?;
+
+        Ok(secp
+            .verify_schnorr(
+                &signature.sig,
+                &Message::from_slice(&sighash.to_byte_array())?,
+                &output_key,
+            )
+            .is_ok())
+    } else {
+        bail!("BIP322 simple signature is supported for P2WPKH and P2TR addresses only");
+    }
+}
+
+/// Sign message with BIP137 signature of P2PKH, P2SH-P2WPKH or P2WPKH address
+pub fn sign_bip137<C: Signing>(
+    secp: &Secp256k1<C>,
+    address: &Address,
+    private_key: &PrivateKey,
+    message: &str,
+) -> Result<String> {
+    let script_pubkey = address.script_pubkey();
+    let public_key = private_key.public_key(secp);
+
+    let header = if script_pubkey == ScriptBuf::new_p2pkh(&public_key.pubkey_hash()) {
+        match public_key.compressed {
+            true => BIP137_HEADER_BASE + 4,
+            false => BIP137_HEADER_BASE,
+        }
+    } else if script_pubkey == p2shwpkh_script(&public_key)? {
+        BIP137_HEADER_BASE + 8
+    } else if script_pubkey == p2wpkh_script(&public_key)? {
+        BIP137_HEADER_BASE + 12
+    } else {
+        bail!("BIP137 signature is supported for P2PKH, P2SH-P2WPKH and P2WPKH addresses only");
+    };
+
+    let msg_hash = signed_msg_hash(message);
+    let signature = secp.sign_ecdsa_recoverable(
+        &Message::from_slice(&msg_hash.to_byte_array())?,
+        &private_key.inner,
+    );
+    let (recovery_id, compact) = signature.serialize_compact();
+
+    let mut bytes = vec![header + recovery_id.to_i32() as u8];
+    bytes.extend_from_slice(&compact);
+
+    Ok(BASE64.encode(bytes))
+}
+
+/// Verify BIP137 signature, any script type of the recovered key is accepted
+/// as some wallets don't set header flags by the address type
+pub fn verify_bip137<C: Verification>(
+    secp: &Secp256k1<C>,
+    address: &Address,
+    message: &str,
+    signature: &str,
+) -> Result<bool> {
+    let bytes = BASE64.decode(signature.trim())?;
+    if !is_bip137_signature(&bytes) {
+        bail!("Invalid BIP137 signature");
+    }
+
+    let flag = bytes[0] - BIP137_HEADER_BASE;
+    let signature =
+        RecoverableSignature::from_compact(&bytes[1..], RecoveryId::from_i32((flag % 4) as i32)?)?;
+
+    let msg_hash = signed_msg_hash(message);
+    let public_key =
+        match secp.recover_ecdsa(&Message::from_slice(&msg_hash.to_byte_array())?, &signature) {
+            Ok(public_key) => PublicKey {
+                compressed: flag >= 4,
+                inner: public_key,
+            },
+            Err(_) => return Ok(false),
+        };
+
+    let script_pubkey = address.script_pubkey();
+    if script_pubkey == ScriptBuf::new_p2pkh(&public_key.pubkey_hash()) {
+        return Ok(true);
+    }
+    if !public_key.compressed {
+        return Ok(false);
+    }
+
+    Ok(script_pubkey == p2shwpkh_script(&public_key)?
+        || script_pubkey == p2wpkh_script(&public_key)?)
+}
+
+/// BIP137 signatures are 65 bytes long with header between 27 and 42,
+/// BIP322 simple signatures are never 65 bytes long
+pub fn is_bip137_signature(bytes: &[u8]) -> bool {
+    bytes.len() == 65 && (BIP137_HEADER_BASE..BIP137_HEADER_BASE + 16).contains(&bytes[0])
+}
+
+fn bip322_to_spend(script_pubkey: &Script, message: &str) -> Transaction {
+    let message_hash = bip322_message_hash(message);
+
+    Transaction {
+        version: 0,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0xFFFFFFFF,
+            },
+            script_sig: Builder::new()
+                .push_opcode(OP_PUSHBYTES_0)
+                .push_slice(message_hash.to_byte_array())
+                .into_script(),
+            sequence: Sequence(0),
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: script_pubkey.to_owned(),
+        }],
+    }
+}
+
+fn bip322_to_sign(to_spend: &Transaction, witness: Witness) -> Transaction {
+    Transaction {
+        version: 0,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: to_spend.txid(),
+                vout: 0,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence(0),
+            witness,
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+fn p2wpkh_script(public_key: &PublicKey) -> Result<ScriptBuf> {
+    match public_key.wpubkey_hash() {
+        Some(wpubkey_hash) => Ok(ScriptBuf::new_v0_p2wpkh(&wpubkey_hash)),
+        None => bail!("Segwit requires compressed public key"),
+    }
+}
+
+fn p2shwpkh_script(public_key: &PublicKey) -> Result<ScriptBuf> {
+    Ok(ScriptBuf::new_p2sh(
+        &p2wpkh_script(public_key)?.script_hash(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bdk::bitcoin::Network;
+
+    use super::*;
+
+    // Test vectors from BIP322
+    const PRIVATE_KEY: &str = "L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k";
+    const P2WPKH_ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const P2TR_ADDRESS: &str = "bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3";
+
+    fn address(address: &str) -> Address {
+        Address::from_str(address)
+            .unwrap()
+            .require_network(Network::Bitcoin)
+            .unwrap()
+    }
+
+    #[test]
+    fn bip322_message_hashes() {
+        assert_eq!(
+            bip322_message_hash("").to_string(),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            bip322_message_hash("Hello World").to_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+    }
+
+    #[test]
+    fn verify_bip322_vectors() -> Result<()> {
+        let secp = Secp256k1::new();
+
+        assert!(verify_bip322_simple(
+            &secp,
+            &address(P2WPKH_ADDRESS),
+            "",
+            "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI="
+        )?);
+        assert!(verify_bip322_simple(
+            &secp,
+            &address(P2WPKH_ADDRESS),
+            "Hello World",
+            "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI="
+        )?);
+        assert!(!verify_bip322_simple(
+            &secp,
+            &address(P2WPKH_ADDRESS),
+            "Hello World",
+            "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI="
+        )?);
+        assert!(verify_bip322_simple(
+            &secp,
+            &address(P2TR_ADDRESS),
+            "Hello World",
+            "AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ=="
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify() -> Result<()> {
+        let secp = Secp256k1::new();
+        let private_key = PrivateKey::from_wif(PRIVATE_KEY)?;
+
+        for address in [address(P2WPKH_ADDRESS), address(P2TR_ADDRESS)] {
+            let signature = sign_bip322_simple(&secp, &address, &private_key, "Walletka")?;
+            assert!(verify_bip322_simple(
+                &secp, &address, "Walletka", &signature
+            )?);
+            assert!(!verify_bip322_simple(&secp, &address, "Other", &signature)?);
+        }
+
+        let address = address(P2WPKH_ADDRESS);
+        let signature = sign_bip137(&secp, &address, &private_key, "Walletka")?;
+        assert!(is_bip137_signature(&BASE64.decode(&signature)?));
+        assert!(verify_bip137(&secp, &address, "Walletka", &signature)?);
+        assert!(!verify_bip137(&secp, &address, "Other", &signature)?);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 pub mod bitcoin;
 pub mod cashu;
+pub mod message_signing;
 pub mod rgb;
+pub mod silent_payments;
 mod walletka_traits;
 
 pub use walletka_traits::*;
\ No newline at end of file
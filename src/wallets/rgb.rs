@@ -2,18 +2,24 @@ use std::collections::HashMap;
 use std::fs::{self};
 use std::sync::RwLock;
 
-use anyhow::{bail, Ok, Result};
-use log::info;
+use log::{info, warn};
 use rgb_lib::wallet::{
-    AssetNIA, DatabaseType, Online, ReceiveData, RefreshFilter, Unspent, Wallet, WalletData,
+    AssetNIA, DatabaseType, Invoice, InvoiceData, Online, ReceiveData, Recipient, RefreshFilter,
+    Transfer, TransferKind, Unspent, Wallet, WalletData, WitnessData,
 };
 use rgb_lib::{restore_keys, BitcoinNetwork};
 use tokio::task;
 
+use crate::errors::WalletkaError;
+
+/// Bitcoin amount sent along with assets to witness recipient
+const WITNESS_AMOUNT_SAT: u64 = 1000;
+const WITNESS_RECIPIENT_PREFIX: &str = "wvout:";
+
 pub struct RgbWallet {
     inner_wallet: Wallet,
     online: Option<Online>,
-    indexer_endpoint: Option<String>,
+    indexer_endpoints: Vec<String>,
     default_transport_endpoint: Option<String>,
     assets: RwLock<HashMap<String, AssetNIA>>, // TODO: RgbAsset struct
 }
@@ -21,11 +27,19 @@ pub struct RgbWallet {
 impl RgbWallet {
     pub async fn new(
         mnemonic: String,
+        passphrase: Option<String>,
         data_path: String,
         network: BitcoinNetwork,
-        indexer_endpoint: Option<String>,
+        indexer_endpoints: Vec<String>,
         default_transport_endpoint: Option<String>,
-    ) -> Result<Self> {
+    ) -> Result<Self, WalletkaError> {
+        // rgb-lib derives keys from the bare mnemonic, refuse to silently use other keys
+        if passphrase.is_some_and(|p| !p.is_empty()) {
+            return Err(WalletkaError::InvalidMnemonic {
+                details: "RGB wallet doesn't support BIP39 passphrase".to_string(),
+            });
+        }
+
         let data_path = format!("{data_path}/.rgb");
 
         fs::create_dir_all(data_path.clone())?;
@@ -43,36 +57,49 @@ impl RgbWallet {
             vanilla_keychain: None,
         };
 
-        let inner_wallet = task::block_in_place(move || -> Result<Wallet> {
+        let inner_wallet = task::block_in_place(move || -> Result<Wallet, WalletkaError> {
             Ok(Wallet::new(wallet_data.clone())?)
         })?;
 
         Ok(Self {
             inner_wallet,
             online: None,
-            indexer_endpoint,
+            indexer_endpoints,
             default_transport_endpoint,
             assets: RwLock::new(HashMap::new()),
         })
     }
 
-    pub fn go_online(&mut self, endpoint: Option<String>) -> Result<()> {
-        let endpoint = match endpoint {
-            Some(endpoint) => endpoint,
-            None => match self.indexer_endpoint.clone() {
-                Some(endpoint) => endpoint,
-                None => bail!("No indexer endpoint provided"),
-            },
+    /// Connect to given indexer or fail over between configured ones.
+    /// Working indexer is tried first next time
+    pub fn go_online(&mut self, endpoint: Option<String>) -> Result<(), WalletkaError> {
+        let endpoints = match endpoint {
+            Some(endpoint) => vec![endpoint],
+            None => self.indexer_endpoints.clone(),
         };
 
-        let online = self.inner_wallet.go_online(false, endpoint.clone())?;
-        self.online = Some(online);
-        self.indexer_endpoint = Some(endpoint);
+        if endpoints.is_empty() {
+            return Err(WalletkaError::InvalidInput {
+                details: "No indexer endpoint provided".to_string(),
+            });
+        }
 
-        Ok(())
+        for endpoint in endpoints {
+            match self.inner_wallet.go_online(false, endpoint.clone()) {
+                Ok(online) => {
+                    self.online = Some(online);
+                    self.indexer_endpoints.retain(|e| e != &endpoint);
+                    self.indexer_endpoints.insert(0, endpoint);
+                    return Ok(());
+                }
+                Err(err) => warn!("RGB indexer {} failed: {}", endpoint, err),
+            }
+        }
+
+        Err(WalletkaError::Offline)
     }
 
-    pub fn ensure_online(&mut self) -> Result<()> {
+    pub fn ensure_online(&mut self) -> Result<(), WalletkaError> {
         if self.online.is_none() {
             self.go_online(None)?;
         }
@@ -83,7 +110,8 @@ impl RgbWallet {
         self.assets.read().unwrap().get(&asset_id).cloned()
     }
 
-    pub fn sync(&mut self, asset_id: Option<String>, light: bool) -> Result<bool> {
+    /// Refresh transfers, returns number of refreshed transfers
+    pub fn sync(&mut self, asset_id: Option<String>, light: bool) -> Result<u64, WalletkaError> {
         self.ensure_online()?;
 
         let filter = if light {
@@ -103,11 +131,9 @@ impl RgbWallet {
 
         let res = self
             .inner_wallet
-            .refresh(self.online.clone().unwrap(), asset_id, filter)
-            .unwrap();
+            .refresh(self.online.clone().unwrap(), asset_id, filter)?;
 
-        dbg!(&res);
-        Ok(!res.is_empty()) // TODO
+        Ok(res.len() as u64)
     }
 
     pub fn update_assets(
@@ -115,7 +141,7 @@ impl RgbWallet {
         refresh: bool,
         update_transfers: bool,
         firs_refresh: bool,
-    ) -> Result<()> {
+    ) -> Result<(), WalletkaError> {
         if refresh && !firs_refresh {
             self.sync(None, false)?;
         }
@@ -156,10 +182,11 @@ impl RgbWallet {
         refresh: bool,
         update_transfers: bool,
         _update_transfers_filter: Option<String>,
-    ) -> Result<()> {
+    ) -> Result<(), WalletkaError> {
         let mut call_list_transfers = update_transfers;
         if refresh {
-            call_list_transfers = self.sync(Some(asset_id.clone()), false)? || call_list_transfers;
+            call_list_transfers =
+                self.sync(Some(asset_id.clone()), false)? > 0 || call_list_transfers;
             // Todo update balance
         }
 
@@ -170,7 +197,7 @@ impl RgbWallet {
         Ok(())
     }
 
-    pub fn create_utxos(&mut self) -> Result<()> {
+    pub fn create_utxos(&mut self) -> Result<(), WalletkaError> {
         self.ensure_online()?;
 
         self.inner_wallet
@@ -178,7 +205,7 @@ impl RgbWallet {
         Ok(())
     }
 
-    pub fn get_utxos(&self) -> Result<Vec<Unspent>> {
+    pub fn get_utxos(&self) -> Result<Vec<Unspent>, WalletkaError> {
         Ok(self.inner_wallet.list_unspents(None, false)?)
     }
 
@@ -188,7 +215,7 @@ impl RgbWallet {
         name: String,
         precision: u8,
         amount: u64,
-    ) -> Result<AssetNIA> {
+    ) -> Result<AssetNIA, WalletkaError> {
         self.ensure_online()?;
 
         let asset = self.inner_wallet.issue_asset_nia(
@@ -202,7 +229,7 @@ impl RgbWallet {
         Ok(asset)
     }
 
-    pub fn get_rgb20_assets(&self) -> Result<Vec<AssetNIA>> {
+    pub fn get_rgb20_assets(&self) -> Result<Vec<AssetNIA>, WalletkaError> {
         let assets = self.inner_wallet.list_assets(vec![])?;
 
         match assets.nia {
@@ -211,6 +238,99 @@ impl RgbWallet {
         }
     }
 
+    pub fn list_transfers(&self, asset_id: String) -> Result<Vec<Transfer>, WalletkaError> {
+        Ok(self.inner_wallet.list_transfers(Some(asset_id))?)
+    }
+
+    pub fn parse_invoice(invoice: &str) -> Result<InvoiceData, WalletkaError> {
+        Ok(Invoice::new(invoice.to_string())?.invoice_data())
+    }
+
+    /// Pay invoice, `amount` is used when the invoice doesn't specify it.
+    /// Returns the sent transfer
+    pub fn send(
+        &mut self,
+        invoice: &str,
+        amount: Option<u64>,
+        fee_rate: f32,
+    ) -> Result<(AssetNIA, Transfer), WalletkaError> {
+        let invoice = Self::parse_invoice(invoice)?;
+
+        let asset_id = match invoice.asset_id {
+            Some(asset_id) => asset_id,
+            None => {
+                return Err(WalletkaError::InvalidInput {
+                    details: "Invoice doesn't specify asset".to_string(),
+                })
+            }
+        };
+        let amount = match invoice.amount.or(amount) {
+            Some(amount) => amount,
+            None => {
+                return Err(WalletkaError::InvalidInput {
+                    details: "Amount is missing".to_string(),
+                })
+            }
+        };
+        let asset = match self
+            .get_rgb20_assets()?
+            .into_iter()
+            .find(|a| a.asset_id == asset_id)
+        {
+            Some(asset) => asset,
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Asset {} not found", asset_id),
+                })
+            }
+        };
+        if asset.balance.spendable < amount {
+            return Err(WalletkaError::InsufficientFunds {
+                details: format!("Insufficient {} balance", asset.ticker),
+            });
+        }
+
+        let witness_data = match invoice.recipient_id.starts_with(WITNESS_RECIPIENT_PREFIX) {
+            true => Some(WitnessData {
+                amount_sat: WITNESS_AMOUNT_SAT,
+                blinding: None,
+            }),
+            false => None,
+        };
+        let recipient = Recipient {
+            recipient_id: invoice.recipient_id,
+            witness_data,
+            amount,
+            transport_endpoints: invoice.transport_endpoints,
+        };
+
+        self.ensure_online()?;
+        let txid = self
+            .inner_wallet
+            .send(
+                self.online.clone().unwrap(),
+                HashMap::from([(asset_id.clone(), vec![recipient])]),
+                false,
+                fee_rate,
+                1,
+            )?
+            .txid;
+        info!("RGB transfer sent: {}", txid);
+
+        match self
+            .list_transfers(asset_id)?
+            .into_iter()
+            .find(|t| matches!(t.kind, TransferKind::Send) && t.txid.as_ref() == Some(&txid))
+        {
+            Some(transfer) => Ok((asset, transfer)),
+            None => {
+                return Err(WalletkaError::NotFound {
+                    details: format!("Sent transfer {} not found", txid),
+                })
+            }
+        }
+    }
+
     pub fn create_invoice(
         &self,
         asset_id: Option<String>,
@@ -219,7 +339,7 @@ impl RgbWallet {
         min_confirmations: Option<u8>,
         transport_url: Option<String>,
         blinded: bool,
-    ) -> Result<ReceiveData> {
+    ) -> Result<ReceiveData, WalletkaError> {
         let mut transport_endpoints = vec![];
 
         if let Some(transport_url) = transport_url {
@@ -230,7 +350,9 @@ impl RgbWallet {
             transport_endpoints.push(self.default_transport_endpoint.clone().unwrap());
         } 
         if transport_endpoints.is_empty() {
-            bail!("No transport endpoint provided");
+            return Err(WalletkaError::InvalidInput {
+                details: "No transport endpoint provided".to_string(),
+            });
         }
 
         let min_confirmations = min_confirmations.unwrap_or(1);
@@ -0,0 +1,587 @@
+//! BIP352 silent payments without labels
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use bdk::bitcoin::{
+    bech32::{self, FromBase32, ToBase32, Variant},
+    bip32::{DerivationPath, ExtendedPrivKey},
+    blockdata::script::Instruction,
+    consensus::serialize,
+    hashes::{sha256, Hash, HashEngine},
+    key::TapTweak,
+    secp256k1::{
+        KeyPair, Parity, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification,
+        XOnlyPublicKey,
+    },
+    Network, OutPoint, Script, TxIn, TxOut,
+};
+
+const MAINNET_HRP: &str = "sp";
+const TESTNET_HRP: &str = "tsp";
+const ADDRESS_VERSION: u8 = 0;
+
+const INPUTS_TAG: &[u8] = b"BIP0352/Inputs";
+const SHARED_SECRET_TAG: &[u8] = b"BIP0352/SharedSecret";
+
+/// Taproot internal key without known discrete logarithm, inputs spending with it are skipped
+const NUMS_H: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// Silent payment address, `sp1...` on mainnet and `tsp1...` on test networks
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SilentPaymentAddress {
+    pub scan_key: PublicKey,
+    pub spend_key: PublicKey,
+    pub mainnet: bool,
+}
+
+impl SilentPaymentAddress {
+    pub fn is_valid_for_network(&self, network: Network) -> bool {
+        self.mainnet == (network == Network::Bitcoin)
+    }
+}
+
+impl fmt::Display for SilentPaymentAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hrp = match self.mainnet {
+            true => MAINNET_HRP,
+            false => TESTNET_HRP,
+        };
+
+        let mut data = vec![bech32::u5::try_from_u8(ADDRESS_VERSION).unwrap()];
+        data.extend(
+            [self.scan_key.serialize(), self.spend_key.serialize()]
+                .concat()
+                .to_base32(),
+        );
+
+        match bech32::encode(hrp, data, Variant::Bech32m) {
+            Ok(address) => write!(f, "{}", address),
+            Err(_) => Err(fmt::Error),
+        }
+    }
+}
+
+impl FromStr for SilentPaymentAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(address: &str) -> Result<Self> {
+        let (hrp, data, variant) = bech32::decode(address.trim())?;
+
+        let mainnet = match hrp.as_str() {
+            MAINNET_HRP => true,
+            TESTNET_HRP => false,
+            _ => bail!("Not a silent payment address"),
+        };
+        if variant != Variant::Bech32m {
+            bail!("Silent payment address must be bech32m encoded");
+        }
+
+        let (version, payload) = match data.split_first() {
+            Some((version, payload)) => (version.to_u8(), Vec::<u8>::from_base32(payload)?),
+            None => bail!("Empty silent payment address"),
+        };
+        if version != ADDRESS_VERSION {
+            bail!("Unsupported silent payment address version {}", version);
+        }
+        if payload.len() != 66 {
+            bail!("Invalid silent payment address length");
+        }
+
+        Ok(SilentPaymentAddress {
+            scan_key: PublicKey::from_slice(&payload[..33])?,
+            spend_key: PublicKey::from_slice(&payload[33..])?,
+            mainnet,
+        })
+    }
+}
+
+/// Scan and spend keys of silent payment receiver
+pub struct SilentPaymentKeys {
+    scan_key: SecretKey,
+    spend_key: SecretKey,
+}
+
+impl SilentPaymentKeys {
+    /// Derive keys from master key, scan key `m/352'/coin'/0'/1'/0`
+    /// and spend key `m/352'/coin'/0'/0'/0`
+    pub fn from_xprv<C: Signing>(
+        secp: &Secp256k1<C>,
+        xprv: &ExtendedPrivKey,
+        coin_type: u32,
+    ) -> Result<Self> {
+        let derive = |path: String| -> Result<SecretKey> {
+            let path = DerivationPath::from_str(&path)?;
+            Ok(xprv.derive_priv(secp, &path)?.private_key)
+        };
+
+        Ok(Self {
+            scan_key: derive(format!("m/352'/{coin_type}'/0'/1'/0"))?,
+            spend_key: derive(format!("m/352'/{coin_type}'/0'/0'/0"))?,
+        })
+    }
+
+    pub fn address<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        network: Network,
+    ) -> SilentPaymentAddress {
+        SilentPaymentAddress {
+            scan_key: self.scan_key.public_key(secp),
+            spend_key: self.spend_key.public_key(secp),
+            mainnet: network == Network::Bitcoin,
+        }
+    }
+
+    /// Private key spending output found with given tweak
+    pub fn output_key(&self, tweak: [u8; 32]) -> Result<SecretKey> {
+        Ok(self.spend_key.add_tweak(&Scalar::from_be_bytes(tweak)?)?)
+    }
+
+    /// Find outputs paying to us, returns output indexes with their tweaks
+    pub fn scan<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        input_keys: &[PublicKey],
+        smallest_outpoint: &OutPoint,
+        outputs: &[TxOut],
+    ) -> Result<Vec<(usize, [u8; 32])>> {
+        if input_keys.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let input_key = PublicKey::combine_keys(&input_keys.iter().collect::<Vec<_>>())?;
+        let shared_secret = input_key
+            .mul_tweak(secp, &input_hash(smallest_outpoint, &input_key)?)?
+            .mul_tweak(secp, &Scalar::from(self.scan_key))?;
+
+        let spend_key = self.spend_key.public_key(secp);
+        let mut found = vec![];
+
+        for k in 0.. {
+            let tweak = shared_secret_tweak(&shared_secret, k);
+            let (output_key, _) = spend_key
+                .add_exp_tweak(secp, &Scalar::from_be_bytes(tweak)?)?
+                .x_only_public_key();
+
+            match outputs
+                .iter()
+                .position(|output| taproot_output_key(&output.script_pubkey) == Some(output_key))
+            {
+                Some(index) => found.push((index, tweak)),
+                None => break,
+            }
+        }
+
+        Ok(found)
+    }
+}
+
+/// Taproot output key paying to silent payment address.
+/// Input keys have to be prepared with [`input_secret_key`]
+pub fn sender_output_key<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    input_keys: &[SecretKey],
+    smallest_outpoint: &OutPoint,
+    address: &SilentPaymentAddress,
+    k: u32,
+) -> Result<XOnlyPublicKey> {
+    let (first, rest) = match input_keys.split_first() {
+        Some(keys) => keys,
+        None => bail!("Silent payment needs at least one eligible input"),
+    };
+
+    let mut input_key = *first;
+    for key in rest {
+        input_key = input_key.add_tweak(&Scalar::from(*key))?;
+    }
+
+    let input_hash = input_hash(smallest_outpoint, &input_key.public_key(secp))?;
+    let shared_secret = address
+        .scan_key
+        .mul_tweak(secp, &Scalar::from(input_key.mul_tweak(&input_hash)?))?;
+
+    let tweak = shared_secret_tweak(&shared_secret, k);
+    let (output_key, _) = address
+        .spend_key
+        .add_exp_tweak(secp, &Scalar::from_be_bytes(tweak)?)?
+        .x_only_public_key();
+
+    Ok(output_key)
+}
+
+/// Private key of input as used by silent payments, taproot keys are tweaked and have even y
+pub fn input_secret_key<C: Signing>(
+    secp: &Secp256k1<C>,
+    private_key: &SecretKey,
+    script_pubkey: &Script,
+) -> SecretKey {
+    if !script_pubkey.is_v1_p2tr() {
+        return *private_key;
+    }
+
+    let keypair = KeyPair::from_secret_key(secp, private_key)
+        .tap_tweak(secp, None)
+        .to_inner();
+    let secret_key = SecretKey::from_keypair(&keypair);
+
+    match keypair.x_only_public_key().1 {
+        Parity::Odd => secret_key.negate(),
+        Parity::Even => secret_key,
+    }
+}
+
+/// Public key of eligible input, `None` when the input can't be used for silent payments
+pub fn input_public_key(txin: &TxIn, prevout: &TxOut) -> Option<PublicKey> {
+    let script = &prevout.script_pubkey;
+
+    if script.is_v1_p2tr() {
+        let mut witness = txin.witness.to_vec();
+        // Annex
+        if witness.len() > 1 && witness.last()?.first() == Some(&0x50) {
+            witness.pop();
+        }
+        // Script path spend with NUMS internal key
+        if witness.len() > 1 && witness.last()?.get(1..33) == Some(&NUMS_H[..]) {
+            return None;
+        }
+
+        let output_key = XOnlyPublicKey::from_slice(&script.as_bytes()[2..]).ok()?;
+        return Some(PublicKey::from_x_only_public_key(output_key, Parity::Even));
+    }
+
+    if script.is_v0_p2wpkh() || script.is_p2sh() {
+        // P2SH is eligible only as P2SH-P2WPKH
+        if script.is_p2sh() {
+            let redeem_script = txin.script_sig.instructions().last()?.ok()?;
+            match redeem_script {
+                Instruction::PushBytes(bytes)
+                    if Script::from_bytes(bytes.as_bytes()).is_v0_p2wpkh() => {}
+                _ => return None,
+            }
+        }
+
+        return match (txin.witness.len(), txin.witness.nth(1)) {
+            (2, Some(key)) if key.len() == 33 => PublicKey::from_slice(key).ok(),
+            _ => None,
+        };
+    }
+
+    if script.is_p2pkh() {
+        let pubkey_hash = &script.as_bytes()[3..23];
+
+        return txin
+            .script_sig
+            .instructions()
+            .filter_map(|instruction| match instruction.ok()? {
+                Instruction::PushBytes(bytes) if bytes.len() == 33 => {
+                    let key = bdk::bitcoin::PublicKey::from_slice(bytes.as_bytes()).ok()?;
+                    match key.pubkey_hash().as_byte_array()[..] == pubkey_hash[..] {
+                        true => Some(key.inner),
+                        false => None,
+                    }
+                }
+                _ => None,
+            })
+            .last();
+    }
+
+    None
+}
+
+/// Lexicographically smallest serialized outpoint of transaction inputs
+pub fn smallest_outpoint(inputs: &[TxIn]) -> Option<OutPoint> {
+    inputs
+        .iter()
+        .map(|input| input.previous_output)
+        .min_by_key(|outpoint| serialize(outpoint))
+}
+
+fn taproot_output_key(script: &Script) -> Option<XOnlyPublicKey> {
+    match script.is_v1_p2tr() {
+        true => XOnlyPublicKey::from_slice(&script.as_bytes()[2..]).ok(),
+        false => None,
+    }
+}
+
+fn input_hash(smallest_outpoint: &OutPoint, input_key: &PublicKey) -> Result<Scalar> {
+    let hash = tagged_hash(
+        INPUTS_TAG,
+        &[serialize(smallest_outpoint), input_key.serialize().to_vec()].concat(),
+    );
+
+    Ok(Scalar::from_be_bytes(hash.to_byte_array())?)
+}
+
+fn shared_secret_tweak(shared_secret: &PublicKey, k: u32) -> [u8; 32] {
+    tagged_hash(
+        SHARED_SECRET_TAG,
+        &[shared_secret.serialize().to_vec(), k.to_be_bytes().to_vec()].concat(),
+    )
+    .to_byte_array()
+}
+
+fn tagged_hash(tag: &[u8], data: &[u8]) -> sha256::Hash {
+    let tag = sha256::Hash::hash(tag);
+
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_byte_array());
+    engine.input(tag.as_byte_array());
+    engine.input(data);
+
+    sha256::Hash::from_engine(engine)
+}
+
+#[cfg(test)]
+mod tests {
+    use bdk::bitcoin::key::TweakedPublicKey;
+    use bdk::bitcoin::script::Builder;
+    use bdk::bitcoin::{Address, ScriptBuf, Sequence, Txid, Witness};
+
+    use super::*;
+
+    // Test vector "Simple send: two inputs" from BIP352
+    const SCAN_KEY: &str = "0f694e068028a717f8af6b9411f9a133dd3565258714cc226594b34db90c1f2c";
+    const SPEND_KEY: &str = "9d6ad855ce3417ef84e836892e5a56392bfba05fa5d97ccea30e266f540e08b3";
+    const ADDRESS: &str = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+    const INPUT_KEYS: [&str; 2] = [
+        "eadc78165ff1f8ea94ad7cfdc54990738a4c53f6e0507b42154201b8e5dff3b1",
+        "93f5ed907ad5b2bdbbdcb5d9116ebc0a4e1f92f910d5260237fa45a9408aad16",
+    ];
+    const OUTPOINTS: [&str; 2] = [
+        "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16:0",
+        "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d:0",
+    ];
+    const OUTPUT_KEY: &str = "3e9fce73d4e77a4809908e3c3a2e54ee147b9312dc5044a193d1fc85de46e3c1";
+    const OUTPUT_TWEAK: &str = "f438b40179a3c4262de12986c0e6cce0634007cdc79c1dcd3e20b9ebc2e7eef6";
+
+    fn vector_keys() -> Result<SilentPaymentKeys> {
+        Ok(SilentPaymentKeys {
+            scan_key: SecretKey::from_str(SCAN_KEY)?,
+            spend_key: SecretKey::from_str(SPEND_KEY)?,
+        })
+    }
+
+    /// P2PKH inputs of the vector, script signatures carry public keys only
+    fn vector_inputs<C: Signing>(secp: &Secp256k1<C>) -> Result<Vec<(TxIn, TxOut)>> {
+        let mut inputs = vec![];
+
+        for (key, outpoint) in INPUT_KEYS.iter().zip(OUTPOINTS.iter()) {
+            let public_key =
+                bdk::bitcoin::PublicKey::new(SecretKey::from_str(key)?.public_key(secp));
+
+            inputs.push((
+                TxIn {
+                    previous_output: OutPoint::from_str(outpoint)?,
+                    script_sig: Builder::new().push_key(&public_key).into_script(),
+                    sequence: Sequence::MAX,
+                    witness: Witness::new(),
+                },
+                TxOut {
+                    value: 10_000,
+                    script_pubkey: ScriptBuf::new_p2pkh(&public_key.pubkey_hash()),
+                },
+            ));
+        }
+
+        Ok(inputs)
+    }
+
+    #[test]
+    fn bip352_address_encoding() -> Result<()> {
+        let secp = Secp256k1::new();
+        let address = vector_keys()?.address(&secp, Network::Bitcoin);
+
+        assert_eq!(ADDRESS, address.to_string());
+        assert_eq!(address, SilentPaymentAddress::from_str(ADDRESS)?);
+        assert!(address.is_valid_for_network(Network::Bitcoin));
+        assert!(!address.is_valid_for_network(Network::Testnet));
+
+        Ok(())
+    }
+
+    #[test]
+    fn bip352_sending() -> Result<()> {
+        let secp = Secp256k1::new();
+        let address = SilentPaymentAddress::from_str(ADDRESS)?;
+        let mut inputs = vector_inputs(&secp)?;
+        let mut input_keys = INPUT_KEYS
+            .iter()
+            .map(|key| SecretKey::from_str(key))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let txins = inputs
+            .iter()
+            .map(|(txin, _)| txin.clone())
+            .collect::<Vec<_>>();
+        let smallest_outpoint = smallest_outpoint(&txins).unwrap();
+        assert_eq!(OutPoint::from_str(OUTPOINTS[0])?, smallest_outpoint);
+
+        let sender_keys = input_keys
+            .iter()
+            .zip(inputs.iter())
+            .map(|(key, (_, prevout))| input_secret_key(&secp, key, &prevout.script_pubkey))
+            .collect::<Vec<_>>();
+        let output_key = sender_output_key(&secp, &sender_keys, &smallest_outpoint, &address, 0)?;
+        assert_eq!(XOnlyPublicKey::from_str(OUTPUT_KEY)?, output_key);
+
+        // Order of inputs doesn't matter
+        inputs.reverse();
+        input_keys.reverse();
+        let txins = inputs
+            .iter()
+            .map(|(txin, _)| txin.clone())
+            .collect::<Vec<_>>();
+        let output_key = sender_output_key(
+            &secp,
+            &input_keys,
+            &smallest_outpoint(&txins).unwrap(),
+            &address,
+            0,
+        )?;
+        assert_eq!(XOnlyPublicKey::from_str(OUTPUT_KEY)?, output_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bip352_receiving() -> Result<()> {
+        let secp = Secp256k1::new();
+        let keys = vector_keys()?;
+        let inputs = vector_inputs(&secp)?;
+
+        let public_keys = inputs
+            .iter()
+            .filter_map(|(txin, prevout)| input_public_key(txin, prevout))
+            .collect::<Vec<_>>();
+        assert_eq!(2, public_keys.len());
+
+        let output_key = XOnlyPublicKey::from_str(OUTPUT_KEY)?;
+        let outputs = [TxOut {
+            value: 1_000,
+            script_pubkey: ScriptBuf::new_v1_p2tr_tweaked(
+                TweakedPublicKey::dangerous_assume_tweaked(output_key),
+            ),
+        }];
+        let txins = inputs
+            .iter()
+            .map(|(txin, _)| txin.clone())
+            .collect::<Vec<_>>();
+
+        let found = keys.scan(
+            &secp,
+            &public_keys,
+            &smallest_outpoint(&txins).unwrap(),
+            &outputs,
+        )?;
+        assert_eq!(1, found.len());
+        assert_eq!(0, found[0].0);
+        assert_eq!(
+            OUTPUT_TWEAK,
+            found[0]
+                .1
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+        );
+
+        let (spend_key, _) = keys.output_key(found[0].1)?.x_only_public_key(&secp);
+        assert_eq!(output_key, spend_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_and_scan() -> Result<()> {
+        let secp = Secp256k1::new();
+        let xprv = ExtendedPrivKey::new_master(Network::Regtest, &[7; 32])?;
+
+        let keys = SilentPaymentKeys::from_xprv(&secp, &xprv, 1)?;
+        let address = keys.address(&secp, Network::Regtest);
+
+        assert!(address.to_string().starts_with("tsp1q"));
+        assert_eq!(
+            address,
+            SilentPaymentAddress::from_str(&address.to_string())?
+        );
+
+        // Sender spends P2WPKH and P2TR inputs
+        let input_keys = [
+            SecretKey::from_slice(&[1; 32])?,
+            SecretKey::from_slice(&[2; 32])?,
+        ];
+        let prevouts = [
+            TxOut {
+                value: 10_000,
+                script_pubkey: Address::p2wpkh(
+                    &bdk::bitcoin::PublicKey::new(input_keys[0].public_key(&secp)),
+                    Network::Regtest,
+                )?
+                .script_pubkey(),
+            },
+            TxOut {
+                value: 10_000,
+                script_pubkey: ScriptBuf::new_v1_p2tr(
+                    &secp,
+                    input_keys[1].x_only_public_key(&secp).0,
+                    None,
+                ),
+            },
+        ];
+        let inputs = [
+            TxIn {
+                previous_output: OutPoint::new(Txid::from_slice(&[3; 32])?, 1),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::from_slice(&[
+                    vec![0; 71],
+                    input_keys[0].public_key(&secp).serialize().to_vec(),
+                ]),
+            },
+            TxIn {
+                previous_output: OutPoint::new(Txid::from_slice(&[3; 32])?, 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::from_slice(&[vec![0; 64]]),
+            },
+        ];
+
+        let smallest_outpoint = smallest_outpoint(&inputs).unwrap();
+        assert_eq!(0, smallest_outpoint.vout);
+
+        let sender_keys = input_keys
+            .iter()
+            .zip(prevouts.iter())
+            .map(|(key, prevout)| input_secret_key(&secp, key, &prevout.script_pubkey))
+            .collect::<Vec<_>>();
+        let output_key = sender_output_key(&secp, &sender_keys, &smallest_outpoint, &address, 0)?;
+
+        let outputs = [TxOut {
+            value: 5_000,
+            script_pubkey: ScriptBuf::new_v1_p2tr_tweaked(
+                bdk::bitcoin::key::TweakedPublicKey::dangerous_assume_tweaked(output_key),
+            ),
+        }];
+
+        // Receiver sees only public data
+        let public_keys = inputs
+            .iter()
+            .zip(prevouts.iter())
+            .filter_map(|(input, prevout)| input_public_key(input, prevout))
+            .collect::<Vec<_>>();
+        assert_eq!(2, public_keys.len());
+
+        let found = keys.scan(&secp, &public_keys, &smallest_outpoint, &outputs)?;
+        assert_eq!(1, found.len());
+        assert_eq!(0, found[0].0);
+
+        let (spend_key, _) = keys.output_key(found[0].1)?.x_only_public_key(&secp);
+        assert_eq!(output_key, spend_key);
+
+        Ok(())
+    }
+}
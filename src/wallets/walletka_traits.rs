@@ -1,5 +1,5 @@
-use anyhow::Result;
+use crate::errors::WalletkaError;
 
 pub trait NestedWallet {
-    fn sync(&self) -> Result<()>;
+    fn sync(&self) -> Result<(), WalletkaError>;
 }
@@ -1,11 +1,27 @@
 use std::sync::Arc;
-use tokio::{runtime::Runtime, sync::Mutex};
+use tokio::{
+    runtime::Runtime,
+    sync::{broadcast::error::RecvError, Mutex},
+    task::JoinHandle,
+};
 
 use walletka_core::{
     bdk::bitcoin::Network,
     builder::WalletkaBuilder as BuilderSdk,
-    enums::{WalletkaAssetLocation, WalletkaAssetState, WalletkaLayer},
-    types::{Amount, Currency, WalletkaAsset, WalletkaBalance},
+    errors::WalletkaError,
+    io::clients::ChainBackend,
+    io::entities::WalletkaTransactionEntry,
+    enums::{
+        BitcoinScriptType, CashuQuoteState, FeePolicy, LayerSyncStatus, MessageSignatureFormat,
+        PaymentDestination, TransactionDirection, TransactionStatus, WalletkaAssetLocation,
+        WalletkaAssetState, WalletkaEvent, WalletkaLayer, WatchOnlySource,
+    },
+    types::{
+        Amount, BatchPaymentOutput, BatchPaymentResult, BatchRecipient, Bip21Uri, BitcoinAccount,
+        BitcoinTxPreview, CashuQuote, Currency, LayerSyncResult, PaymentReceipt, SyncReport,
+        SyncSchedulerConfig, TransactionFilter, WalletkaAsset, WalletkaBalance,
+        WalletkaHistoryItem,
+    },
     Walletka as WalletkaSdk,
 };
 
@@ -20,19 +36,19 @@ struct WalletkaBuilder {
 }
 
 impl WalletkaBuilder {
-    fn new() -> Self {
-        Self {
+    fn new() -> Result<Self, WalletkaError> {
+        Ok(Self {
             inner_builder: Mutex::new(BuilderSdk::default()),
-            rt: Runtime::new().unwrap(),
-        }
+            rt: Runtime::new()?,
+        })
     }
 
-    fn set_mnemonic(&self, mnemonic_words: String) {
+    fn set_mnemonic(&self, mnemonic_words: String, passphrase: Option<String>) {
         self.rt.block_on(async {
             self.inner_builder
                 .lock()
                 .await
-                .set_mnemonic(mnemonic_words, None)
+                .set_mnemonic(mnemonic_words, passphrase)
         });
     }
 
@@ -78,15 +94,57 @@ impl WalletkaBuilder {
         });
     }
 
-    fn build(&self) -> Arc<Walletka> {
-        let walletka_rt = Runtime::new().unwrap();
-        let walletka = walletka_rt
-            .block_on(async { self.inner_builder.lock().await.build().await.unwrap() });
+    fn set_esplora_url(&self, esplora_url: Option<String>) {
+        self.rt.block_on(async {
+            self.inner_builder
+                .lock()
+                .await
+                .set_esplora_url(esplora_url);
+        });
+    }
+
+    fn set_chain_backends(&self, chain_backends: Vec<ChainBackend>) {
+        self.rt.block_on(async {
+            self.inner_builder
+                .lock()
+                .await
+                .set_chain_backends(chain_backends);
+        });
+    }
 
-        Arc::new(Walletka {
+    fn set_bitcoin_accounts(&self, accounts: Vec<BitcoinAccount>) {
+        self.rt.block_on(async {
+            self.inner_builder
+                .lock()
+                .await
+                .set_bitcoin_accounts(accounts);
+        });
+    }
+
+    fn set_watch_only(&self, source: Option<WatchOnlySource>) {
+        self.rt.block_on(async {
+            self.inner_builder.lock().await.set_watch_only(source);
+        });
+    }
+
+    fn set_cashu_reclaim_after(&self, secs: Option<u64>) -> Result<(), WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_builder
+                .lock()
+                .await
+                .set_cashu_reclaim_after(secs)
+        })
+    }
+
+    fn build(&self) -> Result<Arc<Walletka>, WalletkaError> {
+        let walletka_rt = Runtime::new()?;
+        let walletka =
+            walletka_rt.block_on(async { self.inner_builder.lock().await.build().await })?;
+
+        Ok(Arc::new(Walletka {
             inner_wallet: Mutex::new(walletka),
             rt: walletka_rt,
-        })
+        }))
     }
 }
 
@@ -95,54 +153,481 @@ struct Walletka {
     rt: Runtime,
 }
 
+pub trait SyncProgressCallback: Send + Sync {
+    fn on_layer_progress(&self, result: LayerSyncResult);
+}
+
+pub trait WalletkaEventListener: Send + Sync {
+    fn on_event(&self, event: WalletkaEvent);
+}
+
+struct EventSubscription {
+    task: JoinHandle<()>,
+}
+
+impl EventSubscription {
+    fn cancel(&self) {
+        self.task.abort();
+    }
+}
+
 impl Walletka {
-    async fn sync(&self, light: bool) {
-        self.inner_wallet.lock().await.sync(light).await.unwrap();
+    async fn sync(&self, light: bool) -> Result<SyncReport, WalletkaError> {
+        self.inner_wallet.lock().await.sync(light).await
     }
 
-    fn get_bitcoin_address(&self) -> String {
+    /// Listener is called from background task until the subscription is cancelled
+    fn subscribe(&self, listener: Box<dyn WalletkaEventListener>) -> Arc<EventSubscription> {
+        let mut receiver = self.inner_wallet.blocking_lock().subscribe();
+
+        let task = self.rt.spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => listener.on_event(event),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        Arc::new(EventSubscription { task })
+    }
+
+    async fn sync_with_progress(
+        &self,
+        light: bool,
+        callback: Box<dyn SyncProgressCallback>,
+    ) -> Result<SyncReport, WalletkaError> {
+        self.inner_wallet
+            .lock()
+            .await
+            .sync_with_progress(light, &|result| callback.on_layer_progress(result))
+            .await
+    }
+
+    /// Replaces running scheduler, should be stopped when the app goes to background
+    fn start_sync_scheduler(&self, config: SyncSchedulerConfig) -> Result<(), WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .start_sync_scheduler(config)
+                .await
+        })
+    }
+
+    fn stop_sync_scheduler(&self) {
+        self.rt.block_on(async {
+            self.inner_wallet.lock().await.stop_sync_scheduler().await;
+        });
+    }
+
+    fn is_sync_scheduler_running(&self) -> bool {
         self.inner_wallet
             .blocking_lock()
-            .get_bitcoin_address()
-            .unwrap()
-            .to_string()
+            .is_sync_scheduler_running()
+    }
+
+    fn set_online(&self, online: bool) {
+        self.inner_wallet.blocking_lock().set_online(online);
+    }
+
+    fn is_watch_only(&self) -> bool {
+        self.inner_wallet.blocking_lock().is_watch_only()
     }
 
-    fn get_balance(&self, currency_symbol: Option<String>) -> WalletkaBalance {
+    fn get_layers(&self) -> Vec<WalletkaLayer> {
+        self.inner_wallet.blocking_lock().get_layers()
+    }
+
+    fn get_bitcoin_address(&self) -> Result<String, WalletkaError> {
+        let address = self.inner_wallet.blocking_lock().get_bitcoin_address()?;
+
+        Ok(address.to_string())
+    }
+
+    fn create_bip21_uri(
+        &self,
+        amount_sat: Option<u64>,
+        label: Option<String>,
+        message: Option<String>,
+        cashu: bool,
+        rgb_invoice: Option<String>,
+    ) -> Result<String, WalletkaError> {
+        self.inner_wallet.blocking_lock().create_bip21_uri(
+            amount_sat,
+            label,
+            message,
+            cashu,
+            rgb_invoice,
+        )
+    }
+
+    fn parse_bip21_uri(&self, uri: String) -> Result<Bip21Uri, WalletkaError> {
+        self.inner_wallet.blocking_lock().parse_bip21_uri(uri)
+    }
+
+    fn pay_to_address(
+        &self,
+        address: String,
+        amount_sat: u64,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<String>,
+    ) -> Result<String, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .pay_to_address(address, amount_sat, fee_policy, utxos)
+    }
+
+    fn preview_bitcoin_payment(
+        &self,
+        address: String,
+        amount_sat: u64,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<String>,
+    ) -> Result<BitcoinTxPreview, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .preview_bitcoin_payment(address, amount_sat, fee_policy, utxos)
+    }
+
+    fn parse_destination(&self, destination: String) -> Result<PaymentDestination, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .parse_destination(destination)
+    }
+
+    fn pay(
+        &self,
+        destination: String,
+        amount: Option<u64>,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<PaymentReceipt, WalletkaError> {
         self.rt.block_on(async {
             self.inner_wallet
                 .lock()
                 .await
-                .get_balance(currency_symbol)
+                .pay(destination, amount, fee_policy)
+                .await
+        })
+    }
+
+    fn pay_batch(
+        &self,
+        recipients: Vec<BatchRecipient>,
+        send_max_to: Option<String>,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<BatchPaymentResult, WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .pay_batch(recipients, send_max_to, fee_policy)
+                .await
+        })
+    }
+
+    fn sweep_wif(
+        &self,
+        wif: String,
+        destination: Option<String>,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<Vec<String>, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .sweep_wif(wif, destination, fee_policy)
+    }
+
+    fn drain_to(
+        &self,
+        address: String,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<String, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .drain_to(address, fee_policy)
+    }
+
+    fn pay_payjoin_uri(
+        &self,
+        uri: String,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<String, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .pay_payjoin_uri(uri, fee_policy)
+    }
+
+    fn get_payjoin_uri(
+        &self,
+        endpoint: String,
+        amount_sat: Option<u64>,
+    ) -> Result<String, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .get_payjoin_uri(endpoint, amount_sat)
+    }
+
+    fn create_payjoin_proposal(
+        &self,
+        original_psbt: String,
+        query: String,
+    ) -> Result<String, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .create_payjoin_proposal(original_psbt, query)
+    }
+
+    fn sign_message(
+        &self,
+        address: String,
+        message: String,
+        format: MessageSignatureFormat,
+    ) -> Result<String, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .sign_message(address, message, format)
+    }
+
+    fn verify_message(
+        &self,
+        address: String,
+        message: String,
+        signature: String,
+    ) -> Result<bool, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .verify_message(address, message, signature)
+    }
+
+    fn get_silent_payment_address(&self) -> Result<String, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .get_silent_payment_address()
+    }
+
+    fn scan_silent_payments(
+        &self,
+        from_height: u32,
+        to_height: Option<u32>,
+    ) -> Result<Vec<String>, WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .scan_silent_payments(from_height, to_height)
                 .await
-                .unwrap()
         })
     }
 
-    fn get_assets(&self) -> Vec<WalletkaAsset> {
-        let assets = self
-            .rt
-            .block_on(async { self.inner_wallet.lock().await.get_assets().await.unwrap() });
+    fn sweep_silent_payments(
+        &self,
+        fee_policy: Option<FeePolicy>,
+    ) -> Result<Option<String>, WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .sweep_silent_payments(fee_policy)
+                .await
+        })
+    }
 
-        assets
+    fn set_utxo_frozen(&self, outpoint: String, frozen: bool) -> Result<(), WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .set_utxo_frozen(outpoint, frozen)
+                .await
+        })
     }
 
-    fn claim_cashu_token(&self, token: String) {
+    fn set_utxo_label(&self, outpoint: String, label: Option<String>) -> Result<(), WalletkaError> {
         self.rt.block_on(async {
             self.inner_wallet
                 .lock()
                 .await
-                .claim_cashu_token(token)
+                .set_utxo_label(outpoint, label)
                 .await
-                .unwrap();
         })
     }
 
-    fn create_rgb_utxos(&self) {
+    fn estimate_bitcoin_fee_rate(&self, target_blocks: u32) -> Result<f32, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .estimate_bitcoin_fee_rate(target_blocks)
+    }
+
+    fn create_psbt(
+        &self,
+        address: String,
+        amount_sat: u64,
+        fee_policy: Option<FeePolicy>,
+        utxos: Vec<String>,
+    ) -> Result<String, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .create_psbt(address, amount_sat, fee_policy, utxos)
+    }
+
+    fn sign_psbt(&self, psbt: String) -> Result<String, WalletkaError> {
+        self.inner_wallet.blocking_lock().sign_psbt_base64(psbt)
+    }
+
+    fn combine_psbts(&self, psbts: Vec<String>) -> Result<String, WalletkaError> {
+        self.inner_wallet.blocking_lock().combine_psbts(psbts)
+    }
+
+    fn finalize_psbt(&self, psbt: String) -> Result<String, WalletkaError> {
+        self.inner_wallet.blocking_lock().finalize_psbt(psbt)
+    }
+
+    fn broadcast_psbt(&self, psbt: String) -> Result<String, WalletkaError> {
+        self.inner_wallet.blocking_lock().broadcast_psbt(psbt)
+    }
+
+    fn bump_fee(&self, txid: String, new_fee_rate: f32) -> Result<String, WalletkaError> {
         self.inner_wallet
             .blocking_lock()
-            .create_rgb_utxos()
-            .unwrap();
+            .bump_fee(txid, new_fee_rate)
+    }
+
+    fn cpfp(&self, txid: String, package_fee_rate: f32) -> Result<String, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .cpfp(txid, package_fee_rate)
+    }
+
+    fn get_bitcoin_accounts(&self) -> Vec<BitcoinAccount> {
+        self.inner_wallet.blocking_lock().get_bitcoin_accounts()
+    }
+
+    fn set_bitcoin_account(&self, account: BitcoinAccount) -> Result<(), WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .set_bitcoin_account(account)
+    }
+
+    fn sweep_legacy_bitcoin_wallet(&self) -> Result<Option<String>, WalletkaError> {
+        self.inner_wallet
+            .blocking_lock()
+            .sweep_legacy_bitcoin_wallet()
+    }
+
+    fn get_balance(
+        &self,
+        currency_symbol: Option<String>,
+    ) -> Result<WalletkaBalance, WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .get_balance(currency_symbol)
+                .await
+        })
+    }
+
+    fn get_transactions(
+        &self,
+        filter: TransactionFilter,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<WalletkaHistoryItem>, WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .get_transactions(filter, offset, limit)
+                .await
+        })
+    }
+
+    fn set_transaction_details(
+        &self,
+        id: String,
+        contact_id: Option<String>,
+        memo: Option<String>,
+    ) -> Result<WalletkaHistoryItem, WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .set_transaction_details(id, contact_id, memo)
+                .await
+        })
+    }
+
+    fn get_assets(&self) -> Result<Vec<WalletkaAsset>, WalletkaError> {
+        self.rt
+            .block_on(async { self.inner_wallet.lock().await.get_assets().await })
+    }
+
+    fn claim_cashu_token(&self, token: String) -> Result<(), WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .claim_cashu_token(token)
+                .await
+        })
+    }
+
+    fn reclaim_cashu_token(&self, token_id: String) -> Result<WalletkaHistoryItem, WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .reclaim_cashu_token(token_id)
+                .await
+        })
+    }
+
+    fn melt_cashu(
+        &self,
+        invoice: String,
+        keyset_id: String,
+    ) -> Result<PaymentReceipt, WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .melt_cashu(invoice, keyset_id)
+                .await
+        })
+    }
+
+    fn request_cashu_mint(
+        &self,
+        mint_url: String,
+        amount_sat: u64,
+    ) -> Result<CashuQuote, WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .request_cashu_mint(mint_url, amount_sat)
+                .await
+        })
+    }
+
+    fn check_cashu_mint_quote(&self, quote_id: String) -> Result<CashuQuote, WalletkaError> {
+        self.rt.block_on(async {
+            self.inner_wallet
+                .lock()
+                .await
+                .check_cashu_mint_quote(quote_id)
+                .await
+        })
+    }
+
+    fn get_cashu_mint_quotes(&self) -> Result<Vec<CashuQuote>, WalletkaError> {
+        self.rt
+            .block_on(async { self.inner_wallet.lock().await.get_cashu_mint_quotes().await })
+    }
+
+    fn create_rgb_utxos(&self) -> Result<(), WalletkaError> {
+        self.inner_wallet.blocking_lock().create_rgb_utxos()
     }
 
     fn create_rgb_invoice(
@@ -153,18 +638,15 @@ impl Walletka {
         min_confirmations: Option<u8>,
         transport_url: Option<String>,
         blinded: bool,
-    ) -> String {
-        self.inner_wallet
-            .blocking_lock()
-            .create_rgb_invoice(
-                asset_id,
-                amount,
-                duration_seconds,
-                min_confirmations,
-                transport_url,
-                blinded,
-            )
-            .unwrap()
+    ) -> Result<String, WalletkaError> {
+        self.inner_wallet.blocking_lock().create_rgb_invoice(
+            asset_id,
+            amount,
+            duration_seconds,
+            min_confirmations,
+            transport_url,
+            blinded,
+        )
     }
 
     fn issue_rgb20_asset(
@@ -173,10 +655,9 @@ impl Walletka {
         name: String,
         precision: u8,
         amount: u64,
-    ) -> String {
+    ) -> Result<String, WalletkaError> {
         self.inner_wallet
             .blocking_lock()
             .issue_rgb20_asset(ticker, name, precision, amount)
-            .unwrap()
     }
 }